@@ -0,0 +1,334 @@
+//! Parsing and checking test results reported in APA style, such as "t(38) = 2.41, p = .021".
+//!
+//! Each result gives a test statistic with its degrees of freedom and a p-value. The p-value is
+//! recomputed over every statistic which rounds to the reported one, and compared with the
+//! reported p-value as in `statcheck`.
+
+use std::sync::LazyLock;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use regex::{Captures, Regex};
+
+use crate::confusion::ReportedMetric;
+use crate::distributions::{gamma_inc_upper, normal_sf};
+use crate::rivets::{rounding_interval, RIVETSInput};
+use crate::statcheck::{abs_range, check_alpha, compare, f_p, t_p, Recomputed, StatcheckError};
+
+/// Matches a test statistic, its degrees of freedom in parentheses, and its p-value. The df of a
+/// chi-squared test may be followed by the sample size, as in "χ²(1, N = 90)".
+static APA_RESULT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)
+        (?P<test>\bt|\bF|\br|χ²|χ2|\bchi2|\bX2|\bz|\bZ)\s*
+        (?:\(\s*
+            (?P<df1>\d+(?:\.\d+)?)\s*
+            (?:,\s*(?:N\s*=\s*\d+|(?P<df2>\d+(?:\.\d+)?))\s*)?
+        \))?
+        \s*=\s*
+        (?P<statistic>[-−–]?\s*(?:\d+\.?\d*|\.\d+))
+        \s*[,;]\s*
+        p\s*(?P<comparison><=|>=|≤|≥|=|<|>)\s*
+        (?P<p>\d*\.\d+|\d+)",
+    )
+    .expect("the APA result pattern is valid")
+});
+
+/// The tests whose results can be parsed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TestType {
+    T,
+    F,
+    R,
+    ChiSquared,
+    Z,
+}
+
+impl TestType {
+    fn new(name: &str) -> Self {
+        match name {
+            "t" => TestType::T,
+            "F" => TestType::F,
+            "r" => TestType::R,
+            "z" | "Z" => TestType::Z,
+            _ => TestType::ChiSquared,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TestType::T => "t",
+            TestType::F => "F",
+            TestType::R => "r",
+            TestType::ChiSquared => "chi2",
+            TestType::Z => "z",
+        }
+    }
+
+    /// The two-sided p-value of a statistic of this test with these degrees of freedom. F and
+    /// chi-squared tests have only an upper tail.
+    fn p(&self, statistic: f64, df1: f64, df2: f64) -> f64 {
+        match self {
+            TestType::T => t_p(statistic, df1),
+            TestType::F => f_p(statistic, df1, df2),
+            TestType::R if statistic.abs() >= 1.0 => 0.0,
+            TestType::R => t_p(statistic * (df1 / (1.0 - statistic.powi(2))).sqrt(), df1),
+            TestType::ChiSquared => gamma_inc_upper(df1 / 2.0, statistic / 2.0),
+            TestType::Z => 2.0 * normal_sf(statistic.abs()),
+        }
+    }
+}
+
+/// A test result parsed from APA-style text, with its numbers kept as reported.
+#[derive(Debug, PartialEq)]
+pub struct ApaResult {
+    pub test: TestType,
+    pub df1: Option<f64>,
+    pub df2: Option<f64>,
+    pub statistic: String,
+    /// How the p-value compares with the reported one: "=", "<" or ">".
+    pub comparison: &'static str,
+    pub p: String,
+    /// The text the result was parsed from.
+    pub text: String,
+}
+
+impl ApaResult {
+    /// Reads a match of `APA_RESULT`, unless the test has the wrong number of degrees of
+    /// freedom: two for F, none for z and one otherwise.
+    fn new(captures: &Captures) -> Option<Self> {
+        let test = TestType::new(&captures["test"]);
+        let df = |name: &str| captures.name(name).and_then(|df| df.as_str().parse().ok());
+        let (df1, df2) = (df("df1"), df("df2"));
+        let valid = match test {
+            TestType::F => df1.is_some() && df2.is_some(),
+            TestType::Z => df1.is_none(),
+            _ => df1.is_some() && df2.is_none(),
+        };
+        if !valid {
+            return None;
+        }
+
+        let statistic = captures["statistic"]
+            .replace(['−', '–'], "-")
+            .split_whitespace()
+            .collect();
+        let comparison = match &captures["comparison"] {
+            "<" | "<=" | "≤" => "<",
+            ">" | ">=" | "≥" => ">",
+            _ => "=",
+        };
+        Some(ApaResult {
+            test,
+            df1,
+            df2,
+            statistic,
+            comparison,
+            p: captures["p"].to_string(),
+            text: captures[0].to_string(),
+        })
+    }
+
+    /// Recomputes the p-value at the reported statistic and over the statistics which round to
+    /// it. Every p-value falls as the absolute value of the statistic grows.
+    fn recompute(&self, rounding: &str, threshold: f64) -> Result<Recomputed, StatcheckError> {
+        let (df1, df2) = (self.df1.unwrap_or(f64::NAN), self.df2.unwrap_or(f64::NAN));
+        let p = |statistic: f64| self.test.p(statistic, df1, df2);
+
+        let reported = RIVETSInput::Str(self.statistic.clone());
+        let bounds = rounding_interval(&reported, rounding, threshold)?;
+        let statistic = reported.to_str().parse::<f64>().unwrap_or(f64::NAN).abs();
+        let statistic_range = abs_range((bounds.lower, bounds.upper));
+
+        Ok(Recomputed {
+            statistic,
+            statistic_range,
+            p: p(statistic),
+            p_range: (p(statistic_range.1), p(statistic_range.0)),
+        })
+    }
+
+    /// The reported p-value in the form `ReportedP` reads, e.g. "<.05".
+    fn reported_p(&self) -> ReportedMetric {
+        let comparison = if self.comparison == "=" { "" } else { self.comparison };
+        ReportedMetric::Str(format!("{comparison}{}", self.p))
+    }
+}
+
+/// Finds every APA-style test result in `text`, in order. Results with the wrong number of
+/// degrees of freedom for their test are skipped.
+pub fn parse_apa_results(text: &str) -> Vec<ApaResult> {
+    APA_RESULT
+        .captures_iter(text)
+        .filter_map(|captures| ApaResult::new(&captures))
+        .collect()
+}
+
+fn to_dict<'py>(py: Python<'py>, result: &ApaResult) -> PyResult<Bound<'py, PyDict>> {
+    let dict = PyDict::new(py);
+    dict.set_item("Test", result.test.name())?;
+    dict.set_item("DF1", result.df1)?;
+    dict.set_item("DF2", result.df2)?;
+    dict.set_item("Statistic", &result.statistic)?;
+    dict.set_item("Comparison", result.comparison)?;
+    dict.set_item("P", &result.p)?;
+    dict.set_item("Text", &result.text)?;
+    Ok(dict)
+}
+
+#[pyfunction(signature = (text))]
+/// Extracts the test results reported in APA style from a text.
+///
+/// Parameters:
+///     text (str): Text containing results such as "t(38) = 2.41, p = .021",
+///     "F(2, 57) = 3.9, p < .05", "r(48) = -.32, p = .02", "χ²(1, N = 90) = 4.2, p = .04" or
+///     "z = 2.1, p = .036".
+///
+/// Returns:
+///     List[dict]: One record per result, in order, with Test ("t", "F", "r", "chi2" or "z"),
+///     DF1 and DF2, the degrees of freedom (None where the test has fewer), Statistic and P,
+///     the reported values as strings, Comparison, the operator before the p-value ("=", "<" or
+///     ">"), and Text, the matched text.
+///
+/// Usage Example:
+///     >>> from scrutipy import parse_apa
+///     >>> res = parse_apa("t(38) = 2.41, p = .021 and F(2, 57) = 3.9, p < .05")
+///     >>> [(r["Test"], r["DF1"], r["DF2"], r["Statistic"], r["Comparison"], r["P"]) for r in res]
+///     [('t', 38.0, None, '2.41', '=', '.021'), ('F', 2.0, 57.0, '3.9', '<', '.05')]
+///
+/// Notes:
+///     - Results with the wrong number of degrees of freedom for their test, such as an F with
+///     only one, are skipped. "≤" and "≥" are read as "<" and ">".
+pub fn parse_apa(py: Python, text: &str) -> PyResult<Vec<PyObject>> {
+    parse_apa_results(text)
+        .iter()
+        .map(|result| Ok(to_dict(py, result)?.into()))
+        .collect()
+}
+
+#[pyfunction(signature = (text, alpha=0.05, rounding="up_or_down", threshold=5.0))]
+/// Extracts the test results reported in APA style from a text and checks each reported
+/// p-value against the one recomputed from the statistic and its degrees of freedom.
+///
+/// Parameters:
+///     text (str): Text containing results, as in `parse_apa()`.
+///     alpha (float): The significance level for decision errors. Defaults to 0.05.
+///     rounding (str): The rounding scheme of the reported statistics and p-values, one of
+///     "up_or_down", "up", "down", "even", "ceiling", "floor", "trunc" and "anti_trunc".
+///     Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///
+/// Returns:
+///     List[dict]: The records of `parse_apa()`, with Computed_P, the two-sided p-value of the
+///     reported statistic, P_Min and P_Max, its range over the statistics which round to the
+///     reported one, Consistent, whether the reported p-value agrees with that range, and
+///     Decision_Error, True if it is inconsistent and no possible p-value agrees with it on
+///     significance at alpha.
+///
+/// Raises:
+///     ValueError: If alpha is not between 0 and 1, or the rounding scheme is invalid for a
+///     result in the text.
+///
+/// Usage Example:
+///     >>> from scrutipy import apa_check
+///     >>> res = apa_check("t(38) = 2.41, p = .021; t(38) = 1.20, p = .04")
+///     >>> [(r["Consistent"], r["Decision_Error"]) for r in res]
+///     [(True, False), (False, True)]
+///
+/// Notes:
+///     - p-values are two-sided for t, r and z, and upper-tailed for F and chi-squared. r is
+///     tested with t = r * sqrt(df / (1 - r^2)) on its df.
+pub fn apa_check(py: Python, text: &str, alpha: f64, rounding: &str, threshold: f64) -> PyResult<Vec<PyObject>> {
+    check_alpha(alpha)?;
+
+    parse_apa_results(text)
+        .iter()
+        .map(|result| {
+            let recomputed = result.recompute(rounding, threshold)?;
+            let comparison = compare(&recomputed, None, Some(&result.reported_p()), alpha, rounding, threshold)?;
+
+            let dict = to_dict(py, result)?;
+            dict.set_item("Computed_P", recomputed.p)?;
+            dict.set_item("P_Min", recomputed.p_range.0)?;
+            dict.set_item("P_Max", recomputed.p_range.1)?;
+            dict.set_item("Consistent", comparison.p)?;
+            dict.set_item("Decision_Error", comparison.decision_error)?;
+            Ok(dict.into())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn parse_one(text: &str) -> ApaResult {
+        let mut results = parse_apa_results(text);
+        assert_eq!(results.len(), 1, "{text}");
+        results.remove(0)
+    }
+
+    #[test]
+    fn parse_tests() {
+        let t = parse_one("as predicted, t(38) = 2.41, p = .021.");
+        assert_eq!((t.test, t.df1, t.df2), (TestType::T, Some(38.0), None));
+        assert_eq!((t.statistic.as_str(), t.comparison, t.p.as_str()), ("2.41", "=", ".021"));
+        assert_eq!(t.text, "t(38) = 2.41, p = .021");
+
+        let f = parse_one("F(2, 57) = 3.9, p < .05");
+        assert_eq!((f.test, f.df1, f.df2, f.comparison), (TestType::F, Some(2.0), Some(57.0), "<"));
+        let welch = parse_one("t(17.35) = −1.20, p ≥ .05");
+        assert_eq!((welch.df1, welch.statistic.as_str(), welch.comparison), (Some(17.35), "-1.20", ">"));
+        let chi2 = parse_one("χ²(1, N = 90) = 4.2, p = .04");
+        assert_eq!((chi2.test, chi2.df1, chi2.df2), (TestType::ChiSquared, Some(1.0), None));
+        assert_eq!(parse_one("r(48) = -.32, p = .02").statistic, "-.32");
+        assert_eq!(parse_one("z = 2.1, p = .036").test, TestType::Z);
+    }
+
+    #[test]
+    fn parse_skips() {
+        // F needs two degrees of freedom, and z none
+        assert!(parse_apa_results("F(2) = 3.9, p < .05").is_empty());
+        assert!(parse_apa_results("z(3) = 2.1, p = .036").is_empty());
+        // a t inside a word is not a test
+        assert!(parse_apa_results("at(38) = 2.41, p = .021").is_empty());
+        assert_eq!(parse_apa_results("t(38) = 2.41, p = .021; F(2, 57) = 3.9, p < .05").len(), 2);
+    }
+
+    #[test]
+    fn p_values() {
+        // the critical values of each test at 0.05
+        assert!((TestType::T.p(2.024394164, 38.0, f64::NAN) - 0.05).abs() < 1e-9);
+        assert!((TestType::Z.p(-1.959963985, f64::NAN, f64::NAN) - 0.05).abs() < 1e-9);
+        assert!((TestType::ChiSquared.p(3.841458821, 1.0, f64::NAN) - 0.05).abs() < 1e-9);
+        // with 2 df, the chi-squared p-value is exp(-x / 2)
+        assert!((TestType::ChiSquared.p(5.0, 2.0, f64::NAN) - (-2.5f64).exp()).abs() < 1e-12);
+        // and keeps its precision far in the tail
+        assert!((TestType::ChiSquared.p(90.0, 2.0, f64::NAN) / (-45f64).exp() - 1.0).abs() < 1e-12);
+        assert!((TestType::Z.p(10.0, f64::NAN, f64::NAN) / 1.5239706048321054e-23 - 1.0).abs() < 1e-10);
+        // r = t / sqrt(df + t^2)
+        let r = 2.024394164 / (38.0 + 2.024394164f64.powi(2)).sqrt();
+        assert!((TestType::R.p(r, 38.0, f64::NAN) - 0.05).abs() < 1e-9);
+        assert_eq!(TestType::R.p(-1.0, 38.0, f64::NAN), 0.0);
+    }
+
+    #[test]
+    fn recomputed_range() {
+        let t = parse_one("t(38) = 2.41, p = .021");
+        let recomputed = t.recompute("up_or_down", 5.0).unwrap();
+        assert!((recomputed.statistic_range.0 - 2.405).abs() < 1e-12);
+        assert!((recomputed.statistic_range.1 - 2.415).abs() < 1e-12);
+        assert!(recomputed.p_range.0 < recomputed.p && recomputed.p < recomputed.p_range.1);
+        let comparison = compare(&recomputed, None, Some(&t.reported_p()), 0.05, "up_or_down", 5.0).unwrap();
+        assert_eq!((comparison.p, comparison.decision_error), (Some(true), Some(false)));
+
+        let t = parse_one("t(38) = 1.20, p = .04");
+        let recomputed = t.recompute("up_or_down", 5.0).unwrap();
+        let comparison = compare(&recomputed, None, Some(&t.reported_p()), 0.05, "up_or_down", 5.0).unwrap();
+        assert_eq!((comparison.p, comparison.decision_error), (Some(false), Some(true)));
+
+        // a statistic which rounds from either side of zero
+        let z = parse_one("z = 0.0, p > .05");
+        assert_eq!(z.recompute("up_or_down", 5.0).unwrap().p_range.1, 1.0);
+    }
+}
@@ -14,10 +14,31 @@
 
 use num::{Float, FromPrimitive, Integer, NumCast, ToPrimitive};
 use std::collections::VecDeque;
+use std::time::Instant;
 use rayon::prelude::*;
-use pyo3::pyfunction;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyString;
+use pyo3::{pyfunction, IntoPyObject};
+use thiserror::Error;
+use crate::closure_checkpoint::{check_shard, dfs_checkpointed};
+use crate::progress::Progress;
+use crate::utils::stream_rng;
 
-#[pyfunction(signature = (mean, sd, n, scale_min, scale_max, rounding_error_mean = 0.05, rounding_error_sd = 0.05))]
+/// The samples returned by `closure()`.
+///
+/// With `items = 1` every observation is an integer on the scale, so samples are returned as
+/// integers. With `items > 1` the observations are means of several items and are returned as
+/// floats on the grid with step `1 / items`.
+#[derive(IntoPyObject)]
+pub enum ClosureResults {
+    Integer(Vec<Vec<i32>>),
+    Items(Vec<Vec<f64>>),
+}
+
+#[pyfunction(signature = (mean, sd, n, scale_min, scale_max, rounding_error_mean = 0.05, rounding_error_sd = 0.05, items = 1, budget = None, on_budget = "warn", checkpoint = None, resume = false, shard_index = 0, num_shards = 1, progress = None))]
 #[cfg(not(tarpaulin_include))]
 /// A Python implementation of the CLOSURE algorithm for reconstructing datasets from summary
 /// statistics. 
@@ -30,9 +51,37 @@ use pyo3::pyfunction;
 ///     scale_max (int): The maximum scale value (inclusive).
 ///     rounding_error_mean (float): The allowable rounding error for the mean.
 ///     rounding_error_sd (float): The allowable rounding error for the standard deviation.
+///     items (int): The number of items averaged into each observation. Defaults to 1. For a
+///     multi-item scale, observations lie on a grid from scale_min to scale_max with step 1/items.
+///     budget (Optional[float]): A time budget in seconds. If given, the runtime is first
+///     estimated with `closure_estimate()`, and `on_budget` decides what happens if the estimate
+///     exceeds the budget. Defaults to None, which skips the estimate.
+///     on_budget (str): Either "warn", to issue a warning and run anyway, or "error", to raise a
+///     ValueError instead of running. Defaults to "warn".
+///     checkpoint (Optional[str]): A path to a checkpoint file. Each branch of the search is
+///     recorded in the file as soon as it finishes. Defaults to None, which runs without a checkpoint.
+///     resume (bool): Whether to continue from an existing checkpoint file, skipping finished
+///     branches. Defaults to False, in which case an existing checkpoint file raises a ValueError.
+///     shard_index (int): Which shard of the search to run, from 0 to num_shards - 1. Defaults to 0.
+///     num_shards (int): The number of shards to split the search into, e.g. to run it on several
+///     machines. The union of the results of all shards is the full result. Defaults to 1.
+///     progress (Optional[str]): When to show a progress bar over the branches of the search:
+///     "auto", only if stderr is a terminal, "always" or "never". Defaults to None, which uses
+///     the mode set by `set_progress()`.
 ///
 /// Returns:
 ///     List[List[int]]: A list of lists, where each inner list represents a valid combination of integer values that meet the specified criteria.
+///     If items > 1, the inner lists contain floats on the 1/items grid instead.
+///
+/// Raises:
+///     ValueError: If items is less than 1.
+///     ValueError: If on_budget is "error" and the estimated runtime exceeds the budget, or if
+///     on_budget is not one of "warn" and "error".
+///     ValueError: If the checkpoint file exists and resume is False, if it was written for
+///     other parameters or another shard, or if shard_index is not less than num_shards.
+///     ValueError: If progress is not one of "auto", "always" and "never".
+///     IOError: If the checkpoint file cannot be read or written.
+///
 /// Usage Example:
 ///     >>> from closure_core import closure
 ///     >>> combinations = closure(3.5, 1.2, 50, 0, 7, 0.05, 0.005)
@@ -44,7 +93,186 @@ use pyo3::pyfunction;
 ///     - It is a high-level interface to the lower-level `dfs_branch()` Rust function. 
 ///     - Despite optimizations and parallelisms, the space of possible solutions grows explosively
 ///     as n, the range, and rounding error increase.
+#[allow(clippy::too_many_arguments)]
 pub fn closure(
+    py: Python,
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    budget: Option<f64>,
+    on_budget: &str,
+    checkpoint: Option<&str>,
+    resume: bool,
+    shard_index: usize,
+    num_shards: usize,
+    progress: Option<&str>,
+) -> PyResult<ClosureResults> {
+    check_items(items)?;
+    // checked up front, since a problem without solutions never reaches dfs_checkpointed()
+    check_shard(shard_index, num_shards)?;
+    let progress = Progress::parse(progress)?;
+    if on_budget != "warn" && on_budget != "error" {
+        return Err(PyValueError::new_err(format!(
+            "on_budget must be either \"warn\" or \"error\", not \"{on_budget}\""
+        )));
+    }
+
+    if let Some(budget) = budget {
+        let estimate = estimate_closure(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items, ESTIMATE_PROBES, rand::rng().random());
+
+        if estimate.seconds > budget {
+            let message = format!(
+                "CLOSURE is estimated to take {:.1} seconds ({:.3e} search nodes), which exceeds the budget of {budget} seconds. Consider closure_sample() instead.",
+                estimate.seconds, estimate.nodes
+            );
+            if on_budget == "error" {
+                return Err(PyValueError::new_err(message));
+            }
+            py.import("warnings")?.call_method1("warn", (PyString::new(py, message.as_str()),))?;
+        }
+    }
+
+    // skip the search entirely if the problem is impossible on its face
+    let results = match closure_feasibility(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items) {
+        Ok(()) if checkpoint.is_some() || num_shards != 1 => dfs_checkpointed(
+            mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items,
+            shard_index, num_shards, checkpoint, resume, progress,
+        )?,
+        Ok(()) => dfs_parallel_with_progress(
+            ClosureBounds::new(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items),
+            progress,
+        ),
+        Err(_) => Vec::new(),
+    };
+
+    if items == 1 {
+        Ok(ClosureResults::Integer(results))
+    } else {
+        Ok(ClosureResults::Items(items_to_scale(results, items)))
+    }
+}
+
+/// The number of probes `closure()` uses to estimate its runtime against a budget.
+const ESTIMATE_PROBES: usize = 10000;
+
+/// Rejects a number of items below 1, which would divide by zero or flip the scale when
+/// observations are moved onto the scale of item sums.
+pub(crate) fn check_items(items: i32) -> PyResult<()> {
+    if items < 1 {
+        return Err(PyValueError::new_err(format!("items must be at least 1, but got {items}")));
+    }
+    Ok(())
+}
+
+#[pyfunction(signature = (mean, sd, n, scale_min, scale_max, rounding_error_mean = 0.05, rounding_error_sd = 0.05, items = 1, probes = 10000, seed = None))]
+#[cfg(not(tarpaulin_include))]
+/// Estimates how long `closure()` will take on a problem, without running it.
+///
+/// First runs quick impossibility checks: whether the mean lies within the scale range, whether
+/// any sum of n observations rounds to the mean, and whether the SD is below the largest SD
+/// achievable with that mean and range. If these pass, it follows random paths through the
+/// CLOSURE search tree (Knuth's estimator) to estimate the number of search nodes, the number
+/// of solutions, and the runtime.
+///
+/// Parameters:
+///     mean (float): The target mean of the combinations.
+///     sd (float): The target standard deviation of the combinations.
+///     n (int): The number of values in each combination.
+///     scale_min (int): The minimum scale value (inclusive).
+///     scale_max (int): The maximum scale value (inclusive).
+///     rounding_error_mean (float): The allowable rounding error for the mean.
+///     rounding_error_sd (float): The allowable rounding error for the standard deviation.
+///     items (int): The number of items averaged into each observation. Defaults to 1.
+///     probes (int): The number of random paths to follow. Defaults to 10000.
+///     seed (Optional[int]): Seed for the random number generator. If None, a random seed is used.
+///
+/// Returns:
+///     ClosureEstimate: An object with attributes `feasible`, `reason`, `nodes`, `solutions` and
+///     `seconds`.
+///
+/// Raises:
+///     ValueError: If items is less than 1.
+///
+/// Usage Example:
+///     >>> from scrutipy import closure_estimate
+///     >>> est = closure_estimate(3.5, 1.2, 50, 0, 7, 0.05, 0.005, seed = 1)
+///     >>> est.feasible
+///     True
+///     >>> closure_estimate(3.5, 4.0, 50, 0, 7).reason
+///     'The standard deviation 4 exceeds 3.5355339059327378, the largest achievable with this mean, sample size and scale range'
+///
+/// Notes:
+///     - The estimates are unbiased but have high variance, and usually come out too low when
+///     few probes are used. Treat them as orders of magnitude, and increase `probes` for more
+///     stable numbers.
+///     - Passing the impossibility checks does not guarantee that any solution exists.
+#[allow(clippy::too_many_arguments)]
+pub fn closure_estimate(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    probes: usize,
+    seed: Option<u64>,
+) -> PyResult<ClosureEstimate> {
+    check_items(items)?;
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+
+    Ok(estimate_closure(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items, probes, seed))
+}
+
+#[pyfunction(signature = (mean, sd, n, scale_min, scale_max, rounding_error_mean = 0.05, rounding_error_sd = 0.05, items = 1, k = 1, seed = None, max_nodes = 1000000))]
+#[cfg(not(tarpaulin_include))]
+/// Draws random samples from the space of datasets which CLOSURE would reconstruct, without
+/// enumerating all of them.
+///
+/// Use this instead of `closure()` when n and the scale range are too large for full
+/// enumeration, but you still want to see what plausible datasets look like.
+///
+/// Parameters:
+///     mean (float): The target mean of the combinations.
+///     sd (float): The target standard deviation of the combinations.
+///     n (int): The number of values in each combination.
+///     scale_min (int): The minimum scale value (inclusive).
+///     scale_max (int): The maximum scale value (inclusive).
+///     rounding_error_mean (float): The allowable rounding error for the mean.
+///     rounding_error_sd (float): The allowable rounding error for the standard deviation.
+///     items (int): The number of items averaged into each observation. Defaults to 1.
+///     k (int): The number of samples to draw. Defaults to 1.
+///     seed (Optional[int]): Seed for the random number generator. If None, a random seed is used.
+///     max_nodes (int): The maximum number of search steps for each sample. Defaults to 1000000.
+///
+/// Returns:
+///     List[List[int]]: Up to k sampled combinations, in the same format as `closure()`.
+///
+/// Raises:
+///     ValueError: If items is less than 1.
+///
+/// Usage Example:
+///     >>> from scrutipy import closure_sample
+///     >>> samples = closure_sample(3.5, 1.2, 50, 0, 7, 0.05, 0.005, k = 5, seed = 42)
+///     >>> print(len(samples))
+///     5
+///
+/// Notes:
+///     - Each sample is the first solution found by a depth-first search in random order. It
+///     uses the same pruning as `closure()`.
+///     - Samples are independent, so duplicates are possible.
+///     - Sampling is only approximately uniform. Solutions in branches of the search with few
+///     other solutions are somewhat more likely to be drawn than solutions in dense branches.
+///     - The output may be shorter than k, or empty, if a search runs out of max_nodes before
+///     finding a solution. This does not prove that no solution exists.
+#[allow(clippy::too_many_arguments)]
+pub fn closure_sample(
     mean: f64,
     sd: f64,
     n: i32,
@@ -52,8 +280,30 @@ pub fn closure(
     scale_max: i32,
     rounding_error_mean: f64,
     rounding_error_sd: f64,
-) -> Vec<Vec<i32>> {
-    dfs_parallel(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd)
+    items: i32,
+    k: usize,
+    seed: Option<u64>,
+    max_nodes: usize,
+) -> PyResult<ClosureResults> {
+    check_items(items)?;
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+
+    let results = dfs_sample(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items, k, seed, max_nodes);
+
+    if items == 1 {
+        Ok(ClosureResults::Integer(results))
+    } else {
+        Ok(ClosureResults::Items(items_to_scale(results, items)))
+    }
+}
+
+/// Converts samples of item sums, as returned by `dfs_parallel()`, back onto the original scale
+/// by dividing each value by the number of items.
+pub fn items_to_scale(results: Vec<Vec<i32>>, items: i32) -> Vec<Vec<f64>> {
+    results
+        .into_iter()
+        .map(|sample| sample.into_iter().map(|x| x as f64 / items as f64).collect())
+        .collect()
 }
 
 /// An iterator over a range of Rint-friendly generic integers `U`.
@@ -179,7 +429,7 @@ pub fn range_u<U: Integer + Copy>(start: U, end: U) -> IntegerRange<U> {
 /// - `running_m2`: The running second moment (M2) of the values, used for
 ///   calculating variance and standard deviation.
 #[derive(Clone)]
-struct Combination<U, T> {
+pub(crate) struct Combination<U, T> {
     values: Vec<U>,
     running_sum: T,
     running_m2: T,
@@ -214,6 +464,228 @@ pub fn count_initial_combinations(scale_min: i32, scale_max: i32) -> i32 {
     (range_size * (range_size + 1)) / 2
 }
 
+/// The fixed bounds of a CLOSURE search, shared by every branch of the search tree.
+///
+/// All values are on the scale of item sums (see `dfs_parallel()`), so the scale bounds, mean
+/// and standard deviation have already been multiplied by `items`. Both the exhaustive search in
+/// `dfs_branch()` and the random probes in `random_probe()` expand nodes through
+/// `for_each_child()`, so they prune the search tree in exactly the same way.
+///
+/// # Fields
+///
+/// - `n`: The total number of values in each combination.
+/// - `scale_min`: The minimum scale value (inclusive).
+/// - `scale_max_plus_1`: The maximum scale value plus one, used for range operations.
+/// - `target_sum_upper`: The upper bound for the target sum of the combination.
+/// - `target_sum_lower`: The lower bound for the target sum of the combination.
+/// - `sd_upper`: The upper bound for the standard deviation of the combination.
+/// - `sd_lower`: The lower bound for the standard deviation of the combination.
+/// - `m2_lower`: The smallest second moment (M2) a complete combination may have.
+/// - `scale_max_t`: The maximum scale value as a float.
+/// - `scale_min_sum_t`: A precomputed vector of minimum scale sums for each position.
+/// - `scale_max_sum_t`: A precomputed vector of maximum scale sums for each position.
+pub(crate) struct ClosureBounds<T, U> {
+    n: usize,
+    scale_min: U,
+    scale_max_plus_1: U,
+    target_sum_upper: T,
+    target_sum_lower: T,
+    sd_upper: T,
+    sd_lower: T,
+    m2_lower: T,
+    scale_max_t: T,
+    scale_min_sum_t: Vec<T>,
+    scale_max_sum_t: Vec<T>,
+}
+
+impl<T, U> ClosureBounds<T, U>
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+{
+    /// Derives the search bounds from the summary statistics, moving everything onto the scale
+    /// of item sums.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        mean: T,
+        sd: T,
+        n: U,
+        scale_min: U,
+        scale_max: U,
+        rounding_error_mean: T,
+        rounding_error_sd: T,
+        items: U,
+    ) -> Self {
+        // Move everything onto the scale of item sums, where each observation is an integer
+        let items_float = T::from(items).unwrap();
+        let mean = mean * items_float;
+        let sd = sd * items_float;
+        let rounding_error_mean = rounding_error_mean * items_float;
+        let rounding_error_sd = rounding_error_sd * items_float;
+        let scale_min = scale_min * items;
+        let scale_max = scale_max * items;
+
+        // Convert integer `n` to float to enable multiplication with other floats
+        let n_float = T::from(U::to_i32(&n).unwrap()).unwrap();
+
+        // Target sum calculations
+        let target_sum = mean * n_float;
+        let rounding_error_sum = rounding_error_mean * n_float;
+
+        // Convert to usize for range operations
+        let n_usize = U::to_usize(&n).unwrap();
+
+        // Precomputing scale sums directly on T types
+        let scale_min_sum_t: Vec<T> = (0..n_usize)
+            .map(|x| T::from(scale_min).unwrap() * T::from(x).unwrap())
+            .collect();
+
+        let scale_max_sum_t: Vec<T> = (0..n_usize)
+            .map(|x| T::from(scale_max).unwrap() * T::from(x).unwrap())
+            .collect();
+
+        let sd_lower = sd - rounding_error_sd;
+        let m2_lower = sd_lower.max(T::zero()).powi(2) * (n_float - T::one());
+
+        ClosureBounds {
+            n: n_usize,
+            scale_min,
+            scale_max_plus_1: scale_max + U::one(),
+            target_sum_upper: target_sum + rounding_error_sum,
+            target_sum_lower: target_sum - rounding_error_sum,
+            sd_upper: sd + rounding_error_sd,
+            sd_lower,
+            m2_lower,
+            scale_max_t: T::from(scale_max).unwrap(),
+            scale_min_sum_t,
+            scale_max_sum_t,
+        }
+    }
+
+    /// Generates the initial pairs `(i, j)` with `i <= j` from which every branch starts.
+    pub(crate) fn initial_combinations(&self) -> Vec<Combination<U, T>> {
+        // instead of generating the initial combinations using concrete types, we're keeping them in U
+        // and T using the iterator for U
+        range_u(self.scale_min, self.scale_max_plus_1)
+            .flat_map(|i| {
+                range_u(i, self.scale_max_plus_1).map(move |j| {
+                    // turn the integer type into the float type
+                    // again, might be good for readability to rename T to F
+                    let i_float = T::from(i).unwrap();
+                    let j_float = T::from(j).unwrap();
+                    let sum = i_float + j_float;
+                    let current_mean = sum / T::from(2).unwrap();
+
+                    let diff_i = i_float - current_mean;
+                    let diff_j = j_float - current_mean;
+                    let current_m2 = diff_i * diff_i + diff_j * diff_j;
+
+                    Combination {
+                        values: vec![i, j],
+                        running_sum: sum,
+                        running_m2: current_m2,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Whether a complete combination has a standard deviation within bounds. The upper bound
+    /// has already been enforced while the combination was built.
+    fn is_solution(&self, combination: &Combination<U, T>) -> bool {
+        let n_minus_1_float = T::from(self.n - 1).unwrap();
+        let current_std = (combination.running_m2 / n_minus_1_float).sqrt();
+        current_std >= self.sd_lower
+    }
+
+    /// An upper bound on the second moment (M2) which a partial combination can still reach once
+    /// its remaining `n_left` values, each between `last_value` and the scale maximum, are added.
+    ///
+    /// Every remaining value y satisfies (y - last)(max - y) >= 0, so y^2 <= (last + max) y -
+    /// last * max. This bounds the final sum of squares linearly in the final sum, and the
+    /// resulting bound on M2 is maximised over the final sums which still meet the target mean.
+    fn max_final_m2(&self, sum: T, m2: T, len: usize, last_value: T, n_left: usize) -> T {
+        let n_float = T::from(self.n).unwrap();
+        let n_left_float = T::from(n_left).unwrap();
+        let sum_squares = m2 + sum * sum / T::from(len).unwrap();
+        let spread = last_value + self.scale_max_t;
+
+        let lowest_sum = self.target_sum_lower.max(sum + n_left_float * last_value);
+        let highest_sum = self.target_sum_upper.min(sum + n_left_float * self.scale_max_t);
+        let final_sum = (n_float * spread / T::from(2).unwrap()).max(lowest_sum).min(highest_sum);
+
+        sum_squares + spread * (final_sum - sum)
+            - n_left_float * last_value * self.scale_max_t
+            - final_sum * final_sum / n_float
+    }
+
+    /// Calls `visit` on every extension of `current` by one value which survives pruning.
+    ///
+    /// Values are added in non-decreasing order. A branch is pruned if even the smallest
+    /// possible remaining values would overshoot the target sum, if even the largest would fall
+    /// short of it, if the standard deviation already exceeds its upper bound, or if it can no
+    /// longer reach its lower bound (see `max_final_m2()`).
+    #[inline]
+    fn for_each_child<F>(&self, current: &Combination<U, T>, mut visit: F)
+    where
+        F: FnMut(Combination<U, T>),
+    {
+        // Calculate remaining items to add
+        let current_len = current.values.len();
+        let n_left = self.n - current_len - 1; // How many more items after the next one
+        let next_n = current_len + 1;
+
+        // Get current mean
+        let current_mean = current.running_sum / T::from(current_len).unwrap();
+
+        // Get the last value
+        let last_value = current.values[current_len - 1];
+
+        for next_value in range_u(last_value, self.scale_max_plus_1) {
+            let next_value_as_t = T::from(next_value).unwrap();
+            let next_sum = current.running_sum + next_value_as_t;
+
+            // Safe indexing with bounds check (using usize for indexing)
+            if n_left < self.scale_min_sum_t.len() {
+                let minmean = next_sum + self.scale_min_sum_t[n_left];
+                if minmean > self.target_sum_upper {
+                    break; // Early termination - better than take_while!
+                }
+
+                // Safe indexing with bounds check (using usize for indexing)
+                if n_left < self.scale_max_sum_t.len() {
+                    let maxmean = next_sum + self.scale_max_sum_t[n_left];
+                    if maxmean < self.target_sum_lower {
+                        continue;
+                    }
+
+                    let next_mean = next_sum / T::from(next_n).unwrap();
+                    let delta = next_value_as_t - current_mean;
+                    let delta2 = next_value_as_t - next_mean;
+                    let next_m2 = current.running_m2 + delta * delta2;
+
+                    let min_sd = (next_m2 / T::from(self.n - 1).unwrap()).sqrt();
+                    if min_sd <= self.sd_upper {
+                        // allow for floating point error, since the bound is tight
+                        let max_m2 = self.max_final_m2(next_sum, next_m2, next_n, next_value_as_t, n_left);
+                        if max_m2 < self.m2_lower * (T::one() - T::from(1e-9).unwrap()) {
+                            continue;
+                        }
+
+                        let mut new_values = current.values.clone();
+                        new_values.push(next_value);
+                        visit(Combination {
+                            values: new_values,
+                            running_sum: next_sum,
+                            running_m2: next_m2,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Executes the CLOSURE algorithm in parallel to find all valid combinations.
 ///
 /// This function takes summary statistics and scale parameters to compute
@@ -235,11 +707,15 @@ pub fn count_initial_combinations(scale_min: i32, scale_max: i32) -> i32 {
 /// - `scale_max`: The maximum scale value (inclusive).
 /// - `rounding_error_mean`: The allowable rounding error for the mean.
 /// - `rounding_error_sd`: The allowable rounding error for the standard deviation.
+/// - `items`: The number of items averaged into each observation. With `items > 1`, observations
+///   lie on a grid with step `1 / items`. The search runs on the item sums, i.e. every value and
+///   statistic is multiplied by `items`, so all arithmetic on the scale stays in integers.
 ///
 /// # Returns
 ///
 /// A vector of vectors, where each inner vector represents a valid combination
-/// of integer values that meet the specified criteria.
+/// of integer values that meet the specified criteria. If `items > 1`, each value is the sum
+/// over items of one observation; divide by `items` to recover the scale value.
 ///
 /// # Example
 ///
@@ -248,7 +724,7 @@ pub fn count_initial_combinations(scale_min: i32, scale_max: i32) -> i32 {
 /// use num::FromPrimitive;
 ///
 /// let combinations = dfs_parallel(
-///     10.0, 2.0, 3, 1, 5, 0.1, 0.1
+///     10.0, 2.0, 3, 1, 5, 0.1, 0.1, 1
 /// );
 /// assert!(combinations.is_empty()); // If there are no results, the outer vector will be empty
 /// ```
@@ -258,12 +734,13 @@ pub fn count_initial_combinations(scale_min: i32, scale_max: i32) -> i32 {
 /// use num::FromPrimitive;
 ///
 /// let combinations = dfs_parallel(
-///     3.5, 0.57, 100, 0, 7, 0.05, 0.05
+///     3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1
 /// );
 ///
 /// assert_eq!(combinations.len(), 568); // If results are found, each one will be stored as a
 /// Vec<U> inside the outer vector
 /// ```
+#[allow(clippy::too_many_arguments)]
 pub fn dfs_parallel<T, U>(
     mean: T,
     sd: T,
@@ -272,80 +749,50 @@ pub fn dfs_parallel<T, U>(
     scale_max: U,
     rounding_error_mean: T,
     rounding_error_sd: T,
+    items: U,
 ) -> Vec<Vec<U>>
 where
     T: Float + FromPrimitive + Send + Sync, // suggest renaming to F to indicate float type?
     U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
 {
-    // Convert integer `n` to float to enable multiplication with other floats
-    let n_float = T::from(U::to_i32(&n).unwrap()).unwrap();
-    
-    // Target sum calculations
-    let target_sum = mean * n_float;
-    let rounding_error_sum = rounding_error_mean * n_float;
-    
-    let target_sum_upper = target_sum + rounding_error_sum;
-    let target_sum_lower = target_sum - rounding_error_sum;
-    let sd_upper = sd + rounding_error_sd;
-    let sd_lower = sd - rounding_error_sd;
-
-    // Convert to usize for range operations
-    let n_usize = U::to_usize(&n).unwrap();
-    
-    // Precomputing scale sums directly on T types 
-    let scale_min_sum_t: Vec<T> = (0..n_usize)
-        .map(|x| T::from(scale_min).unwrap() * T::from(x).unwrap())
-        .collect();
-    
-    let scale_max_sum_t: Vec<T> = (0..n_usize)
-        .map(|x| T::from(scale_max).unwrap() * T::from(x).unwrap())
-        .collect();
-    
-    let n_minus_1 = n - U::one();
-    let scale_max_plus_1 = scale_max + U::one();
-
-   // instead of generating the initial combinations using concrete types, we're keeping them in U
-    // and T using the iterator for U 
-    let combinations = range_u(scale_min, scale_max_plus_1)
-    .flat_map(|i| {
-        range_u(i, scale_max_plus_1).map(move |j| {
-            let initial_combination = vec![i, j];
-
-            // turn the integer type into the float type
-            // again, might be good for readability to rename T to F
-            let i_float = T::from(i).unwrap();
-            let j_float = T::from(j).unwrap();
-            let sum = i_float + j_float;
-            let current_mean = sum / T::from(2).unwrap();
-
-            let diff_i = i_float - current_mean;
-            let diff_j = j_float - current_mean;
-            let current_m2 = diff_i * diff_i + diff_j * diff_j;
-
-            (initial_combination, sum, current_m2)
-        })
-    })
-    .collect::<Vec<_>>();
+    let bounds = ClosureBounds::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+    );
+
+    dfs_parallel_with_progress(bounds, Progress::Never)
+}
+
+/// Runs `dfs_parallel()` on prepared bounds, drawing a progress bar over the initial branches
+/// according to `progress`.
+pub(crate) fn dfs_parallel_with_progress<T, U>(
+    bounds: ClosureBounds<T, U>,
+    progress: Progress,
+) -> Vec<Vec<U>>
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+{
+    let combinations = bounds.initial_combinations();
+    let bar = progress.bar(combinations.len() as u64);
 
     // Process combinations in parallel
-    combinations.par_iter()
-        .flat_map(|(combo, running_sum, running_m2)| {
-            dfs_branch(
-                combo.clone(),
-                *running_sum,
-                *running_m2,
-                n_usize,
-                target_sum_upper,
-                target_sum_lower,
-                sd_upper,
-                sd_lower,
-                &scale_min_sum_t,
-                &scale_max_sum_t,
-                n_minus_1,
-                scale_max_plus_1,
-            )
+    let results = combinations.into_par_iter()
+        .flat_map(|combo| {
+            let samples = dfs_branch(combo, &bounds);
+            bar.inc(1);
+            samples
         })
-        .collect()
+        .collect();
+
+    bar.finish();
+    results
 }
 
 /// Collects all valid combinations from a starting point using a depth-first search approach.
@@ -361,111 +808,446 @@ where
 ///
 /// # Parameters
 ///
-/// - `start_combination`: A vector of integers representing the initial combination to start from.
-/// - `running_sum_init`: The initial running sum of the values in the starting combination.
-/// - `running_m2_init`: The initial running second moment (M2) of the values, used for variance calculation.
-/// - `n`: The total number of values in each combination.
-/// - `target_sum_upper`: The upper bound for the target sum of the combination.
-/// - `target_sum_lower`: The lower bound for the target sum of the combination.
-/// - `sd_upper`: The upper bound for the standard deviation of the combination.
-/// - `sd_lower`: The lower bound for the standard deviation of the combination.
-/// - `scale_min_sum_t`: A precomputed vector of minimum scale sums for each position.
-/// - `scale_max_sum_t`: A precomputed vector of maximum scale sums for each position.
-/// - `_n_minus_1`: The total number of values minus one, used for internal calculations.
-/// - `scale_max_plus_1`: The maximum scale value plus one, used for range operations.
+/// - `start_combination`: The initial combination to start from, along with its running sum
+///   and running second moment (M2).
+/// - `bounds`: The fixed bounds of the search, see `ClosureBounds`.
 ///
 /// # Returns
 ///
 /// A vector of vectors, where each inner vector represents a valid combination of integer
 /// values that meet the specified criteria.
 #[inline]
-#[allow(clippy::too_many_arguments)]
-fn dfs_branch<T, U>(
-    start_combination: Vec<U>,
-    running_sum_init: T,
-    running_m2_init: T,
-    n: usize,  // Use usize for the length
-    target_sum_upper: T,
-    target_sum_lower: T,
-    sd_upper: T,
-    sd_lower: T,
-    scale_min_sum_t: &[T],
-    scale_max_sum_t: &[T],
-    _n_minus_1: U,
-    scale_max_plus_1: U,
+pub(crate) fn dfs_branch<T, U>(
+    start_combination: Combination<U, T>,
+    bounds: &ClosureBounds<T, U>,
 ) -> Vec<Vec<U>>
 where
     T: Float + FromPrimitive + Send + Sync,
     U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
 {
-    let mut stack = VecDeque::with_capacity(n * 2); // Preallocate with reasonable capacity
+    let mut stack = VecDeque::with_capacity(bounds.n * 2); // Preallocate with reasonable capacity
     let mut results = Vec::new();
-    
-    stack.push_back(Combination {
-        values: start_combination.clone(),
-        running_sum: running_sum_init,
-        running_m2: running_m2_init,
-    });
-    
+
+    stack.push_back(start_combination);
+
     while let Some(current) = stack.pop_back() {
-        if current.values.len() >= n {
-            let n_minus_1_float = T::from(n - 1).unwrap();
-            let current_std = (current.running_m2 / n_minus_1_float).sqrt();
-            if current_std >= sd_lower {
+        if current.values.len() >= bounds.n {
+            if bounds.is_solution(&current) {
                 results.push(current.values);
             }
             continue;
         }
 
-        // Calculate remaining items to add
-        let current_len = current.values.len();
-        let n_left = n - current_len - 1; // How many more items after the next one
-        let next_n = current_len + 1;
+        bounds.for_each_child(&current, |child| stack.push_back(child));
+    }
+    results
+}
 
-        // Get current mean
-        let current_mean = current.running_sum / T::from(current_len).unwrap();
+/// Runs a depth-first search from randomly ordered initial combinations, visiting the children
+/// of every node in random order, and returns the first solution it reaches.
+///
+/// Children are generated with the same pruning that `dfs_branch()` applies. The search gives
+/// up after visiting `max_nodes` nodes.
+fn random_dfs<T, U, R>(
+    initial: &[Combination<U, T>],
+    bounds: &ClosureBounds<T, U>,
+    rng: &mut R,
+    max_nodes: usize,
+) -> Option<Vec<U>>
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+    R: Rng,
+{
+    let mut order: Vec<usize> = (0..initial.len()).collect();
+    order.shuffle(rng);
 
-        // Get the last value        
-        let last_value = current.values[current_len - 1];
+    let mut stack = Vec::with_capacity(bounds.n * 2);
+    let mut nodes = 0;
 
-        for next_value in range_u(last_value, scale_max_plus_1) {
-            let next_value_as_t = T::from(next_value).unwrap();
-            let next_sum = current.running_sum + next_value_as_t;
-            
-            // Safe indexing with bounds check (using usize for indexing)
-            if n_left < scale_min_sum_t.len() {
-                let minmean = next_sum + scale_min_sum_t[n_left];
-                if minmean > target_sum_upper {
-                    break; // Early termination - better than take_while!
-                }
-                
-                // Safe indexing with bounds check (using usize for indexing)
-                if n_left < scale_max_sum_t.len() {
-                    let maxmean = next_sum + scale_max_sum_t[n_left];
-                    if maxmean < target_sum_lower {
-                        continue;
-                    }
-                    
-                    let next_mean = next_sum / T::from(next_n).unwrap();
-                    let delta = next_value_as_t - current_mean;
-                    let delta2 = next_value_as_t - next_mean;
-                    let next_m2 = current.running_m2 + delta * delta2;
-                    
-                    let min_sd = (next_m2 / T::from(n - 1).unwrap()).sqrt();
-                    if min_sd <= sd_upper {
-                        let mut new_values = current.values.clone();
-                        new_values.push(next_value);
-                        stack.push_back(Combination {
-                            values: new_values,
-                            running_sum: next_sum,
-                            running_m2: next_m2,
-                        });
-                    }
+    for i in order {
+        stack.push(initial[i].clone());
+
+        while let Some(current) = stack.pop() {
+            nodes += 1;
+            if nodes > max_nodes {
+                return None;
+            }
+
+            if current.values.len() >= bounds.n {
+                if bounds.is_solution(&current) {
+                    return Some(current.values);
                 }
+                continue;
             }
+
+            let first_child = stack.len();
+            bounds.for_each_child(&current, |child| stack.push(child));
+            stack[first_child..].shuffle(rng);
         }
     }
-    results
+    None
+}
+
+/// Draws random samples from the CLOSURE solution space without enumerating it.
+///
+/// This is the sampling counterpart to `dfs_parallel()`, for problems where full enumeration is
+/// infeasible. Each draw runs `random_dfs()` with its own random number generator, seeded from
+/// `seed` and the index of the draw, so draws run in parallel and the output depends only on
+/// `seed`.
+///
+/// # Bias
+///
+/// Draws are approximately, but not exactly, uniform over the solution space. A randomised
+/// depth-first search returns the first solution it reaches, so solutions in branches with few
+/// solutions, or close to the root of the search tree, are reached first more often than
+/// solutions in dense branches. Draws are independent, so the same combination may appear more
+/// than once.
+///
+/// # Parameters
+///
+/// See `dfs_parallel()` for the parameters shared with the exhaustive search.
+///
+/// - `k`: The number of samples to draw.
+/// - `seed`: The seed for the random number generator. The same seed gives the same samples.
+/// - `max_nodes`: The maximum number of search tree nodes to visit for each draw.
+///
+/// # Returns
+///
+/// Up to `k` combinations, in the same units as `dfs_parallel()`. Draws which reach `max_nodes`
+/// without finding a solution are left out, so the output is empty if there are no solutions.
+#[allow(clippy::too_many_arguments)]
+pub fn dfs_sample<T, U>(
+    mean: T,
+    sd: T,
+    n: U,
+    scale_min: U,
+    scale_max: U,
+    rounding_error_mean: T,
+    rounding_error_sd: T,
+    items: U,
+    k: usize,
+    seed: u64,
+    max_nodes: usize,
+) -> Vec<Vec<U>>
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+{
+    let bounds = ClosureBounds::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+    );
+
+    let initial = bounds.initial_combinations();
+
+    (0..k as u64).into_par_iter()
+        .filter_map(|draw| {
+            let mut rng = stream_rng(seed, draw);
+            random_dfs(&initial, &bounds, &mut rng, max_nodes)
+        })
+        .collect()
+}
+
+/// The outcome of a single random root-to-leaf path through the CLOSURE search tree.
+///
+/// # Fields
+///
+/// - `solution`: Whether the path ended in a valid solution.
+/// - `weight`: The product of the branching factors along the path, including the choice of
+///   initial combination. This is the inverse of the probability of taking this exact path.
+/// - `nodes`: Knuth's estimate of the size of the search tree from this path, i.e. the sum of
+///   the partial products of the branching factors.
+/// - `expanded`: The number of nodes whose children were generated along the path.
+struct ClosureProbe {
+    solution: bool,
+    weight: f64,
+    nodes: f64,
+    expanded: usize,
+}
+
+/// Follows one random path from the root of the search tree to a leaf or a dead end.
+///
+/// At every node, the next value is chosen uniformly among the children which survive the
+/// same pruning that `dfs_branch()` applies.
+fn random_probe<T, U, R>(
+    initial: &[Combination<U, T>],
+    bounds: &ClosureBounds<T, U>,
+    rng: &mut R,
+) -> ClosureProbe
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+    R: Rng,
+{
+    let mut weight = initial.len() as f64;
+    let mut nodes = weight;
+    let mut expanded = 0;
+    let mut current = initial[rng.random_range(0..initial.len())].clone();
+    let mut children = Vec::new();
+
+    while current.values.len() < bounds.n {
+        children.clear();
+        bounds.for_each_child(&current, |child| children.push(child));
+        expanded += 1;
+
+        if children.is_empty() {
+            return ClosureProbe { solution: false, weight, nodes, expanded };
+        }
+
+        weight *= children.len() as f64;
+        nodes += weight;
+        current = children.swap_remove(rng.random_range(0..children.len()));
+    }
+
+    ClosureProbe { solution: bounds.is_solution(&current), weight, nodes, expanded }
+}
+
+/// Knuth's estimate of the size of a CLOSURE search, averaged over random probes.
+///
+/// # Fields
+///
+/// - `nodes`: The estimated number of nodes in the search tree.
+/// - `solutions`: The estimated number of solutions.
+/// - `expanded`: The number of nodes actually expanded by the probes, for timing.
+pub struct DfsEstimate {
+    pub nodes: f64,
+    pub solutions: f64,
+    pub expanded: usize,
+}
+
+/// Estimates the size of the CLOSURE search tree and the number of solutions without running
+/// the search.
+///
+/// This implements Knuth's estimator: each probe follows one random root-to-leaf path (see
+/// `random_probe()`), and the product of the branching factors along the path is an unbiased
+/// estimate of the number of leaves at its depth. Averaging over probes gives unbiased
+/// estimates of the number of nodes and solutions.
+///
+/// # Notes
+///
+/// The estimates are unbiased but have high variance. Most of the mass often sits in a few
+/// rare paths, so with too few probes the estimates usually come out too low. Treat them as
+/// orders of magnitude.
+///
+/// # Parameters
+///
+/// See `dfs_parallel()` for the parameters shared with the exhaustive search.
+///
+/// - `probes`: The number of random paths to follow.
+/// - `seed`: The seed for the random number generator.
+#[allow(clippy::too_many_arguments)]
+pub fn dfs_estimate<T, U>(
+    mean: T,
+    sd: T,
+    n: U,
+    scale_min: U,
+    scale_max: U,
+    rounding_error_mean: T,
+    rounding_error_sd: T,
+    items: U,
+    probes: usize,
+    seed: u64,
+) -> DfsEstimate
+where
+    T: Float + FromPrimitive + Send + Sync,
+    U: Integer + NumCast + ToPrimitive + Copy + Send + Sync,
+{
+    let bounds = ClosureBounds::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+    );
+
+    let initial = bounds.initial_combinations();
+    if probes == 0 || initial.is_empty() {
+        return DfsEstimate { nodes: 0.0, solutions: 0.0, expanded: 0 };
+    }
+
+    let mut rng = stream_rng(seed, 0);
+    let mut nodes = 0.0;
+    let mut solutions = 0.0;
+    let mut expanded = 0;
+
+    for _ in 0..probes {
+        let probe = random_probe(&initial, &bounds, &mut rng);
+        nodes += probe.nodes;
+        if probe.solution {
+            solutions += probe.weight;
+        }
+        expanded += probe.expanded;
+    }
+
+    DfsEstimate {
+        nodes: nodes / probes as f64,
+        solutions: solutions / probes as f64,
+        expanded,
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ClosureInfeasibleError {
+    #[error("The mean {0} cannot be reached on a scale from {1} to {2}")]
+    MeanOutOfRange(f64, i32, i32),
+    #[error("No sum of {0} observations on this scale rounds to the mean {1}")]
+    MeanGranularity(i32, f64),
+    #[error("The standard deviation {0} exceeds {1}, the largest achievable with this mean, sample size and scale range")]
+    SdTooLarge(f64, f64),
+}
+
+/// Runs quick checks which rule out a CLOSURE problem before any search.
+///
+/// The checks are necessary conditions for a solution to exist, so a problem which fails any
+/// of them has no solutions, but a problem which passes them all may still have none.
+///
+/// - The mean, within its rounding error, must lie within the scale range.
+/// - Some achievable sum of the observations must round to the mean (a GRIM-style check).
+/// - The standard deviation, within its rounding error, must not exceed the largest standard
+///   deviation achievable with that mean on that range. For a sum S of n values in [a, b], the
+///   second moment is at most (S - na)(nb - S) / n, reached by piling values onto the bounds.
+///
+/// All checks allow for floating point error, so that they never rule out a problem which
+/// `dfs_parallel()` would solve.
+#[allow(clippy::too_many_arguments)]
+pub fn closure_feasibility(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+) -> Result<(), ClosureInfeasibleError> {
+    let bounds = ClosureBounds::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+    );
+    let fuzz = 1e-9;
+
+    let n_float = n as f64;
+    let lowest_possible = n_float * (scale_min * items) as f64;
+    let highest_possible = n_float * (scale_max * items) as f64;
+
+    if bounds.target_sum_upper < lowest_possible - fuzz || bounds.target_sum_lower > highest_possible + fuzz {
+        return Err(ClosureInfeasibleError::MeanOutOfRange(mean, scale_min, scale_max));
+    }
+
+    let lowest_sum = (bounds.target_sum_lower.max(lowest_possible) - fuzz).ceil();
+    let highest_sum = (bounds.target_sum_upper.min(highest_possible) + fuzz).floor();
+
+    if lowest_sum > highest_sum {
+        return Err(ClosureInfeasibleError::MeanGranularity(n, mean));
+    }
+
+    // the bound on the second moment is concave in the sum, so maximise it over the sums which
+    // still meet the mean
+    let best_sum = ((lowest_possible + highest_possible) / 2.0)
+        .max(lowest_sum)
+        .min(highest_sum);
+    let max_m2 = (best_sum - lowest_possible) * (highest_possible - best_sum) / n_float;
+
+    if max_m2 < bounds.m2_lower * (1.0 - fuzz) {
+        let max_sd = (max_m2 / (n_float - 1.0)).sqrt() / items as f64;
+        return Err(ClosureInfeasibleError::SdTooLarge(sd, max_sd));
+    }
+
+    Ok(())
+}
+
+/// A feasibility estimate for a CLOSURE problem, as returned by `closure_estimate()`.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct ClosureEstimate {
+    /// Whether the problem passed the quick impossibility checks.
+    #[pyo3(get)]
+    pub feasible: bool,
+    /// Why the problem is impossible, if it failed a check.
+    #[pyo3(get)]
+    pub reason: Option<String>,
+    /// The estimated number of nodes in the search tree.
+    #[pyo3(get)]
+    pub nodes: f64,
+    /// The estimated number of solutions.
+    #[pyo3(get)]
+    pub solutions: f64,
+    /// The estimated runtime of `closure()` in seconds.
+    #[pyo3(get)]
+    pub seconds: f64,
+}
+
+#[pymethods]
+impl ClosureEstimate {
+    fn __repr__(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("ClosureEstimate(feasible=False, reason={reason:?})"),
+            None => format!(
+                "ClosureEstimate(feasible=True, nodes={:.3e}, solutions={:.3e}, seconds={:.3e})",
+                self.nodes, self.solutions, self.seconds
+            ),
+        }
+    }
+}
+
+/// Runs the impossibility checks and, if they pass, Knuth's estimator on a CLOSURE problem.
+///
+/// The runtime is extrapolated from the time the probes take per expanded node, divided by the
+/// number of threads `dfs_parallel()` will run on.
+#[allow(clippy::too_many_arguments)]
+pub fn estimate_closure(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    probes: usize,
+    seed: u64,
+) -> ClosureEstimate {
+    if let Err(e) = closure_feasibility(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items) {
+        return ClosureEstimate {
+            feasible: false,
+            reason: Some(e.to_string()),
+            nodes: 0.0,
+            solutions: 0.0,
+            seconds: 0.0,
+        };
+    }
+
+    let start = Instant::now();
+    let estimate = dfs_estimate(mean, sd, n, scale_min, scale_max, rounding_error_mean, rounding_error_sd, items, probes, seed);
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let seconds_per_node = if estimate.expanded > 0 {
+        elapsed / estimate.expanded as f64
+    } else { 0.0 };
+
+    ClosureEstimate {
+        feasible: true,
+        reason: None,
+        nodes: estimate.nodes,
+        solutions: estimate.solutions,
+        seconds: estimate.nodes * seconds_per_node / rayon::current_num_threads() as f64,
+    }
 }
 
 #[cfg(test)]
@@ -480,10 +1262,161 @@ mod tests {
 
     #[test]
     fn test_7980() {
-        assert_eq!(dfs_parallel(3.5, 1.2, 50, 0, 7, 0.05, 0.005).len(), 7980);
+        assert_eq!(dfs_parallel(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1).len(), 7980);
     }
     #[test]
     fn test_empty() {
-        assert!(dfs_parallel(10.0, 2.0, 3, 1, 5, 0.1, 0.1).is_empty());
+        assert!(dfs_parallel(10.0, 2.0, 3, 1, 5, 0.1, 0.1, 1).is_empty());
+    }
+
+    /// Every non-decreasing combination of `n` item sums whose mean and standard deviation are
+    /// within bounds, found without any pruning.
+    #[allow(clippy::too_many_arguments)]
+    fn brute_force(
+        mean: f64,
+        sd: f64,
+        n: usize,
+        scale_min: i32,
+        scale_max: i32,
+        rounding_error_mean: f64,
+        rounding_error_sd: f64,
+        items: i32,
+    ) -> Vec<Vec<i32>> {
+        let items_float = items as f64;
+        let (low, high) = (scale_min * items, scale_max * items);
+        let mut results = Vec::new();
+        let mut stack = vec![vec![]];
+
+        while let Some(values) = stack.pop() {
+            if values.len() == n {
+                let sum: f64 = values.iter().map(|&x| x as f64).sum();
+                let m = sum / n as f64;
+                let m2: f64 = values.iter().map(|&x| (x as f64 - m).powi(2)).sum();
+                let s = (m2 / (n - 1) as f64).sqrt();
+                if (m - mean * items_float).abs() <= rounding_error_mean * items_float
+                    && (s - sd * items_float).abs() <= rounding_error_sd * items_float
+                {
+                    results.push(values);
+                }
+                continue;
+            }
+            let start = values.last().copied().unwrap_or(low);
+            for next in start..=high {
+                let mut extended = values.clone();
+                extended.push(next);
+                stack.push(extended);
+            }
+        }
+        results.sort();
+        results
+    }
+
+    #[test]
+    fn test_pruning_matches_brute_force() {
+        let cases = [
+            (3.0, 1.26, 6, 1, 5, 0.05, 0.05, 1),
+            (2.5, 1.3, 8, 1, 5, 0.05, 0.05, 1),
+            (4.3, 0.95, 7, 1, 7, 0.05, 0.05, 1),
+            (1.9, 2.1, 9, 0, 6, 0.05, 0.05, 1),
+            (5.6, 1.4, 8, 1, 7, 0.05, 0.05, 1),
+            (4.0, 1.5, 12, 1, 7, 0.05, 0.05, 1),
+            (3.0, 1.2, 10, 1, 5, 0.5, 0.5, 1),
+            (2.25, 0.87, 6, 1, 4, 0.05, 0.05, 2),
+            (3.1, 0.4, 5, 1, 5, 0.05, 0.05, 3),
+        ];
+
+        for (mean, sd, n, scale_min, scale_max, rem, resd, items) in cases {
+            let expected = brute_force(mean, sd, n, scale_min, scale_max, rem, resd, items);
+            let mut results = dfs_parallel(mean, sd, n as i32, scale_min, scale_max, rem, resd, items);
+            results.sort();
+            assert!(!expected.is_empty());
+            assert_eq!(results, expected, "case {:?}", (mean, sd, n, scale_min, scale_max, items));
+        }
+    }
+
+    #[test]
+    fn test_check_items() {
+        assert!(check_items(1).is_ok());
+        assert!(check_items(3).is_ok());
+        assert!(check_items(0).is_err());
+        assert!(check_items(-2).is_err());
+    }
+
+    #[test]
+    fn test_items() {
+        // no integer sample matches, but three samples on the half-point grid do
+        assert!(dfs_parallel(2.25, 0.87, 4, 1, 4, 0.005, 0.005, 1).is_empty());
+
+        let mut results = items_to_scale(dfs_parallel(2.25, 0.87, 4, 1, 4, 0.005, 0.005, 2), 2);
+        results.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(results, vec![
+            vec![1.0, 2.5, 2.5, 3.0],
+            vec![1.5, 1.5, 3.0, 3.0],
+            vec![1.5, 2.0, 2.0, 3.5],
+        ]);
+    }
+
+    #[test]
+    fn test_sample_valid() {
+        let all = dfs_parallel(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1);
+        let samples = dfs_sample(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 20, 42, 1000000);
+        assert_eq!(samples.len(), 20);
+        for sample in samples {
+            assert!(all.contains(&sample));
+        }
+    }
+
+    #[test]
+    fn test_sample_seeded() {
+        let a = dfs_sample(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 5, 7, 1000000);
+        let b = dfs_sample(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 5, 7, 1000000);
+        assert_eq!(a, b);
+
+        // adjacent seeds must not share draws shifted by one
+        let c = dfs_sample(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 5, 8, 1000000);
+        assert_ne!(a[1..], c[..4]);
+        assert_ne!(a[..4], c[1..]);
+    }
+
+    #[test]
+    fn test_sample_empty() {
+        assert!(dfs_sample(10.0, 2.0, 3, 1, 5, 0.1, 0.1, 1, 5, 0, 1000).is_empty());
+    }
+
+    #[test]
+    fn test_feasibility() {
+        assert!(closure_feasibility(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1).is_ok());
+        assert_eq!(
+            closure_feasibility(10.0, 2.0, 3, 1, 5, 0.1, 0.1, 1),
+            Err(ClosureInfeasibleError::MeanOutOfRange(10.0, 1, 5))
+        );
+        assert_eq!(
+            closure_feasibility(3.51, 1.0, 10, 1, 7, 0.001, 0.005, 1),
+            Err(ClosureInfeasibleError::MeanGranularity(10, 3.51))
+        );
+        assert_eq!(
+            closure_feasibility(3.5, 4.0, 50, 0, 7, 0.05, 0.05, 1),
+            Err(ClosureInfeasibleError::SdTooLarge(4.0, 3.5355339059327378))
+        );
+    }
+
+    #[test]
+    fn test_feasibility_items() {
+        // a mean of 3.55 needs a sum of 35.5 over 10 observations, which only half points allow
+        assert!(closure_feasibility(3.55, 1.0, 10, 1, 7, 0.001, 0.005, 1).is_err());
+        assert!(closure_feasibility(3.55, 1.0, 10, 1, 7, 0.001, 0.005, 2).is_ok());
+        assert!(closure_feasibility(2.25, 0.87, 4, 1, 4, 0.005, 0.005, 2).is_ok());
+    }
+
+    #[test]
+    fn test_estimate() {
+        let a = dfs_estimate(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 1000, 3);
+        let b = dfs_estimate(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, 1000, 3);
+        assert_eq!(a.nodes, b.nodes);
+        assert_eq!(a.solutions, b.solutions);
+        assert!(a.nodes > 0.0);
+
+        let small = dfs_estimate(2.25, 0.87, 4, 1, 4, 0.005, 0.005, 2, 20000, 3);
+        assert!((small.solutions - 3.0).abs() < 1.0);
     }
 }
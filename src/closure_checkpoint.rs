@@ -0,0 +1,354 @@
+//! Checkpointing and sharding for long CLOSURE runs.
+//!
+//! `dfs_parallel()` splits the search into independent branches, one for each initial pair
+//! `(i, j)`. This module records each branch in a checkpoint file as soon as it is finished,
+//! so that an interrupted run can resume without redoing finished branches, and lets one
+//! problem be split into shards which run in separate processes or on separate machines.
+//!
+//! The checkpoint is a CSV file with rows of varying length:
+//!
+//! - `closure,<mean>,<sd>,<n>,<scale_min>,<scale_max>,<rounding_error_mean>,<rounding_error_sd>,<items>,<shard_index>,<num_shards>`
+//!   is the first row, which ties the file to one problem.
+//! - `sample,<branch>,<v1>,...,<vn>` records one solution found in a branch.
+//! - `done,<branch>,<count>` marks a branch as finished with `count` solutions.
+//!
+//! Each branch is written as one block of `sample` rows followed by its `done` row. A block
+//! without a matching `done` row, e.g. because the process was killed while writing it, is
+//! discarded on resume and its branch is searched again. On resume the finished blocks are
+//! copied into `<checkpoint>.tmp`, which then replaces the checkpoint.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Mutex;
+
+use csv::{ReaderBuilder, Writer, WriterBuilder};
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::PyErr;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::closure::{dfs_branch, ClosureBounds};
+use crate::progress::Progress;
+
+#[derive(Debug, Error)]
+pub enum ClosureCheckpointError {
+    #[error("Could not access the checkpoint file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not read or write the checkpoint file: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("The checkpoint file {0} already exists. Pass resume=True to continue from it, or delete it to start over")]
+    Exists(String),
+    #[error("The checkpoint file {0} belongs to a different problem or shard. Expected {1:?}, found {2:?}")]
+    Mismatch(String, Vec<String>, Vec<String>),
+    #[error("shard_index must be less than num_shards, but got shard_index {0} and num_shards {1}")]
+    Shard(usize, usize),
+}
+
+impl From<ClosureCheckpointError> for PyErr {
+    fn from(err: ClosureCheckpointError) -> PyErr {
+        match err {
+            ClosureCheckpointError::Io(_) | ClosureCheckpointError::Csv(_) => {
+                PyIOError::new_err(err.to_string())
+            }
+            _ => PyValueError::new_err(err.to_string()),
+        }
+    }
+}
+
+/// Runs CLOSURE on one shard of the search, optionally checkpointing finished branches.
+///
+/// The initial combinations are assigned to shards round-robin: shard `shard_index` takes
+/// every branch whose index modulo `num_shards` equals `shard_index`. Low branch indices start
+/// from small values and tend to be the largest, so round-robin assignment balances the work
+/// better than contiguous blocks. The union of the outputs of all shards is the output of
+/// `dfs_parallel()`.
+///
+/// # Parameters
+///
+/// See `dfs_parallel()` for the parameters of the problem.
+///
+/// - `shard_index`: Which shard to run, from 0 to `num_shards - 1`.
+/// - `num_shards`: The number of shards the problem is split into. Use 1 for the whole problem.
+/// - `checkpoint`: A path to the checkpoint file, or None to run without checkpointing.
+/// - `resume`: Whether to continue from an existing checkpoint file. Branches recorded as
+///   finished are not searched again, and their solutions are read from the file instead. If
+///   the file does not exist yet, the run starts from scratch.
+/// - `progress`: When to draw a progress bar over the branches of this shard.
+///
+/// # Returns
+///
+/// The solutions from this shard, ordered by branch, in the same units as `dfs_parallel()`.
+///
+/// # Errors
+///
+/// - `Exists` if the checkpoint file exists and `resume` is false, to avoid overwriting it.
+/// - `Mismatch` if the checkpoint file was written for different parameters or another shard.
+/// - `Shard` if `shard_index` is not less than `num_shards`.
+/// - `Io` or `Csv` if the file cannot be read or written.
+#[allow(clippy::too_many_arguments)]
+pub fn dfs_checkpointed(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    shard_index: usize,
+    num_shards: usize,
+    checkpoint: Option<&str>,
+    resume: bool,
+    progress: Progress,
+) -> Result<Vec<Vec<i32>>, ClosureCheckpointError> {
+    check_shard(shard_index, num_shards)?;
+
+    let bounds = ClosureBounds::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+    );
+
+    let header: Vec<String> = vec![
+        "closure".to_string(),
+        mean.to_string(),
+        sd.to_string(),
+        n.to_string(),
+        scale_min.to_string(),
+        scale_max.to_string(),
+        rounding_error_mean.to_string(),
+        rounding_error_sd.to_string(),
+        items.to_string(),
+        shard_index.to_string(),
+        num_shards.to_string(),
+    ];
+
+    let mut finished: BTreeMap<usize, Vec<Vec<i32>>> = BTreeMap::new();
+
+    let writer = match checkpoint {
+        None => None,
+        Some(path) => {
+            if Path::new(path).exists() {
+                if !resume {
+                    return Err(ClosureCheckpointError::Exists(path.to_string()));
+                }
+                finished = read_checkpoint(path, &header, n as usize)?;
+            }
+
+            // rewrite the file with only the finished branches, dropping any partial block. The
+            // new file is written next to the old one and renamed over it, so the finished
+            // branches are never lost if the process dies while they are being copied
+            let temp = format!("{path}.tmp");
+            let mut writer = WriterBuilder::new().flexible(true).from_writer(File::create(&temp)?);
+            writer.write_record(&header)?;
+            for (branch, samples) in &finished {
+                write_branch(&mut writer, *branch, samples)?;
+            }
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+            fs::rename(&temp, path)?;
+            Some(Mutex::new(writer))
+        }
+    };
+
+    let pending: Vec<_> = bounds
+        .initial_combinations()
+        .into_iter()
+        .enumerate()
+        .filter(|(branch, _)| branch % num_shards == shard_index && !finished.contains_key(branch))
+        .collect();
+
+    let bar = progress.bar(pending.len() as u64);
+    let searched = pending
+        .into_par_iter()
+        .map(|(branch, combo)| {
+            let samples = dfs_branch(combo, &bounds);
+            if let Some(writer) = &writer {
+                write_branch(&mut writer.lock().unwrap(), branch, &samples)?;
+            }
+            bar.inc(1);
+            Ok((branch, samples))
+        })
+        .collect::<Result<Vec<_>, ClosureCheckpointError>>()?;
+    bar.finish();
+
+    finished.extend(searched);
+
+    Ok(finished.into_values().flatten().collect())
+}
+
+/// Checks that `shard_index` names one of `num_shards` shards, which also rules out 0 shards.
+pub fn check_shard(shard_index: usize, num_shards: usize) -> Result<(), ClosureCheckpointError> {
+    if shard_index >= num_shards {
+        return Err(ClosureCheckpointError::Shard(shard_index, num_shards));
+    }
+    Ok(())
+}
+
+/// Appends one finished branch to the checkpoint and flushes it to disk.
+fn write_branch(
+    writer: &mut Writer<File>,
+    branch: usize,
+    samples: &[Vec<i32>],
+) -> Result<(), ClosureCheckpointError> {
+    for sample in samples {
+        let mut record = vec!["sample".to_string(), branch.to_string()];
+        record.extend(sample.iter().map(|v| v.to_string()));
+        writer.write_record(&record)?;
+    }
+    writer.write_record(["done".to_string(), branch.to_string(), samples.len().to_string()])?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the finished branches from a checkpoint file.
+///
+/// Reading stops at the first record which cannot be parsed, which can only be a truncated
+/// last line. A block of samples is only kept if it is followed by a `done` row with the same
+/// branch and the right number of samples.
+fn read_checkpoint(
+    path: &str,
+    header: &[String],
+    n: usize,
+) -> Result<BTreeMap<usize, Vec<Vec<i32>>>, ClosureCheckpointError> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_path(path)?;
+    let mut records = reader.records();
+
+    let mut finished = BTreeMap::new();
+
+    // an empty file has no progress to resume from
+    let Some(first) = records.next() else {
+        return Ok(finished);
+    };
+    let first: Vec<String> = first?.iter().map(|s| s.to_string()).collect();
+    if first != header {
+        return Err(ClosureCheckpointError::Mismatch(path.to_string(), header.to_vec(), first));
+    }
+
+    let mut block: Option<(usize, Vec<Vec<i32>>)> = None;
+
+    for record in records {
+        let Ok(record) = record else { break };
+        let branch: Option<usize> = record.get(1).and_then(|b| b.parse().ok());
+
+        match (record.get(0), branch) {
+            (Some("sample"), Some(branch)) => {
+                let sample: Option<Vec<i32>> = record.iter().skip(2).map(|v| v.parse().ok()).collect();
+                let Some(sample) = sample.filter(|s| s.len() == n) else { break };
+
+                match &mut block {
+                    Some((b, samples)) if *b == branch => samples.push(sample),
+                    _ => block = Some((branch, vec![sample])),
+                }
+            }
+            (Some("done"), Some(branch)) => {
+                let Some(count) = record.get(2).and_then(|c| c.parse::<usize>().ok()) else { break };
+
+                let samples = match block.take() {
+                    Some((b, samples)) if b == branch => samples,
+                    _ => Vec::new(),
+                };
+                if samples.len() == count {
+                    finished.insert(branch, samples);
+                }
+            }
+            _ => break,
+        }
+    }
+
+    Ok(finished)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::closure::dfs_parallel;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("scrutipy_{}_{name}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    fn sorted(mut samples: Vec<Vec<i32>>) -> Vec<Vec<i32>> {
+        samples.sort();
+        samples
+    }
+
+    #[test]
+    fn shards_cover_everything() {
+        let full = sorted(dfs_parallel(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1));
+        let shards: Vec<Vec<i32>> = (0..3)
+            .flat_map(|i| dfs_checkpointed(3.5, 1.2, 50, 0, 7, 0.05, 0.005, 1, i, 3, None, false, Progress::Never).unwrap())
+            .collect();
+        assert_eq!(sorted(shards), full);
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let path = temp_path("resume");
+        let first = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), false, Progress::Never).unwrap();
+        assert_eq!(first.len(), 568);
+
+        // everything is finished, so resuming only reads the file
+        let resumed = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), true, Progress::Never).unwrap();
+        assert_eq!(resumed, first);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resume_from_truncated_checkpoint() {
+        let path = temp_path("truncated");
+        let first = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), false, Progress::Never).unwrap();
+
+        // simulate a crash partway through writing the file
+        let contents = fs::read(&path).unwrap();
+        fs::write(&path, &contents[..contents.len() * 2 / 3 + 7]).unwrap();
+
+        let resumed = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), true, Progress::Never).unwrap();
+        assert_eq!(sorted(resumed), sorted(first.clone()));
+
+        // the rewritten checkpoint has replaced the old one and records every branch
+        assert!(!Path::new(&format!("{path}.tmp")).exists());
+        let header = fs::read_to_string(&path).unwrap().lines().next().unwrap().split(',').map(String::from).collect::<Vec<_>>();
+        let finished = read_checkpoint(&path, &header, 100).unwrap();
+        assert_eq!(sorted(finished.into_values().flatten().collect()), sorted(first));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuse_to_overwrite() {
+        let path = temp_path("exists");
+        fs::write(&path, "").unwrap();
+        let err = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), false, Progress::Never).unwrap_err();
+        assert!(matches!(err, ClosureCheckpointError::Exists(_)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuse_other_problem() {
+        let path = temp_path("mismatch");
+        dfs_checkpointed(10.0, 2.0, 3, 1, 5, 0.1, 0.1, 1, 0, 1, Some(&path), false, Progress::Never).unwrap();
+        let err = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 0, 1, Some(&path), true, Progress::Never).unwrap_err();
+        assert!(matches!(err, ClosureCheckpointError::Mismatch(..)));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_shard() {
+        let err = dfs_checkpointed(3.5, 0.57, 100, 0, 7, 0.05, 0.05, 1, 2, 2, None, false, Progress::Never).unwrap_err();
+        assert!(matches!(err, ClosureCheckpointError::Shard(2, 2)));
+        assert!(matches!(check_shard(3, 1), Err(ClosureCheckpointError::Shard(3, 1))));
+        assert!(matches!(check_shard(0, 0), Err(ClosureCheckpointError::Shard(0, 0))));
+        assert!(check_shard(0, 1).is_ok());
+    }
+}
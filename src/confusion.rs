@@ -1,362 +1,1588 @@
+//! Reconstructing confusion matrices from reported diagnostic accuracy metrics.
+//!
+//! Each search enumerates every confusion matrix with a given sample size, scores it against
+//! any set of reported metrics, and keeps the matrices closest to them. The positives (TP + FN) are
+//! enumerated first, so that TP fixes FN and TN fixes FP, and the search runs in parallel over
+//! (positives, TP) pairs, keeping only the best `top_n` candidates in a bounded heap.
+//!
+//! Metrics reported as strings are matched with their rounding in mind, as in GRIM: a candidate
+//! matches if its metric rounds to the reported value, so that a search with no matching
+//! candidate shows that the reported metrics are impossible for that sample size.
+
 use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::ops::RangeInclusive;
+
+use polars::prelude::{Column, DataFrame, PolarsResult};
+use pyo3::{exceptions::{PyImportError, PyValueError}, pyfunction, types::{PyAnyMethods, PyDict, PyDictMethods, PyList, PyListMethods}, Bound, FromPyObject, IntoPyObject, PyErr, PyObject, PyResult, Python};
+use pyo3_polars::PyDataFrame;
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::debit::unround;
+use crate::distributions::{beta_inc_inv, normal_quantile};
+use crate::grim::is_near;
+use crate::progress::Progress;
+use crate::utils::{decimal_places_scalar, reround};
+
+/// The rounding schemes accepted by `reround()`.
+pub(crate) const ROUNDING_SCHEMES: [&str; 12] = [
+    "up_or_down", "up_from_or_down_from", "ceiling_or_floor", "even", "up", "down", "up_from",
+    "down_from", "ceiling", "floor", "trunc", "anti_trunc",
+];
+
+/// The rounding schemes for which `unround()` can find the interval of values which round to a
+/// reported number.
+pub(crate) const UNROUND_SCHEMES: [&str; 8] = ["up_or_down", "up", "down", "even", "ceiling", "floor", "trunc", "anti_trunc"];
+
+/// The metrics which can be computed from a confusion matrix.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Metric {
+    Sensitivity,
+    Specificity,
+    Ppv,
+    Npv,
+    Accuracy,
+    Prevalence,
+    Plr,
+    Nlr,
+    Dor,
+    F1,
+    YoudenJ,
+    /// Cohen's kappa, reading the matrix as an agreement table of two raters.
+    Kappa,
+    /// The observed agreement of two raters, which is the accuracy under another name.
+    Agreement,
+}
+
+/// The number of variants of `Metric`, and so the length of the array of metrics returned by
+/// `metrics_from_counts()`.
+const N_METRICS: usize = 13;
+
+impl Metric {
+    const ALL: [Metric; N_METRICS] = [
+        Metric::Sensitivity,
+        Metric::Specificity,
+        Metric::Ppv,
+        Metric::Npv,
+        Metric::Accuracy,
+        Metric::Prevalence,
+        Metric::Plr,
+        Metric::Nlr,
+        Metric::Dor,
+        Metric::F1,
+        Metric::YoudenJ,
+        Metric::Kappa,
+        Metric::Agreement,
+    ];
+
+    /// The name of the metric in output columns, e.g. `Calculated_Sensitivity`.
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Sensitivity => "Sensitivity",
+            Metric::Specificity => "Specificity",
+            Metric::Ppv => "PPV",
+            Metric::Npv => "NPV",
+            Metric::Accuracy => "Accuracy",
+            Metric::Prevalence => "Prevalence",
+            Metric::Plr => "PLR",
+            Metric::Nlr => "NLR",
+            Metric::Dor => "DOR",
+            Metric::F1 => "F1",
+            Metric::YoudenJ => "Youden_J",
+            Metric::Kappa => "Kappa",
+            Metric::Agreement => "Agreement",
+        }
+    }
+
+    /// Reads a metric from a key of the dict passed to `reconstruct_confusion()`, ignoring case.
+    fn from_key(key: &str) -> Result<Self, ConfusionError> {
+        match key.to_lowercase().as_str() {
+            "sensitivity" | "sens" => Ok(Metric::Sensitivity),
+            "specificity" | "spec" => Ok(Metric::Specificity),
+            "ppv" => Ok(Metric::Ppv),
+            "npv" => Ok(Metric::Npv),
+            "accuracy" => Ok(Metric::Accuracy),
+            "prevalence" => Ok(Metric::Prevalence),
+            "plr" | "lr+" | "+lr" => Ok(Metric::Plr),
+            "nlr" | "lr-" | "-lr" => Ok(Metric::Nlr),
+            "dor" => Ok(Metric::Dor),
+            "f1" => Ok(Metric::F1),
+            "youden_j" | "youden" | "j" => Ok(Metric::YoudenJ),
+            "kappa" | "cohens_kappa" => Ok(Metric::Kappa),
+            "agreement" | "observed_agreement" => Ok(Metric::Agreement),
+            _ => Err(ConfusionError::UnknownMetric(key.to_string())),
+        }
+    }
+}
+
+/// Computes every `Metric` of a confusion matrix, indexed by `Metric as usize`.
+///
+/// Metrics which are undefined for the matrix are NaN: proportions with an empty denominator,
+/// and ratios of 0 to 0. Ratios of a positive number to 0 are infinite.
+pub fn metrics_from_counts(tp: u32, tn: u32, fp: u32, f_n: u32) -> [f64; N_METRICS] {
+    let proportion = |num: u32, den: u32| if den != 0 { num as f64 / den as f64 } else { f64::NAN };
+
+    let sensitivity = proportion(tp, tp + f_n);
+    let specificity = proportion(tn, tn + fp);
+
+    // written in terms of counts so that a zero denominator is exactly zero, which gives infinity
+    // or NaN by IEEE division
+    let plr = sensitivity / proportion(fp, tn + fp);
+    let nlr = proportion(f_n, tp + f_n) / specificity;
+    let dor = (tp as f64 * tn as f64) / (fp as f64 * f_n as f64);
 
-use pyo3::{pyfunction, types::{PyDict, PyDictMethods, PyList, PyListMethods}, PyObject, PyResult, Python};
-use indicatif::ProgressIterator;
+    let mut metrics = [0.0; N_METRICS];
+    metrics[Metric::Sensitivity as usize] = sensitivity;
+    metrics[Metric::Specificity as usize] = specificity;
+    metrics[Metric::Ppv as usize] = proportion(tp, tp + fp);
+    metrics[Metric::Npv as usize] = proportion(tn, tn + f_n);
+    metrics[Metric::Accuracy as usize] = proportion(tp + tn, tp + tn + fp + f_n);
+    metrics[Metric::Prevalence as usize] = proportion(tp + f_n, tp + tn + fp + f_n);
+    metrics[Metric::Plr as usize] = plr;
+    metrics[Metric::Nlr as usize] = nlr;
+    metrics[Metric::Dor as usize] = dor;
+    metrics[Metric::F1 as usize] = proportion(2 * tp, 2 * tp + fp + f_n);
+    metrics[Metric::YoudenJ as usize] = sensitivity + specificity - 1.0;
+    metrics[Metric::Kappa as usize] = cohens_kappa(tp, tn, fp, f_n).0;
+    metrics[Metric::Agreement as usize] = metrics[Metric::Accuracy as usize];
+    metrics
+}
+
+/// Cohen's kappa of a 2x2 agreement table, with the observed and expected agreement.
+///
+/// The table is read as a confusion matrix with one rater as the test and the other as the
+/// reference: TP and TN are the cases both raters call positive and negative, FP the cases only
+/// the first rater calls positive, and FN the cases only the second does.
+///
+/// # Returns
+///
+/// (kappa, observed agreement, expected agreement). Kappa is NaN if the expected agreement is 1,
+/// and everything is NaN for an empty table.
+pub fn cohens_kappa(tp: u32, tn: u32, fp: u32, f_n: u32) -> (f64, f64, f64) {
+    let n = (tp + tn + fp + f_n) as f64;
+    let observed = (tp + tn) as f64 / n;
+    let expected = ((tp + fp) as f64 * (tp + f_n) as f64 + (tn + f_n) as f64 * (tn + fp) as f64) / (n * n);
+    ((observed - expected) / (1.0 - expected), observed, expected)
+}
+
+/// A metric as reported in a paper, either as a string, which keeps its trailing zeros and so
+/// its precision, or as a number.
+#[derive(FromPyObject)]
+pub enum ReportedMetric {
+    Str(String),
+    Num(f64),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfusionError {
+    #[error("The reported metric {0} is not a number")]
+    NotANumber(String),
+    #[error("{0} is not a valid rounding scheme. Use one of {1:?}")]
+    InvalidRounding(String, Vec<&'static str>),
+    #[error("The rounding scheme {0} requires a threshold other than the default, 5.0")]
+    ThresholdRequired(String),
+    #[error("{0} is not a known metric. Use sensitivity, specificity, ppv, npv, accuracy, prevalence, plr, nlr, dor, f1, youden_j, kappa or agreement")]
+    UnknownMetric(String),
+    #[error("At least one metric or confidence interval must be given")]
+    NoMetrics,
+    #[error("{0} is not a valid confidence interval method. Use \"wilson\" or \"clopper_pearson\"")]
+    InvalidCiMethod(String),
+    #[error("The confidence level must lie strictly between 0 and 1, but got {0}")]
+    InvalidCiLevel(f64),
+    #[error("Confidence intervals for {0} are not supported")]
+    CiUnsupported(&'static str),
+    #[error("{0} is not a valid output. Use \"dicts\", \"polars\" or \"pandas\"")]
+    InvalidOutput(String),
+    #[error("{0} was given more than once, possibly under another of its names")]
+    DuplicateMetric(&'static str),
+}
+
+impl From<ConfusionError> for PyErr {
+    fn from(err: ConfusionError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Decides whether a calculated metric matches a reported one.
+struct MatchRule {
+    metric: Metric,
+    reported: f64,
+    kind: MatchKind,
+}
+
+enum MatchKind {
+    /// A metric reported as a number matches if it is within the tolerance.
+    Tolerance(f64),
+    /// A metric reported as a string matches if it rounds to the reported value with the given
+    /// number of decimal places. The interval from `unround()`, where there is one, rules out
+    /// most candidates before the costlier `reround()`.
+    Rounding {
+        digits: i32,
+        interval: Option<(f64, f64)>,
+        rounding: String,
+        threshold: f64,
+        symmetric: bool,
+    },
+}
+
+impl MatchRule {
+    fn new(
+        metric: Metric,
+        reported: &ReportedMetric,
+        tolerance: f64,
+        rounding: &str,
+        threshold: f64,
+        symmetric: bool,
+    ) -> Result<Self, ConfusionError> {
+        if !ROUNDING_SCHEMES.contains(&rounding) {
+            return Err(ConfusionError::InvalidRounding(rounding.to_string(), ROUNDING_SCHEMES.to_vec()));
+        }
+        if rounding.contains("_from") && threshold == 5.0 {
+            return Err(ConfusionError::ThresholdRequired(rounding.to_string()));
+        }
+
+        let reported_str = match reported {
+            ReportedMetric::Num(x) => {
+                return Ok(MatchRule { metric, reported: *x, kind: MatchKind::Tolerance(tolerance) });
+            }
+            ReportedMetric::Str(s) => s.trim(),
+        };
 
-struct SnspnReturn {
+        let reported: f64 = reported_str
+            .parse()
+            .map_err(|_| ConfusionError::NotANumber(reported_str.to_string()))?;
+        let digits = decimal_places_scalar(Some(reported_str), ".");
+
+        // unround() needs decimal places, and anti_trunc has no interval around 0
+        let interval = match digits {
+            Some(_) if UNROUND_SCHEMES.contains(&rounding) && !(rounding == "anti_trunc" && reported == 0.0) => {
+                unround(reported_str, rounding, threshold).ok().map(|bounds| {
+                    // widen the interval slightly, leaving the boundary cases to reround()
+                    (bounds.lower - 1e-9, bounds.upper + 1e-9)
+                })
+            }
+            _ => None,
+        };
+
+        Ok(MatchRule {
+            metric,
+            reported,
+            kind: MatchKind::Rounding {
+                digits: digits.unwrap_or(0),
+                interval,
+                rounding: rounding.to_string(),
+                threshold,
+                symmetric,
+            },
+        })
+    }
+
+    /// How far a calculated value may lie from the reported value and still match it.
+    fn slack(&self) -> f64 {
+        match &self.kind {
+            MatchKind::Tolerance(tolerance) => *tolerance,
+            // rounding to `digits` decimal places never moves a value by a whole unit
+            MatchKind::Rounding { digits, .. } => 10f64.powi(-digits),
+        }
+    }
+
+    /// An interval which contains every matching value, if one is known without rounding.
+    fn bounds(&self) -> Option<(f64, f64)> {
+        match &self.kind {
+            MatchKind::Tolerance(tolerance) => Some((self.reported - tolerance, self.reported + tolerance)),
+            MatchKind::Rounding { interval, .. } => *interval,
+        }
+    }
+
+    fn matches(&self, calculated: f64) -> bool {
+        match &self.kind {
+            MatchKind::Tolerance(tolerance) => (calculated - self.reported).abs() <= *tolerance,
+            MatchKind::Rounding { digits, interval, rounding, threshold, symmetric } => {
+                if !calculated.is_finite() {
+                    return false;
+                }
+                if let Some((lower, upper)) = interval {
+                    if calculated < *lower || calculated > *upper {
+                        return false;
+                    }
+                }
+                reround(vec![calculated], *digits, rounding, *threshold, *symmetric)
+                    .into_iter()
+                    .any(|rounded| is_near(rounded, self.reported, f64::EPSILON.powf(0.5)))
+            }
+        }
+    }
+}
+
+/// A candidate confusion matrix, scored against the reported metrics.
+///
+/// `calculated` holds every metric and `errors` the error of each reported metric, both indexed
+/// by `Metric as usize`. The errors of metrics which were not reported are 0.
+#[derive(Clone, Copy, Debug)]
+struct Candidate {
     tp: u32,
     tn: u32,
     fp: u32,
     f_n: u32,
-    calc_sens: f32,
-    calc_spec: f32,
-    sens_error: f32,
-    spec_error: f32,
-    total_error: f32,
+    calculated: [f64; N_METRICS],
+    errors: [f64; N_METRICS],
+    total_error: f64,
     exact_match: bool,
 }
 
-#[pyfunction(signature = (sensitivity, specificity, sample_size, tolerance=1e-6, n_positive=None, top_n=None))]
-pub fn calculate_snspn(
-    py: Python,
-    sensitivity: f32, 
-    specificity: f32, 
-    sample_size: u32, 
-    tolerance: f32, 
-    n_positive: Option<u32>,
-    top_n: Option<u32>,
-) -> PyResult<PyObject> {
-    let mut results = Vec::new();
+// Candidates are ordered by total error, with ties broken by (TP, TN, FP) so that the output
+// does not depend on how the parallel search was scheduled.
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.total_error
+            .total_cmp(&other.total_error)
+            .then_with(|| (self.tp, self.tn, self.fp).cmp(&(other.tp, other.tn, other.fp)))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-    let n_path_bool = n_positive.is_some();
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
 
-    for tp in (0..sample_size+1).progress() { // because apparently std::iter::ExactSizeIterator is
-        // not implemented on inclusive ranges https://github.com/rust-lang/rust/issues/36386
-        for tn in 0..=(sample_size - tp) {
-            for fp in 0..=(sample_size - tp - tn) {
-                let f_n = sample_size - tp - tn - fp;
+impl Eq for Candidate {}
 
-                if (tp + tn + fp + f_n) != sample_size {
-                    continue
-                }
-                if n_path_bool && (tp + f_n) != n_positive.unwrap() {
-                    continue
+/// Which candidates `search_tables()` keeps, and in what order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Ranking {
+    /// Every candidate, by total error alone, as the `calculate_*` functions have always ranked
+    /// them.
+    ByError,
+    /// Every candidate, with exact matches ahead of the rest.
+    ExactFirst,
+    /// Only the candidates which match every reported metric.
+    ExactOnly,
+}
+
+impl Ranking {
+    fn new(exact_only: bool) -> Self {
+        if exact_only { Ranking::ExactOnly } else { Ranking::ExactFirst }
+    }
+}
+
+/// Keeps the `capacity` best candidates under a `Ranking`, or every candidate if `capacity` is
+/// None.
+///
+/// The heap is a max-heap, so its top is the worst candidate kept so far, and is the one
+/// replaced when a better candidate arrives. Each candidate is keyed by whether it is ranked
+/// after the exact matches, then by its own order.
+struct TopN {
+    capacity: Option<usize>,
+    ranking: Ranking,
+    heap: BinaryHeap<(bool, Candidate)>,
+}
+
+impl TopN {
+    fn new(capacity: Option<usize>, ranking: Ranking) -> Self {
+        TopN { capacity, ranking, heap: BinaryHeap::new() }
+    }
+
+    fn push(&mut self, candidate: Candidate) {
+        let entry = (self.ranking == Ranking::ExactFirst && !candidate.exact_match, candidate);
+        match self.capacity {
+            Some(capacity) if self.heap.len() >= capacity => {
+                if self.heap.peek().is_some_and(|worst| entry < *worst) {
+                    self.heap.pop();
+                    self.heap.push(entry);
                 }
+            }
+            _ => self.heap.push(entry),
+        }
+    }
+
+    fn merge(mut self, other: TopN) -> Self {
+        for (_, candidate) in other.heap {
+            self.push(candidate);
+        }
+        self
+    }
 
-                let calc_sens = if tp + f_n != 0 {
-                    tp as f32 / (tp + f_n) as f32
-                } else {
-                    0.0
-                };
-
-                let calc_spec = if tn + fp != 0 {
-                    tn as f32 / (tn + fp) as f32
-                } else {
-                    0.0
-                };
-
-                let sens_error = (sensitivity - calc_sens).abs();
-                let spec_error = (specificity - calc_spec).abs();
-                let total_error = sens_error + spec_error;
-
-                results.push(SnspnReturn { 
-                    tp,
-                    tn,
-                    fp, 
-                    f_n, 
-                    calc_sens,
-                    calc_spec,
-                    sens_error,
-                    spec_error,
-                    total_error,
-                    exact_match: total_error <= tolerance,
-                });
+    /// The candidates kept, from the best to the worst.
+    fn into_sorted_vec(self) -> Vec<Candidate> {
+        self.heap.into_sorted_vec().into_iter().map(|(_, candidate)| candidate).collect()
+    }
+}
+
+/// The range of TN which can match the reported metrics among the tables with `p` positives and
+/// `tp` true positives, so that the search need not try every TN.
+///
+/// With p and TP fixed, FN is fixed and FP falls as TN rises, so specificity TN / (N - p), NPV
+/// TN / (TN + FN), accuracy (TP + TN) / N and PPV TP / (TP + FP) all rise with TN, and the
+/// interval of each reported metric solves to an interval of TN. The range is widened slightly
+/// against floating point error, and each table in it is still checked against every metric.
+fn tn_range(sample_size: u32, p: u32, tp: u32, rules: &[MatchRule]) -> RangeInclusive<u32> {
+    let negatives = (sample_size - p) as f64;
+    let (n, tp, f_n) = (sample_size as f64, tp as f64, (p as f64) - tp as f64);
+    let (mut lower, mut upper) = (0.0f64, negatives);
+
+    for rule in rules {
+        let Some((lo, hi)) = rule.bounds() else { continue };
+        match rule.metric {
+            Metric::Specificity => {
+                lower = lower.max(lo * negatives);
+                upper = upper.min(hi * negatives);
+            }
+            Metric::Accuracy | Metric::Agreement => {
+                lower = lower.max(lo * n - tp);
+                upper = upper.min(hi * n - tp);
             }
+            // TN >= lo (TN + FN) and TN <= hi (TN + FN)
+            Metric::Npv => {
+                if lo < 1.0 {
+                    lower = lower.max(lo * f_n / (1.0 - lo));
+                }
+                if hi < 1.0 {
+                    upper = upper.min(hi * f_n / (1.0 - hi));
+                }
+            }
+            // TP >= lo (TP + FP) and TP <= hi (TP + FP), with FP = N - p - TN
+            Metric::Ppv => {
+                if lo > 0.0 {
+                    lower = lower.max(negatives - tp * (1.0 - lo) / lo);
+                }
+                if hi > 0.0 {
+                    upper = upper.min(negatives - tp * (1.0 - hi) / hi);
+                }
+            }
+            _ => {}
         }
     }
 
-    results.sort_by(|a, b| 
-        a.total_error.partial_cmp(&b.total_error).unwrap_or(Ordering::Equal) 
-    );
+    let fuzz = 1e-6;
+    ((lower - fuzz).ceil().max(0.0) as u32)..=((upper + fuzz).floor().min(negatives) as u32)
+}
+
+/// Searches all confusion matrices with `sample_size` cases for those whose metrics are closest
+/// to the reported ones.
+///
+/// # Arguments
+///
+/// - `sample_size`: The total number of cases, TP + TN + FP + FN.
+/// - `n_positive`: If given, only matrices with TP + FN equal to it are searched.
+/// - `top_n`: The number of candidates to keep, or None to keep them all.
+/// - `rules`: The reported metrics, at most one per `Metric`, and how to decide whether a
+///   candidate matches them.
+/// - `ranking`: Which candidates to keep and how to order them. With `Ranking::ExactOnly`, the
+///   reported sensitivity and prevalence rule out (positives, TP) pairs, and the specificity,
+///   NPV, PPV and accuracy bound TN through `tn_range()`, so the search no longer visits every
+///   table.
+/// - `ci_rules`: Reported confidence intervals. Candidates whose intervals do not match them
+///   are left out.
+/// - `progress`: When to draw a progress bar over the (positives, TP) pairs.
+///
+/// # Returns
+///
+/// The candidates, from the best to the worst under `ranking`.
+fn search_tables(
+    sample_size: u32,
+    n_positive: Option<u32>,
+    top_n: Option<usize>,
+    rules: &[MatchRule],
+    ranking: Ranking,
+    ci_rules: &[CiRule],
+    progress: Progress,
+) -> Vec<Candidate> {
+    let exact_only = ranking == Ranking::ExactOnly;
+    let positives: Vec<u32> = match n_positive {
+        Some(p) if p <= sample_size => vec![p],
+        Some(_) => Vec::new(),
+        None => (0..=sample_size).collect(),
+    };
+
+    let bar = progress.bar(positives.iter().map(|&p| p as u64 + 1).sum());
+
+    let top = positives
+        .into_par_iter()
+        .flat_map(|p| (0..=p).into_par_iter().map(move |tp| (p, tp)))
+        .fold(
+            || TopN::new(top_n, ranking),
+            |mut top, (p, tp)| {
+                let f_n = p - tp;
+
+                // sensitivity and prevalence do not depend on TN
+                let fixed = metrics_from_counts(tp, 0, sample_size - p, f_n);
+                if exact_only && !rules.iter().all(|rule| {
+                    !matches!(rule.metric, Metric::Sensitivity | Metric::Prevalence)
+                        || rule.matches(fixed[rule.metric as usize])
+                }) {
+                    bar.inc(1);
+                    return top;
+                }
+
+                let tns = if exact_only { tn_range(sample_size, p, tp, rules) } else { 0..=(sample_size - p) };
+                for tn in tns {
+                    let fp = sample_size - p - tn;
+                    if !ci_rules.iter().all(|rule| rule.matches(tp, tn, fp, f_n)) {
+                        continue;
+                    }
+
+                    let calculated = metrics_from_counts(tp, tn, fp, f_n);
+                    let exact_match = rules
+                        .iter()
+                        .all(|rule| rule.matches(calculated[rule.metric as usize]));
+                    if exact_only && !exact_match {
+                        continue;
+                    }
 
-    // taking just the n smallest total_errors
-    let results: Vec<SnspnReturn> = if let Some(n) = top_n {
-        if n < sample_size {
-            results.into_iter().take(n as usize).collect()
-        } else { results }
-    } else { results };
+                    let mut errors = [0.0; N_METRICS];
+                    for rule in rules {
+                        errors[rule.metric as usize] = (rule.reported - calculated[rule.metric as usize]).abs();
+                    }
+                    top.push(Candidate {
+                        tp,
+                        tn,
+                        fp,
+                        f_n,
+                        calculated,
+                        errors,
+                        total_error: errors.iter().sum(),
+                        exact_match,
+                    });
+                }
+                bar.inc(1);
+                top
+            },
+        )
+        .reduce(|| TopN::new(top_n, ranking), TopN::merge);
+
+    bar.finish();
+    top.into_sorted_vec()
+}
 
+/// The names of the TP, TN, FP and FN columns of a confusion matrix.
+const CONFUSION_CELLS: [&str; 4] = ["TP", "TN", "FP", "FN"];
+
+/// The names of the same columns for an agreement table of two raters, see `cohens_kappa()`.
+const AGREEMENT_CELLS: [&str; 4] = ["Both_Yes", "Both_No", "Yes_No", "No_Yes"];
+
+/// Converts candidates into a list of dicts, with a column for each of the four `cells`, and a
+/// calculated value and an error column for each reported metric.
+fn candidates_to_dicts(
+    py: Python,
+    candidates: Vec<Candidate>,
+    rules: &[MatchRule],
+    cells: [&str; 4],
+) -> PyResult<PyObject> {
     let dicts = PyList::empty(py);
 
-    for result in results {
+    for candidate in candidates {
         let dict = PyDict::new(py);
-        dict.set_item("TP", result.tp)?;
-        dict.set_item("TN", result.tn)?;
-        dict.set_item("FP", result.fp)?;
-        dict.set_item("FN", result.f_n)?;
-        dict.set_item("Calculated_Sensitivity", result.calc_sens)?;
-        dict.set_item("Calculated_Specificity", result.calc_spec)?;
-        dict.set_item("Sensitivity_Error", result.sens_error)?;
-        dict.set_item("Specificity_Error", result.spec_error)?;
-        dict.set_item("Total_Error", result.total_error)?;
-        dict.set_item("Exact_Match", result.exact_match)?;
+        dict.set_item(cells[0], candidate.tp)?;
+        dict.set_item(cells[1], candidate.tn)?;
+        dict.set_item(cells[2], candidate.fp)?;
+        dict.set_item(cells[3], candidate.f_n)?;
+        for rule in rules {
+            dict.set_item(format!("Calculated_{}", rule.metric.name()), candidate.calculated[rule.metric as usize])?;
+        }
+        for rule in rules {
+            dict.set_item(format!("{}_Error", rule.metric.name()), candidate.errors[rule.metric as usize])?;
+        }
+        dict.set_item("Total_Error", candidate.total_error)?;
+        dict.set_item("Exact_Match", candidate.exact_match)?;
 
         dicts.append(dict)?;
     }
     Ok(dicts.into())
 }
 
-struct PpvReturn {
-    tp: u32,
-    tn: u32,
-    fp: u32,
-    f_n: u32,
-    calc_ppv: f32,
-    calc_npv: f32,
-    ppv_error: f32,
-    npv_error: f32,
-    total_error: f32,
-    exact_match: bool,
+/// The form in which the search functions return their tables.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Output {
+    /// A list of dicts, one per table.
+    Dicts,
+    /// A Polars DataFrame, built in Rust.
+    Polars,
+    /// A pandas DataFrame, converted from the Polars one.
+    Pandas,
 }
 
-#[pyfunction(signature = (ppv, npv, sample_size, tolerance=1e-6, n_positive=None, top_n=None))]
+impl Output {
+    fn new(output: &str) -> Result<Self, ConfusionError> {
+        match output {
+            "dicts" => Ok(Output::Dicts),
+            "polars" => Ok(Output::Polars),
+            "pandas" => Ok(Output::Pandas),
+            other => Err(ConfusionError::InvalidOutput(other.to_string())),
+        }
+    }
+}
+
+/// Builds a DataFrame of candidates, with the same columns as `candidates_to_dicts()`.
+fn candidates_to_frame(candidates: &[Candidate], rules: &[MatchRule], cells: [&str; 4]) -> PolarsResult<DataFrame> {
+    let counts = |name: &str, count: fn(&Candidate) -> u32| {
+        Column::new(name.into(), candidates.iter().map(count).collect::<Vec<u32>>())
+    };
+
+    let mut columns = vec![
+        counts(cells[0], |c| c.tp),
+        counts(cells[1], |c| c.tn),
+        counts(cells[2], |c| c.fp),
+        counts(cells[3], |c| c.f_n),
+    ];
+    for rule in rules {
+        let calculated: Vec<f64> = candidates.iter().map(|c| c.calculated[rule.metric as usize]).collect();
+        columns.push(Column::new(format!("Calculated_{}", rule.metric.name()).into(), calculated));
+    }
+    for rule in rules {
+        let errors: Vec<f64> = candidates.iter().map(|c| c.errors[rule.metric as usize]).collect();
+        columns.push(Column::new(format!("{}_Error", rule.metric.name()).into(), errors));
+    }
+    columns.push(Column::new("Total_Error".into(), candidates.iter().map(|c| c.total_error).collect::<Vec<f64>>()));
+    columns.push(Column::new("Exact_Match".into(), candidates.iter().map(|c| c.exact_match).collect::<Vec<bool>>()));
+
+    DataFrame::new(columns)
+}
+
+/// Converts candidates into the requested output.
+fn candidates_to_output(
+    py: Python,
+    candidates: Vec<Candidate>,
+    rules: &[MatchRule],
+    output: Output,
+    cells: [&str; 4],
+) -> PyResult<PyObject> {
+    if output == Output::Dicts {
+        return candidates_to_dicts(py, candidates, rules, cells);
+    }
+
+    let frame = candidates_to_frame(&candidates, rules, cells)
+        .map_err(|err| PyValueError::new_err(err.to_string()))?;
+    let frame = PyDataFrame(frame).into_pyobject(py)?;
+
+    if output == Output::Pandas {
+        py.import("pandas").map_err(|_| {
+            PyImportError::new_err(
+                "The 'pandas' package is required for output=\"pandas\" but is not installed.\n\
+                    You can install it with: pip install pandas"
+            )
+        })?;
+        return Ok(frame.call_method0("to_pandas")?.unbind());
+    }
+    Ok(frame.unbind())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (sensitivity, specificity, sample_size, tolerance=1e-6, n_positive=None, top_n=None, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false, progress=None, output="dicts"))]
+pub fn calculate_snspn(
+    py: Python,
+    sensitivity: ReportedMetric,
+    specificity: ReportedMetric,
+    sample_size: u32,
+    tolerance: f64,
+    n_positive: Option<u32>,
+    top_n: Option<usize>,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+    progress: Option<&str>,
+    output: &str,
+) -> PyResult<PyObject> {
+    let progress = Progress::parse(progress)?;
+    let output = Output::new(output)?;
+    let rules = [
+        MatchRule::new(Metric::Sensitivity, &sensitivity, tolerance, &rounding, threshold, symmetric)?,
+        MatchRule::new(Metric::Specificity, &specificity, tolerance, &rounding, threshold, symmetric)?,
+    ];
+    let results = search_tables(sample_size, n_positive, top_n, &rules, Ranking::ByError, &[], progress);
+    candidates_to_output(py, results, &rules, output, CONFUSION_CELLS)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (ppv, npv, sample_size, tolerance=1e-6, n_positive=None, top_n=None, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false, progress=None, output="dicts"))]
 pub fn calculate_ppvnpv(
     py: Python,
-    ppv: f32, 
-    npv: f32, 
-    sample_size: u32, 
-    tolerance: f32, 
+    ppv: ReportedMetric,
+    npv: ReportedMetric,
+    sample_size: u32,
+    tolerance: f64,
+    n_positive: Option<u32>,
+    top_n: Option<usize>,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+    progress: Option<&str>,
+    output: &str,
+) -> PyResult<PyObject> {
+    let progress = Progress::parse(progress)?;
+    let output = Output::new(output)?;
+    let rules = [
+        MatchRule::new(Metric::Ppv, &ppv, tolerance, &rounding, threshold, symmetric)?,
+        MatchRule::new(Metric::Npv, &npv, tolerance, &rounding, threshold, symmetric)?,
+    ];
+    let results = search_tables(sample_size, n_positive, top_n, &rules, Ranking::ByError, &[], progress);
+    candidates_to_output(py, results, &rules, output, CONFUSION_CELLS)
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (plr, nlr, sample_size, tolerance=1e-6, n_positive=None, top_n=None, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false, progress=None, output="dicts"))]
+pub fn calculate_likelihoodratios(
+    py: Python,
+    plr: ReportedMetric,
+    nlr: ReportedMetric,
+    sample_size: u32,
+    tolerance: f64,
     n_positive: Option<u32>,
-    top_n: Option<u32>,
+    top_n: Option<usize>,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+    progress: Option<&str>,
+    output: &str,
 ) -> PyResult<PyObject> {
-    let mut results = Vec::new();
+    let progress = Progress::parse(progress)?;
+    let output = Output::new(output)?;
+    let rules = [
+        MatchRule::new(Metric::Plr, &plr, tolerance, &rounding, threshold, symmetric)?,
+        MatchRule::new(Metric::Nlr, &nlr, tolerance, &rounding, threshold, symmetric)?,
+    ];
+    let results = search_tables(sample_size, n_positive, top_n, &rules, Ranking::ByError, &[], progress);
+    candidates_to_output(py, results, &rules, output, CONFUSION_CELLS)
+}
 
-    let n_path_bool = n_positive.is_some();
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (metrics, sample_size, n_positive=None, tolerance=1e-6, top_n=None, exact_only=false, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false, cis=None, ci_level=0.95, ci_method="wilson", progress=None, output="dicts"))]
+/// Reconstructs the confusion matrices consistent with any combination of reported metrics.
+///
+/// Parameters:
+///     metrics (dict): The reported metrics, keyed by name: sensitivity, specificity, ppv, npv,
+///     accuracy, prevalence, plr, nlr, dor, f1, youden_j, kappa or agreement, ignoring case. Values given as strings,
+///     e.g. "0.71", match when a table's metric rounds to them; values given as numbers match
+///     when within `tolerance`.
+///     sample_size (int): The total number of cases, TP + TN + FP + FN.
+///     n_positive (Optional[int]): The number of positive cases, TP + FN, if known.
+///     tolerance (float): The allowed error for metrics given as numbers. Defaults to 1e-6.
+///     top_n (Optional[int]): The number of tables to return. Defaults to None, which returns all.
+///     exact_only (bool): Whether to return only tables which match every metric. Defaults to False.
+///     rounding (str): The rounding scheme for metrics given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///     cis (Optional[dict]): Reported confidence intervals, as in `check_confusion_ci()`. If
+///     given, only tables whose recomputed intervals match them are returned. Defaults to None.
+///     ci_level (float): The confidence level of the reported intervals. Defaults to 0.95.
+///     ci_method (str): The interval for proportions, either "wilson" or "clopper_pearson".
+///     Defaults to "wilson".
+///     progress (Optional[str]): When to show a progress bar: "auto", only if stderr is a
+///     terminal, "always" or "never". Defaults to None, which uses the mode set by `set_progress()`.
+///     output (str): "dicts" for a list of dicts, "polars" for a Polars DataFrame or "pandas" for a
+///     pandas DataFrame, with the same columns in each case. Defaults to "dicts".
+///
+/// Returns:
+///     List[dict]: One dict per table, with the counts, the calculated value and error of each
+///     reported metric, the total error and whether the table is an exact match. Exact matches
+///     come first, and each group is ordered by total error. With output "polars" or "pandas",
+///     a DataFrame with one row per table instead.
+///
+/// Raises:
+///     ValueError: If neither metrics nor intervals are given, a metric name is unknown or given
+///     twice, e.g. as both "sens" and "sensitivity", a value is not a number, or the rounding scheme, interval settings, progress mode or output are
+///     invalid.
+///     ImportError: If output is "pandas" and pandas is not installed.
+///
+/// Usage Example:
+///     >>> from scrutipy import reconstruct_confusion
+///     >>> tables = reconstruct_confusion({"sensitivity": "0.71", "specificity": "0.75", "prevalence": "0.64"}, 11, exact_only = True)
+///     >>> [(t["TP"], t["TN"], t["FP"], t["FN"]) for t in tables]
+///     [(5, 3, 1, 2)]
+pub fn reconstruct_confusion(
+    py: Python,
+    metrics: Bound<'_, PyDict>,
+    sample_size: u32,
+    n_positive: Option<u32>,
+    tolerance: f64,
+    top_n: Option<usize>,
+    exact_only: bool,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+    cis: Option<Bound<'_, PyDict>>,
+    ci_level: f64,
+    ci_method: &str,
+    progress: Option<&str>,
+    output: &str,
+) -> PyResult<PyObject> {
+    let progress = Progress::parse(progress)?;
+    let output = Output::new(output)?;
+    let settings = CiSettings::new(ci_level, ci_method)?;
+    let ci_rules = extract_ci_rules(cis, tolerance, &rounding, threshold, symmetric, settings, Some(sample_size))?;
 
-    for tp in (0..sample_size+1).progress() { // because apparently std::iter::ExactSizeIterator is
-        // not implemented on inclusive ranges https://github.com/rust-lang/rust/issues/36386
-        for tn in 0..=(sample_size - tp) {
-            for fp in 0..=(sample_size - tp - tn) {
-                let f_n = sample_size - tp - tn - fp;
+    let mut rules: Vec<MatchRule> = Vec::new();
+    for (key, value) in metrics.iter() {
+        let metric = Metric::from_key(&key.extract::<String>()?)?;
+        if rules.iter().any(|rule| rule.metric == metric) {
+            return Err(ConfusionError::DuplicateMetric(metric.name()).into());
+        }
+        let reported = value.extract::<ReportedMetric>()?;
+        rules.push(MatchRule::new(metric, &reported, tolerance, &rounding, threshold, symmetric)?);
+    }
+    if rules.is_empty() && ci_rules.is_empty() {
+        return Err(ConfusionError::NoMetrics.into());
+    }
 
-                if (tp + tn + fp + f_n) != sample_size {
-                    continue
-                }
-                if n_path_bool && (tp + f_n) != n_positive.unwrap() {
-                    continue
-                }
+    let results = search_tables(sample_size, n_positive, top_n, &rules, Ranking::new(exact_only), &ci_rules, progress);
+    candidates_to_output(py, results, &rules, output, CONFUSION_CELLS)
+}
 
-                let calc_ppv = if tp + fp != 0 {
-                    tp as f32 / (tp + fp) as f32
-                } else {
-                    0.0
-                };
-
-                let calc_npv = if tn + f_n != 0 {
-                    tn as f32 / (tn + f_n) as f32
-                } else {
-                    0.0
-                };
-
-                let ppv_error = (ppv - calc_ppv).abs();
-                let npv_error = (npv - calc_npv).abs();
-                let total_error = ppv_error + npv_error;
-
-                results.push(PpvReturn { 
-                    tp,
-                    tn,
-                    fp, 
-                    f_n, 
-                    calc_ppv,
-                    calc_npv,
-                    ppv_error,
-                    npv_error,
-                    total_error,
-                    exact_match: total_error <= tolerance,
-                });
-            }
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (kappa, agreement, sample_size, tolerance=1e-6, top_n=None, exact_only=false, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false, progress=None, output="dicts"))]
+/// Reconstructs the 2x2 agreement tables of two raters consistent with a reported Cohen's kappa
+/// and observed agreement.
+///
+/// Parameters:
+///     kappa (float | str): The reported Cohen's kappa.
+///     agreement (Optional[float | str]): The reported observed agreement, as a proportion
+///     between 0 and 1, or None if it was not reported.
+///     sample_size (int): The number of cases both raters rated.
+///     tolerance (float): The allowed error for values given as numbers. Defaults to 1e-6.
+///     top_n (Optional[int]): The number of tables to return. Defaults to None, which returns all.
+///     exact_only (bool): Whether to return only tables which match every value. Defaults to False.
+///     rounding (str): The rounding scheme for values given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///     progress (Optional[str]): When to show a progress bar, as in `reconstruct_confusion()`.
+///     output (str): "dicts", "polars" or "pandas", as in `reconstruct_confusion()`.
+///
+/// Returns:
+///     List[dict]: One dict per table, with the counts Both_Yes, Both_No, Yes_No (only the first
+///     rater says yes) and No_Yes (only the second rater says yes), the calculated value and
+///     error of each reported value, the total error and whether the table is an exact match.
+///     Exact matches come first, and each group is ordered by total error.
+///
+/// Raises:
+///     ValueError: If a value is not a number, or the rounding scheme, progress mode or output
+///     are invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import calculate_kappa
+///     >>> tables = calculate_kappa("0.40", "0.70", 20, exact_only = True)
+///     >>> len(tables)
+///     13
+///     >>> calculate_kappa("0.47", "0.70", 20, exact_only = True)
+///     []
+///
+/// Notes:
+///     - Values given as strings, e.g. "0.40", match when a table's value rounds to them, so
+///     that a search with no exact match shows that the reported values are impossible for the
+///     sample size. Values given as numbers are ranked by their error.
+///     - Swapping the raters, or the meaning of yes and no, gives another table with the same
+///     kappa and agreement, so consistent tables come in groups.
+pub fn calculate_kappa(
+    py: Python,
+    kappa: ReportedMetric,
+    agreement: Option<ReportedMetric>,
+    sample_size: u32,
+    tolerance: f64,
+    top_n: Option<usize>,
+    exact_only: bool,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+    progress: Option<&str>,
+    output: &str,
+) -> PyResult<PyObject> {
+    let progress = Progress::parse(progress)?;
+    let output = Output::new(output)?;
+
+    let mut rules = vec![MatchRule::new(Metric::Kappa, &kappa, tolerance, &rounding, threshold, symmetric)?];
+    if let Some(agreement) = agreement {
+        rules.push(MatchRule::new(Metric::Agreement, &agreement, tolerance, &rounding, threshold, symmetric)?);
+    }
+
+    let results = search_tables(sample_size, None, top_n, &rules, Ranking::new(exact_only), &[], progress);
+    candidates_to_output(py, results, &rules, output, AGREEMENT_CELLS)
+}
+
+/// The Wilson score interval for a proportion of `x` in `n`, with normal quantile `z`.
+pub fn wilson_interval(x: u32, n: u32, z: f64) -> (f64, f64) {
+    if n == 0 {
+        return (f64::NAN, f64::NAN);
+    }
+    let n = n as f64;
+    let p = x as f64 / n;
+    let denominator = 1.0 + z * z / n;
+    let centre = (p + z * z / (2.0 * n)) / denominator;
+    let half_width = z / denominator * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt();
+    ((centre - half_width).max(0.0), (centre + half_width).min(1.0))
+}
+
+/// The exact Clopper-Pearson interval for a proportion of `x` in `n`, at confidence `level`.
+pub fn clopper_pearson_interval(x: u32, n: u32, level: f64) -> (f64, f64) {
+    if n == 0 {
+        return (f64::NAN, f64::NAN);
+    }
+    let alpha = 1.0 - level;
+    let (x_f, n_f) = (x as f64, n as f64);
+    let lower = if x == 0 { 0.0 } else { beta_inc_inv(x_f, n_f - x_f + 1.0, alpha / 2.0) };
+    let upper = if x == n { 1.0 } else { beta_inc_inv(x_f + 1.0, n_f - x_f, 1.0 - alpha / 2.0) };
+    (lower, upper)
+}
+
+/// A confidence interval for a ratio by the log method: ln(ratio) +/- z * se, where `se` is the
+/// standard error of ln(ratio). The interval is undefined (NaN) when a count in `se` is 0.
+fn log_interval(ratio: f64, se: f64, z: f64) -> (f64, f64) {
+    if !ratio.is_finite() || ratio <= 0.0 || !se.is_finite() {
+        return (f64::NAN, f64::NAN);
+    }
+    ((ratio.ln() - z * se).exp(), (ratio.ln() + z * se).exp())
+}
+
+/// How the confidence interval of a proportion is computed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CiMethod {
+    Wilson,
+    ClopperPearson,
+}
+
+/// The confidence level and method of the intervals.
+#[derive(Clone, Copy, Debug)]
+pub struct CiSettings {
+    level: f64,
+    method: CiMethod,
+    /// The normal quantile for the level, used by the Wilson and log-method intervals.
+    z: f64,
+}
+
+impl CiSettings {
+    pub fn new(level: f64, method: &str) -> Result<Self, ConfusionError> {
+        if !(level > 0.0 && level < 1.0) {
+            return Err(ConfusionError::InvalidCiLevel(level));
         }
+        let method = match method {
+            "wilson" => CiMethod::Wilson,
+            "clopper_pearson" => CiMethod::ClopperPearson,
+            _ => return Err(ConfusionError::InvalidCiMethod(method.to_string())),
+        };
+        Ok(CiSettings { level, method, z: normal_quantile(1.0 - (1.0 - level) / 2.0) })
     }
-    // sort the structs by total_error
-    results.sort_by(|a, b| 
-        a.total_error.partial_cmp(&b.total_error).unwrap_or(Ordering::Equal) 
-    );
 
-    // taking just the n smallest total_errors
-    let results: Vec<PpvReturn> = if let Some(n) = top_n {
-        if n < sample_size {
-            results.into_iter().take(n as usize).collect()
-        } else { results }
-    } else { results };
+    fn proportion(&self, x: u32, n: u32) -> (f64, f64) {
+        match self.method {
+            CiMethod::Wilson => wilson_interval(x, n, self.z),
+            CiMethod::ClopperPearson => clopper_pearson_interval(x, n, self.level),
+        }
+    }
+}
 
-    let dicts = PyList::empty(py);
+/// The counts (x, n) of a metric which is a proportion x / n, or None for other metrics.
+fn proportion_counts(metric: Metric, tp: u32, tn: u32, fp: u32, f_n: u32) -> Option<(u32, u32)> {
+    match metric {
+        Metric::Sensitivity => Some((tp, tp + f_n)),
+        Metric::Specificity => Some((tn, tn + fp)),
+        Metric::Ppv => Some((tp, tp + fp)),
+        Metric::Npv => Some((tn, tn + f_n)),
+        Metric::Accuracy | Metric::Agreement => Some((tp + tn, tp + tn + fp + f_n)),
+        Metric::Prevalence => Some((tp + f_n, tp + tn + fp + f_n)),
+        _ => None,
+    }
+}
 
-    for result in results {
-        let dict = PyDict::new(py);
-        dict.set_item("TP", result.tp)?;
-        dict.set_item("TN", result.tn)?;
-        dict.set_item("FP", result.fp)?;
-        dict.set_item("FN", result.f_n)?;
-        dict.set_item("Calculated_PPV", result.calc_ppv)?;
-        dict.set_item("Calculated_NPV", result.calc_npv)?;
-        dict.set_item("PPV_Error", result.ppv_error)?;
-        dict.set_item("NPV_Error", result.npv_error)?;
-        dict.set_item("Total_Error", result.total_error)?;
-        dict.set_item("Exact_Match", result.exact_match)?;
+/// The confidence interval of one metric of a confusion matrix.
+///
+/// Proportions use the interval chosen in `settings`. The likelihood ratios and the diagnostic
+/// odds ratio use the log method, which is undefined when any count in its standard error is 0.
+/// Kappa uses the large-sample normal interval. F1 and Youden's J have no interval here, so
+/// theirs is NaN.
+fn metric_interval(metric: Metric, tp: u32, tn: u32, fp: u32, f_n: u32, settings: &CiSettings) -> (f64, f64) {
+    if let Some((x, n)) = proportion_counts(metric, tp, tn, fp, f_n) {
+        return settings.proportion(x, n);
+    }
 
-        dicts.append(dict)?;
+    if metric == Metric::Kappa {
+        let (kappa, observed, expected) = cohens_kappa(tp, tn, fp, f_n);
+        let n = (tp + tn + fp + f_n) as f64;
+        let se = (observed * (1.0 - observed) / n).sqrt() / (1.0 - expected);
+        return (kappa - settings.z * se, kappa + settings.z * se);
     }
-    Ok(dicts.into())
+
+    let inv = |x: u32| 1.0 / x as f64;
+    let se = match metric {
+        Metric::Plr => (inv(tp) - inv(tp + f_n) + inv(fp) - inv(fp + tn)).sqrt(),
+        Metric::Nlr => (inv(f_n) - inv(tp + f_n) + inv(tn) - inv(fp + tn)).sqrt(),
+        Metric::Dor => (inv(tp) + inv(tn) + inv(fp) + inv(f_n)).sqrt(),
+        _ => return (f64::NAN, f64::NAN),
+    };
+    log_interval(metrics_from_counts(tp, tn, fp, f_n)[metric as usize], se, settings.z)
 }
 
-struct PlrReturn {
+/// Confidence intervals for every `Metric` of a confusion matrix, indexed by `Metric as usize`.
+///
+/// See `metric_interval()` for the methods.
+pub fn intervals_from_counts(
     tp: u32,
     tn: u32,
     fp: u32,
     f_n: u32,
-    calc_plr: f64,
-    calc_nlr: f64,
-    plr_error: f64,
-    nlr_error: f64,
-    total_error: f64,
-    exact_match: bool,
+    level: f64,
+    method: &str,
+) -> Result<[(f64, f64); N_METRICS], ConfusionError> {
+    let settings = CiSettings::new(level, method)?;
+    Ok(Metric::ALL.map(|metric| metric_interval(metric, tp, tn, fp, f_n, &settings)))
 }
 
-#[pyfunction(signature = (plr, nlr, sample_size, tolerance=1e-6, n_positive=None, top_n=None))]
-pub fn calculate_likelihoodratios(
-    py: Python,
-    plr: f64, 
-    nlr: f64, 
-    sample_size: u32, 
-    tolerance: f64, 
-    n_positive: Option<u32>, 
-    top_n: Option<u32>
-) -> PyResult<PyObject> {
-    let mut results = Vec::new();
-
-    let n_path_bool = n_positive.is_some();
+/// A reported confidence interval for a metric, and how to decide whether a table matches it.
+struct CiRule {
+    metric: Metric,
+    lower: MatchRule,
+    upper: MatchRule,
+    settings: CiSettings,
+    /// For proportions in a search, the counts (x, n) whose interval matches, found up front.
+    proportions: Option<HashSet<(u32, u32)>>,
+}
 
-    for tp in (0..sample_size+1).progress() { // because apparently std::iter::ExactSizeIterator is
-        // not implemented on inclusive ranges https://github.com/rust-lang/rust/issues/36386
-        for tn in 0..=(sample_size - tp) {
-            for fp in 0..=(sample_size - tp - tn) {
-                let f_n = sample_size - tp - tn - fp;
+impl CiRule {
+    /// Builds the rule for a reported interval. If `max_n` is given and the metric is a
+    /// proportion, the matching counts up to that denominator are found up front, so that a
+    /// search can look them up instead of computing an interval for every table.
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        metric: Metric,
+        lower: &ReportedMetric,
+        upper: &ReportedMetric,
+        tolerance: f64,
+        rounding: &str,
+        threshold: f64,
+        symmetric: bool,
+        settings: CiSettings,
+        max_n: Option<u32>,
+    ) -> Result<Self, ConfusionError> {
+        if matches!(metric, Metric::F1 | Metric::YoudenJ) {
+            return Err(ConfusionError::CiUnsupported(metric.name()));
+        }
 
-                if (tp + tn + fp + f_n) != sample_size {
-                    continue
-                }
-                if n_path_bool && (tp + f_n) != n_positive.unwrap() {
-                    continue
-                }
+        let mut rule = CiRule {
+            metric,
+            lower: MatchRule::new(metric, lower, tolerance, rounding, threshold, symmetric)?,
+            upper: MatchRule::new(metric, upper, tolerance, rounding, threshold, symmetric)?,
+            settings,
+            proportions: None,
+        };
 
-                let sens = if tp + f_n != 0 {
-                    tp as f64 / (tp + f_n) as f64
-                } else {0.0};
-
-                let spec = if tn + fp != 0 {
-                    tn as f64 / (tn + fp) as f64
-                } else {0.0};
-
-                let calc_plr = if 1.0 - spec != 0.0 {
-                    sens / (1.0 / spec)
-                } else {f64::INFINITY};
-
-                let calc_nlr = if spec != 0.0 {
-                    (1.0 - sens) / spec
-                } else {f64::INFINITY};
-
-                let plr_error = (plr - calc_plr).abs();
-                let nlr_error = (nlr - calc_nlr).abs();
-
-                let total_error = plr_error + nlr_error;
-
-                results.push(PlrReturn {
-                    tp, 
-                    tn, 
-                    fp, 
-                    f_n, 
-                    calc_plr, 
-                    calc_nlr, 
-                    plr_error, 
-                    nlr_error, 
-                    total_error, 
-                    exact_match: total_error <= tolerance
+        if let (Some(max_n), Some(_)) = (max_n, proportion_counts(metric, 0, 0, 0, 0)) {
+            // an interval always contains its point estimate, so only proportions between the
+            // reported bounds, give or take their rounding, can match
+            let lowest = rule.lower.reported - rule.lower.slack();
+            let highest = rule.upper.reported + rule.upper.slack();
+            let rule_ref = &rule;
+            let proportions = (1..=max_n)
+                .into_par_iter()
+                .flat_map_iter(|n| {
+                    let x_min = (lowest * n as f64).floor().max(0.0) as u32;
+                    let x_max = ((highest * n as f64).ceil().max(0.0) as u32).min(n);
+                    (x_min..=x_max)
+                        .filter(move |&x| rule_ref.interval_matches(rule_ref.settings.proportion(x, n)))
+                        .map(move |x| (x, n))
                 })
-            }
+                .collect();
+            rule.proportions = Some(proportions);
         }
-    }
 
-    // sort the structs by total_error
-    results.sort_by(|a, b| 
-        a.total_error.partial_cmp(&b.total_error).unwrap_or(Ordering::Equal) 
-    );
+        Ok(rule)
+    }
 
-    // taking just the n smallest total_errors
-    let results: Vec<PlrReturn> = if let Some(n) = top_n {
-        if n < sample_size {
-            results.into_iter().take(n as usize).collect()
-        } else { results }
-    } else { results };
+    fn interval_matches(&self, (lower, upper): (f64, f64)) -> bool {
+        self.lower.matches(lower) && self.upper.matches(upper)
+    }
 
-    let dicts = PyList::empty(py);
+    fn matches(&self, tp: u32, tn: u32, fp: u32, f_n: u32) -> bool {
+        match (&self.proportions, proportion_counts(self.metric, tp, tn, fp, f_n)) {
+            (Some(proportions), Some(counts)) => proportions.contains(&counts),
+            _ => self.interval_matches(metric_interval(self.metric, tp, tn, fp, f_n, &self.settings)),
+        }
+    }
+}
 
-    for result in results {
-        let dict = PyDict::new(py);
-        dict.set_item("TP", result.tp)?;
-        dict.set_item("TN", result.tn)?;
-        dict.set_item("FP", result.fp)?;
-        dict.set_item("FN", result.f_n)?;
-        dict.set_item("Calculated_PLR", result.calc_plr)?;
-        dict.set_item("Calculated_NLR", result.calc_nlr)?;
-        dict.set_item("PLR_Error", result.plr_error)?;
-        dict.set_item("NLR_Error", result.nlr_error)?;
-        dict.set_item("Total_Error", result.total_error)?;
-        dict.set_item("Exact_Match", result.exact_match)?;
+/// Reads a dict of reported intervals, mapping metric names to (lower, upper) pairs, into rules.
+#[allow(clippy::too_many_arguments)]
+fn extract_ci_rules(
+    cis: Option<Bound<'_, PyDict>>,
+    tolerance: f64,
+    rounding: &str,
+    threshold: f64,
+    symmetric: bool,
+    settings: CiSettings,
+    max_n: Option<u32>,
+) -> PyResult<Vec<CiRule>> {
+    let Some(cis) = cis else {
+        return Ok(Vec::new());
+    };
+    let mut rules = Vec::new();
+    for (key, value) in cis.iter() {
+        let metric = Metric::from_key(&key.extract::<String>()?)?;
+        if rules.iter().any(|rule: &CiRule| rule.metric == metric) {
+            return Err(ConfusionError::DuplicateMetric(metric.name()).into());
+        }
+        let (lower, upper) = value.extract::<(ReportedMetric, ReportedMetric)>()?;
+        rules.push(CiRule::new(metric, &lower, &upper, tolerance, rounding, threshold, symmetric, settings, max_n)?);
+    }
+    Ok(rules)
+}
 
-        dicts.append(dict)?;
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (tp, tn, fp, f_n, cis, ci_level=0.95, ci_method="wilson", tolerance=1e-6, rounding="up_or_down".to_string(), threshold=5.0, symmetric=false))]
+/// Checks whether reported confidence intervals are consistent with a confusion matrix.
+///
+/// Parameters:
+///     tp (int): The number of true positives.
+///     tn (int): The number of true negatives.
+///     fp (int): The number of false positives.
+///     f_n (int): The number of false negatives.
+///     cis (dict): The reported intervals, mapping metric names as in `reconstruct_confusion()`
+///     to (lower, upper) tuples. Bounds given as strings, e.g. ("0.71", "0.90"), match when the
+///     recomputed bound rounds to them; bounds given as numbers match within `tolerance`.
+///     ci_level (float): The confidence level of the reported intervals. Defaults to 0.95.
+///     ci_method (str): The interval for proportions, either "wilson" or "clopper_pearson".
+///     Defaults to "wilson". Likelihood ratios and the DOR use the log method.
+///     tolerance (float): The allowed error for bounds given as numbers. Defaults to 1e-6.
+///     rounding (str): The rounding scheme for bounds given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///
+/// Returns:
+///     dict: Whether each reported interval is consistent with the counts, keyed like `cis`.
+///
+/// Raises:
+///     ValueError: If a metric name is unknown or has no interval (F1 and Youden's J), a bound
+///     is not a number, or the rounding scheme or interval settings are invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import check_confusion_ci
+///     >>> check_confusion_ci(8, 7, 3, 2, {"sensitivity": ("0.49", "0.94")})
+///     {'sensitivity': True}
+pub fn check_confusion_ci(
+    py: Python,
+    tp: u32,
+    tn: u32,
+    fp: u32,
+    f_n: u32,
+    cis: Bound<'_, PyDict>,
+    ci_level: f64,
+    ci_method: &str,
+    tolerance: f64,
+    rounding: String,
+    threshold: f64,
+    symmetric: bool,
+) -> PyResult<PyObject> {
+    let settings = CiSettings::new(ci_level, ci_method)?;
+    let dict = PyDict::new(py);
+    for (key, value) in cis.iter() {
+        let metric = Metric::from_key(&key.extract::<String>()?)?;
+        let (lower, upper) = value.extract::<(ReportedMetric, ReportedMetric)>()?;
+        let rule = CiRule::new(metric, &lower, &upper, tolerance, &rounding, threshold, symmetric, settings, None)?;
+        dict.set_item(key, rule.matches(tp, tn, fp, f_n))?;
     }
-    Ok(dicts.into())
+    Ok(dict.into())
 }
 
-#[pyfunction]
+/// Turns undefined (NaN) values into None.
+fn defined(x: f64) -> Option<f64> {
+    if x.is_nan() { None } else { Some(x) }
+}
+
+#[pyfunction(signature = (tp, tn, fp, f_n, ci_level=0.95, ci_method="wilson"))]
+/// Calculates diagnostic accuracy metrics and their confidence intervals from the counts of a
+/// confusion matrix.
+///
+/// Parameters:
+///     tp (int): The number of true positives.
+///     tn (int): The number of true negatives.
+///     fp (int): The number of false positives.
+///     f_n (int): The number of false negatives.
+///     ci_level (float): The confidence level of the intervals. Defaults to 0.95.
+///     ci_method (str): The interval for proportions, either "wilson" or "clopper_pearson".
+///     Defaults to "wilson".
+///
+/// Returns:
+///     dict: Sensitivity, Specificity, PPV, NPV, Accuracy, Prevalence, +LR, -LR, DOR, F1 and
+///     Youden_J, with the bounds of each interval under the metric's name followed by _CI_Lower
+///     and _CI_Upper. Proportions have Wilson or Clopper-Pearson intervals, and +LR, -LR and
+///     DOR have log-method intervals. F1 and Youden_J have no intervals.
+///
+/// Raises:
+///     ValueError: If ci_method is not valid, or ci_level is not strictly between 0 and 1.
+///
+/// Notes:
+///     - Metrics and intervals which are undefined for the counts are None, e.g. sensitivity
+///     with no positive cases, or the interval of +LR with no false positives. Ratios of a
+///     positive number to 0 are infinite.
 pub fn calculate_metrics_from_counts(
     py: Python,
     tp: u32, 
     tn: u32, 
     fp: u32, 
-    f_n: u32
+    f_n: u32,
+    ci_level: f64,
+    ci_method: &str,
 ) -> PyResult<PyObject> {
-
-    let sensitivity = if tp+f_n != 0 {
-        tp as f64 / (tp + f_n) as f64
-    } else { 0.0 };
-    let specificity = if tn+fp != 0 {
-        tn as f64 / (tn + fp) as f64
-    } else { 0.0 };
-    let ppv = if tp + fp != 0 {
-        tp as f64/ (tp + fp) as f64
-    } else { 0.0 };
-    let npv = if tn + f_n != 0 {
-        tn as f64 / (tn + f_n) as f64
-    } else { 0.0 };
-
-    let plr = if (1.0 - specificity) != 0.0 {
-        sensitivity / (1.0 - specificity)
-    } else {f64::INFINITY};
-
-    let nlr = if specificity != 0.0 {
-        (1.0 - sensitivity) / specificity
-    } else {f64::INFINITY};
+    let metrics = metrics_from_counts(tp, tn, fp, f_n);
+    let intervals = intervals_from_counts(tp, tn, fp, f_n, ci_level, ci_method)?;
 
     let dict = PyDict::new(py);
 
-    dict.set_item("Sensitivity", sensitivity)?;
-    dict.set_item("Specificity", specificity)?;
-    dict.set_item("PPV", ppv)?;
-    dict.set_item("NPV", npv)?;
-    dict.set_item("+LR", plr)?;
-    dict.set_item("-LR", nlr)?;
+    for (metric, name) in [
+        (Metric::Sensitivity, "Sensitivity"),
+        (Metric::Specificity, "Specificity"),
+        (Metric::Ppv, "PPV"),
+        (Metric::Npv, "NPV"),
+        (Metric::Accuracy, "Accuracy"),
+        (Metric::Prevalence, "Prevalence"),
+        (Metric::Plr, "+LR"),
+        (Metric::Nlr, "-LR"),
+        (Metric::Dor, "DOR"),
+        (Metric::F1, "F1"),
+        (Metric::YoudenJ, "Youden_J"),
+    ] {
+        let (lower, upper) = intervals[metric as usize];
+        dict.set_item(name, defined(metrics[metric as usize]))?;
+        if !matches!(metric, Metric::F1 | Metric::YoudenJ) {
+            dict.set_item(format!("{name}_CI_Lower"), defined(lower))?;
+            dict.set_item(format!("{name}_CI_Upper"), defined(upper))?;
+        }
+    }
 
     Ok(dict.into())
 }
 
+#[pyfunction(signature = (both_yes, both_no, yes_no, no_yes, ci_level=0.95))]
+/// Calculates Cohen's kappa and the agreement of two raters from the counts of a 2x2 agreement
+/// table.
+///
+/// Parameters:
+///     both_yes (int): The number of cases both raters rated yes.
+///     both_no (int): The number of cases both raters rated no.
+///     yes_no (int): The number of cases only the first rater rated yes.
+///     no_yes (int): The number of cases only the second rater rated yes.
+///     ci_level (float): The confidence level of the interval for kappa. Defaults to 0.95.
+///
+/// Returns:
+///     dict: Kappa with the bounds of its large-sample confidence interval, Kappa_CI_Lower and
+///     Kappa_CI_Upper, the observed Agreement with its Wilson interval, Agreement_CI_Lower and
+///     Agreement_CI_Upper, and the Expected_Agreement by chance.
+///
+/// Raises:
+///     ValueError: If ci_level is not strictly between 0 and 1.
+///
+/// Usage Example:
+///     >>> from scrutipy import kappa_from_counts
+///     >>> res = kappa_from_counts(20, 15, 5, 10)
+///     >>> round(res["Kappa"], 4), res["Agreement"]
+///     (0.4, 0.7)
+///
+/// Notes:
+///     - Kappa is undefined, and so None, when the expected agreement is 1, i.e. when both
+///     raters give every case the same rating.
+pub fn kappa_from_counts(
+    py: Python,
+    both_yes: u32,
+    both_no: u32,
+    yes_no: u32,
+    no_yes: u32,
+    ci_level: f64,
+) -> PyResult<PyObject> {
+    let settings = CiSettings::new(ci_level, "wilson")?;
+    let (kappa, observed, expected) = cohens_kappa(both_yes, both_no, yes_no, no_yes);
+
+    let dict = PyDict::new(py);
+    for metric in [Metric::Kappa, Metric::Agreement] {
+        let (lower, upper) = metric_interval(metric, both_yes, both_no, yes_no, no_yes, &settings);
+        let value = if metric == Metric::Kappa { kappa } else { observed };
+        dict.set_item(metric.name(), defined(value))?;
+        dict.set_item(format!("{}_CI_Lower", metric.name()), defined(lower))?;
+        dict.set_item(format!("{}_CI_Upper", metric.name()), defined(upper))?;
+    }
+    dict.set_item("Expected_Agreement", defined(expected))?;
+
+    Ok(dict.into())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn numbers(a: f64, b: f64) -> [MatchRule; 2] {
+        [
+            MatchRule::new(Metric::Sensitivity, &ReportedMetric::Num(a), 1e-6, "up_or_down", 5.0, false).unwrap(),
+            MatchRule::new(Metric::Specificity, &ReportedMetric::Num(b), 1e-6, "up_or_down", 5.0, false).unwrap(),
+        ]
+    }
+
+    fn string(metric: Metric, x: &str, rounding: &str) -> MatchRule {
+        MatchRule::new(metric, &ReportedMetric::Str(x.to_string()), 1e-6, rounding, 5.0, false).unwrap()
+    }
+
+    fn strings(a: &str, b: &str, rounding: &str) -> [MatchRule; 2] {
+        [string(Metric::Sensitivity, a, rounding), string(Metric::Specificity, b, rounding)]
+    }
+
+    #[test]
+    fn search_tables_count() {
+        // every (TP, TN, FP, FN) summing to 20 is searched exactly once
+        assert_eq!(search_tables(20, None, None, &numbers(0.8, 0.7), Ranking::ByError, &[], Progress::Never).len(), 1771);
+        assert_eq!(search_tables(20, Some(10), None, &numbers(0.8, 0.7), Ranking::ByError, &[], Progress::Never).len(), 121);
+        assert!(search_tables(20, Some(21), None, &numbers(0.8, 0.7), Ranking::ByError, &[], Progress::Never).is_empty());
+    }
+
+    #[test]
+    fn search_tables_top_n() {
+        let all = search_tables(40, None, None, &numbers(0.8, 0.70588), Ranking::ByError, &[], Progress::Never);
+        let top = search_tables(40, None, Some(5), &numbers(0.8, 0.70588), Ranking::ByError, &[], Progress::Never);
+        assert_eq!(top, all[..5].to_vec());
+        assert!(all.windows(2).all(|w| w[0].total_error.total_cmp(&w[1].total_error).is_le()));
+    }
+
+    #[test]
+    fn search_tables_snspn() {
+        let top = search_tables(20, None, Some(1), &numbers(0.8, 0.70588), Ranking::ByError, &[], Progress::Never);
+        assert_eq!((top[0].tp, top[0].tn, top[0].fp, top[0].f_n), (8, 7, 3, 2));
+    }
+
+    #[test]
+    fn candidates_frame() {
+        let rules = numbers(0.8, 0.70588);
+        let top = search_tables(20, None, Some(5), &rules, Ranking::ByError, &[], Progress::Never);
+        let frame = candidates_to_frame(&top, &rules, CONFUSION_CELLS).unwrap();
+
+        assert_eq!(frame.shape(), (5, 10));
+        assert_eq!(
+            frame.get_column_names(),
+            ["TP", "TN", "FP", "FN", "Calculated_Sensitivity", "Calculated_Specificity", "Sensitivity_Error", "Specificity_Error", "Total_Error", "Exact_Match"]
+        );
+        assert_eq!(frame.column("TP").unwrap().u32().unwrap().get(0), Some(8));
+        assert_eq!(Output::new("arrow"), Err(ConfusionError::InvalidOutput("arrow".to_string())));
+    }
+
+    #[test]
+    fn kappa() {
+        let (kappa, observed, expected) = cohens_kappa(20, 15, 5, 10);
+        assert!((kappa - 0.4).abs() < 1e-12);
+        assert!((observed - 0.7).abs() < 1e-12);
+        assert!((expected - 0.5).abs() < 1e-12);
+        assert!(cohens_kappa(10, 0, 0, 0).0.is_nan());
+
+        let metrics = metrics_from_counts(20, 15, 5, 10);
+        assert_eq!(metrics[Metric::Agreement as usize], metrics[Metric::Accuracy as usize]);
+    }
+
+    #[test]
+    fn kappa_search() {
+        let rules = [string(Metric::Kappa, "0.40", "up_or_down"), string(Metric::Agreement, "0.70", "up_or_down")];
+        let tables = search_tables(20, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never);
+        assert_eq!(tables.len(), 13);
+        assert!(tables.iter().all(|t| t.tp + t.tn == 14));
+
+        let rules = [string(Metric::Kappa, "0.47", "up_or_down"), string(Metric::Agreement, "0.70", "up_or_down")];
+        assert!(search_tables(20, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never).is_empty());
+    }
+
+    #[test]
+    fn rounding_match() {
+        // 5/7 = 0.714... rounds to 0.71, and 0.625 rounds up to 0.63 but down to 0.62
+        let rule = &strings("0.71", "0.63", "up_or_down");
+        assert!(rule[0].matches(5.0 / 7.0));
+        assert!(!rule[0].matches(0.7));
+        assert!(rule[1].matches(0.625));
+        assert!(strings("0.62", "0.62", "up_or_down")[0].matches(0.625));
+        assert!(!strings("0.62", "0.62", "up")[0].matches(0.625));
+        assert!(strings("0.62", "0.62", "trunc")[0].matches(0.629));
+        assert!(!rule[0].matches(f64::INFINITY));
+    }
+
+    #[test]
+    fn rounding_search() {
+        // sensitivity 5/7 and specificity 3/4 are the only match among 10 cases
+        let top = search_tables(11, None, Some(3), &strings("0.71", "0.75", "up_or_down"), Ranking::ByError, &[], Progress::Never);
+        assert!(top[0].exact_match);
+        assert_eq!((top[0].tp, top[0].tn, top[0].fp, top[0].f_n), (5, 3, 1, 2));
+
+        // no table of 5 cases has a sensitivity which rounds to 0.71
+        let top = search_tables(5, None, Some(3), &strings("0.71", "0.75", "up_or_down"), Ranking::ByError, &[], Progress::Never);
+        assert!(top.iter().all(|c| !c.exact_match));
+    }
+
+    #[test]
+    fn rounding_ranking() {
+        // 1/7 and 1/4 round to 0.1 and 0.2, but 1/6 and 1/5 are closer in total
+        let rules = strings("0.1", "0.2", "up_or_down");
+        let by_error = search_tables(11, None, None, &rules, Ranking::ByError, &[], Progress::Never);
+        assert!(by_error.windows(2).all(|w| w[0].total_error.total_cmp(&w[1].total_error).is_le()));
+        assert!(!by_error[0].exact_match);
+        assert_eq!((by_error[0].tp, by_error[0].f_n, by_error[0].tn, by_error[0].fp), (1, 5, 1, 4));
+
+        let exact_first = search_tables(11, None, None, &rules, Ranking::ExactFirst, &[], Progress::Never);
+        let n_exact = exact_first.iter().filter(|c| c.exact_match).count();
+        assert!(n_exact > 0);
+        assert!(exact_first[..n_exact].iter().all(|c| c.exact_match));
+        assert!(exact_first[..n_exact].windows(2).all(|w| w[0].total_error.total_cmp(&w[1].total_error).is_le()));
+
+        let top = search_tables(11, None, Some(n_exact), &rules, Ranking::ExactFirst, &[], Progress::Never);
+        assert_eq!(top, exact_first[..n_exact].to_vec());
+    }
+
+    #[test]
+    fn exact_only_bounds() {
+        // solving TN from the reported metrics finds the same tables as checking all of them
+        let rule_sets = [
+            Vec::from(strings("0.71", "0.75", "up_or_down")),
+            vec![string(Metric::Ppv, "0.62", "up_or_down"), string(Metric::Npv, "0.80", "up_or_down")],
+            vec![string(Metric::Accuracy, "0.75", "up_or_down"), string(Metric::F1, "0.76", "up_or_down")],
+            vec![string(Metric::Prevalence, "0.40", "up_or_down"), string(Metric::Ppv, "1.0", "up_or_down")],
+            vec![string(Metric::Npv, "0.0", "up_or_down"), string(Metric::Specificity, "0", "up_or_down")],
+            Vec::from(numbers(0.8, 0.7)),
+        ];
+        for rules in rule_sets {
+            let all: Vec<Candidate> = search_tables(40, None, None, &rules, Ranking::ByError, &[], Progress::Never)
+                .into_iter()
+                .filter(|c| c.exact_match)
+                .collect();
+            let bounded = search_tables(40, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never);
+            assert_eq!(bounded, all);
+        }
+    }
+
+    #[test]
+    fn rounding_invalid() {
+        let metric = ReportedMetric::Str("0.5".to_string());
+        assert!(matches!(MatchRule::new(Metric::Ppv, &metric, 1e-6, "sideways", 5.0, false), Err(ConfusionError::InvalidRounding(..))));
+        assert!(matches!(MatchRule::new(Metric::Ppv, &metric, 1e-6, "up_from", 5.0, false), Err(ConfusionError::ThresholdRequired(_))));
+        let metric = ReportedMetric::Str("abc".to_string());
+        assert!(matches!(MatchRule::new(Metric::Ppv, &metric, 1e-6, "up", 5.0, false), Err(ConfusionError::NotANumber(_))));
+    }
+
+    #[test]
+    fn metrics_from_counts_test() {
+        // sensitivity 0.8 and specificity 0.7
+        let metrics = metrics_from_counts(8, 7, 3, 2);
+        assert!((metrics[Metric::Plr as usize] - 0.8 / 0.3).abs() < 1e-12);
+        assert!((metrics[Metric::Nlr as usize] - 0.2 / 0.7).abs() < 1e-12);
+        assert!((metrics[Metric::Accuracy as usize] - 0.75).abs() < 1e-12);
+        assert!((metrics[Metric::Prevalence as usize] - 0.5).abs() < 1e-12);
+        assert!((metrics[Metric::F1 as usize] - 16.0 / 21.0).abs() < 1e-12);
+        assert!((metrics[Metric::YoudenJ as usize] - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn metrics_undefined() {
+        // no positives, and no false positives
+        let metrics = metrics_from_counts(0, 5, 0, 0);
+        assert!(metrics[Metric::Sensitivity as usize].is_nan());
+        assert!(metrics[Metric::Plr as usize].is_nan());
+        assert_eq!(metrics[Metric::Specificity as usize], 1.0);
+
+        let metrics = metrics_from_counts(3, 5, 0, 1);
+        assert_eq!(metrics[Metric::Plr as usize], f64::INFINITY);
+        assert_eq!(metrics[Metric::Dor as usize], f64::INFINITY);
+    }
+
+    #[test]
+    fn intervals() {
+        // reference values from R: binom::binom.confint(8, 10)
+        let (lower, upper) = wilson_interval(8, 10, normal_quantile(0.975));
+        assert!((lower - 0.4901625).abs() < 1e-6);
+        assert!((upper - 0.9433178).abs() < 1e-6);
+        let (lower, upper) = clopper_pearson_interval(8, 10, 0.95);
+        assert!((lower - 0.4439045).abs() < 1e-6);
+        assert!((upper - 0.9747893).abs() < 1e-6);
+        assert_eq!(clopper_pearson_interval(0, 10, 0.95).0, 0.0);
+
+        let intervals = intervals_from_counts(8, 7, 3, 2, 0.95, "wilson").unwrap();
+        let (lower, upper) = intervals[Metric::Plr as usize];
+        assert!(lower < 0.8 / 0.3 && 0.8 / 0.3 < upper);
+        // DOR = 56 / 6, with se sqrt(1/8 + 1/7 + 1/3 + 1/2)
+        let se = (1.0 / 8.0 + 1.0 / 7.0 + 1.0 / 3.0 + 1.0 / 2.0f64).sqrt();
+        let (lower, _) = intervals[Metric::Dor as usize];
+        assert!((lower - ((56.0f64 / 6.0).ln() - 1.959963984540054 * se).exp()).abs() < 1e-9);
+        assert!(intervals[Metric::F1 as usize].0.is_nan());
+
+        let intervals = intervals_from_counts(3, 5, 0, 1, 0.95, "wilson").unwrap();
+        assert!(intervals[Metric::Plr as usize].0.is_nan());
+
+        assert!(matches!(intervals_from_counts(8, 7, 3, 2, 0.95, "exact"), Err(ConfusionError::InvalidCiMethod(_))));
+        assert!(matches!(intervals_from_counts(8, 7, 3, 2, 95.0, "wilson"), Err(ConfusionError::InvalidCiLevel(_))));
+    }
+
+    fn ci_rule(metric: Metric, lower: &str, upper: &str, method: &str, max_n: Option<u32>) -> CiRule {
+        let settings = CiSettings::new(0.95, method).unwrap();
+        let (lower, upper) = (ReportedMetric::Str(lower.to_string()), ReportedMetric::Str(upper.to_string()));
+        CiRule::new(metric, &lower, &upper, 1e-6, "up_or_down", 5.0, false, settings, max_n).unwrap()
+    }
+
+    #[test]
+    fn ci_check() {
+        // Wilson 0.490-0.943 and Clopper-Pearson 0.444-0.975 for a sensitivity of 8/10
+        assert!(ci_rule(Metric::Sensitivity, "0.49", "0.94", "wilson", None).matches(8, 7, 3, 2));
+        assert!(!ci_rule(Metric::Sensitivity, "0.49", "0.94", "clopper_pearson", None).matches(8, 7, 3, 2));
+        assert!(ci_rule(Metric::Sensitivity, "0.44", "0.97", "clopper_pearson", None).matches(8, 7, 3, 2));
+        // 16/20 has the same point estimate but a narrower interval
+        assert!(!ci_rule(Metric::Sensitivity, "0.49", "0.94", "wilson", None).matches(16, 7, 3, 4));
+
+        let bounds = metric_interval(Metric::Plr, 8, 7, 3, 2, &CiSettings::new(0.95, "wilson").unwrap());
+        let (lower, upper) = (format!("{:.2}", bounds.0), format!("{:.2}", bounds.1));
+        assert!(ci_rule(Metric::Plr, &lower, &upper, "wilson", None).matches(8, 7, 3, 2));
+
+        let settings = CiSettings::new(0.95, "wilson").unwrap();
+        let bound = ReportedMetric::Num(0.5);
+        assert!(matches!(
+            CiRule::new(Metric::F1, &bound, &bound, 1e-6, "up_or_down", 5.0, false, settings, None),
+            Err(ConfusionError::CiUnsupported("F1"))
+        ));
+    }
+
+    #[test]
+    fn ci_search() {
+        // the point estimate 0.8 alone allows 8/10, 16/20 and more, but the interval only 8/10
+        let rules = [string(Metric::Sensitivity, "0.80", "up_or_down")];
+        let ci_rules = [ci_rule(Metric::Sensitivity, "0.49", "0.94", "wilson", Some(30))];
+        let tables = search_tables(30, None, None, &rules, Ranking::ExactOnly, &ci_rules, Progress::Never);
+        assert!(!tables.is_empty());
+        assert!(tables.iter().all(|c| (c.tp, c.f_n) == (8, 2)));
+
+        // the precomputed counts agree with computing each interval directly
+        let direct = ci_rule(Metric::Sensitivity, "0.49", "0.94", "wilson", None);
+        let all = search_tables(30, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never);
+        assert_eq!(all.iter().filter(|c| direct.matches(c.tp, c.tn, c.fp, c.f_n)).count(), tables.len());
+    }
+
+    #[test]
+    fn search_tables_any_metrics() {
+        // prevalence 7/11 rules out the tables of 11 with other numbers of positives
+        let rules = [
+            string(Metric::Sensitivity, "0.71", "up_or_down"),
+            string(Metric::Specificity, "0.75", "up_or_down"),
+            string(Metric::Prevalence, "0.64", "up_or_down"),
+        ];
+        let tables = search_tables(11, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never);
+        assert_eq!(tables.len(), 1);
+        assert_eq!((tables[0].tp, tables[0].tn, tables[0].fp, tables[0].f_n), (5, 3, 1, 2));
+        // errors are kept by metric, whatever order the metrics were given in
+        assert!((tables[0].errors[Metric::Prevalence as usize] - (0.64 - 7.0 / 11.0)).abs() < 1e-12);
+        assert_eq!(tables[0].errors[Metric::Accuracy as usize], 0.0);
+
+        // accuracy and F1 alone
+        let rules = [
+            string(Metric::Accuracy, "0.75", "up_or_down"),
+            string(Metric::F1, "0.76", "up_or_down"),
+        ];
+        let tables = search_tables(20, None, None, &rules, Ranking::ExactOnly, &[], Progress::Never);
+        assert!(tables.iter().any(|c| (c.tp, c.tn, c.fp, c.f_n) == (8, 7, 3, 2)));
+        assert!(tables.iter().all(|c| c.tp + c.tn == 15));
+    }
+
+    #[test]
+    fn metric_keys() {
+        assert_eq!(Metric::from_key("LR+"), Ok(Metric::Plr));
+        assert_eq!(Metric::from_key("Youden"), Ok(Metric::YoudenJ));
+        assert_eq!(Metric::from_key("Cohens_Kappa"), Ok(Metric::Kappa));
+        assert_eq!(Metric::from_key("auc"), Err(ConfusionError::UnknownMetric("auc".to_string())));
+    }
+}
@@ -461,7 +461,7 @@ pub fn unround(
     threshold: f64
 ) -> Result<UnroundReturn, RoundingBoundError> {
     let digits = decimal_places_scalar(Some(x), ".");
-    let p10: f64 = 10.0f64.powi(digits.unwrap() + 1);
+    let p10: f64 = 10.0f64.powi(digits.unwrap_or(0) + 1);
     let d = 5.0 / p10;
     let d_var = threshold / p10;
 
@@ -472,7 +472,7 @@ pub fn unround(
         x_num, 
         d_var, 
         d
-    ).unwrap();
+    )?;
 
     let lower = bounds.0;
     let upper = bounds.1;
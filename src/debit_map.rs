@@ -33,6 +33,11 @@ use crate::debit_map_df::debit_map_pl;
 /// usage. Defaults to False.
 /// - `silence_numeric_warning` (bool): Suppress warnings about numeric issues. 
 /// Defaults to False.
+/// - `progress` (Optional[str]): When to show a progress bar over the rows: "auto", only
+/// if stderr is a terminal, "always" or "never". Defaults to None, which uses the mode set by
+/// `set_progress()`.
+/// - `restore_zeros` (bool): Pad the values in `x_col` and `sd_col` with trailing zeros to the
+/// most decimal places in each column, as `restore_zeros()` does. Defaults to False.
 ///
 /// Returns:
 /// - tuple: A tuple containing:
@@ -42,6 +47,7 @@ use crate::debit_map_df::debit_map_pl;
 ///
 /// Raises:
 /// - ImportError: If the `polars` package is not installed.
+/// - ValueError: If `progress` is not one of "auto", "always" and "never".
 ///
 /// Warnings:
 /// - A warning is issued if the default column indices are used without 
@@ -78,6 +84,8 @@ use crate::debit_map_df::debit_map_pl;
      symmetric = false,
      silence_default_warning = false,
      silence_numeric_warning = false,
+     progress = None,
+     restore_zeros = false,
 ))]
 pub fn debit_map<'py>(
     py: Python<'py>,
@@ -92,6 +100,8 @@ pub fn debit_map<'py>(
     symmetric: bool,
     silence_default_warning: bool,
     silence_numeric_warning: bool,
+    progress: Option<&str>,
+    restore_zeros: bool,
 ) -> PyResult<(Vec<bool>, Option<Vec<usize>>)> {
     let polars = py.import("polars").map_err(|_| {
         PyImportError::new_err(
@@ -127,5 +137,7 @@ pub fn debit_map<'py>(
         threshold,
         silence_default_warning,
         silence_numeric_warning,
+        progress,
+        restore_zeros,
     )
 }
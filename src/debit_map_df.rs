@@ -6,6 +6,7 @@ use pyo3::{pyfunction, PyErr, PyResult, Python,
 use pyo3_polars::PyDataFrame;
 use thiserror::Error;
 use crate::debit::debit;
+use crate::progress::Progress;
 use crate::grim_map_df::ColumnInput;
 use crate::utils::{InputType, process_series_to_string, process_series_to_num};
 
@@ -66,10 +67,18 @@ fn parse_col_errors(df: &DataFrame, n_col: ColumnInput, err_name: String) -> Res
 }
 
 
-
+/// Implements debit_map over the columns of a Python dataframe.
+///
+/// Takes the provided dataframe as well as inputs indicating the columns to be used as xs, sds
+/// and ns. If one or more columns are not indicated, it will take the first three columns as xs,
+/// sds and ns by default. All other debit_map arguments can be provided as keyword arguments.
+///
+/// `progress` chooses when to draw a progress bar over the rows, as in `set_progress()`.
+/// `restore_zeros` pads the xs and sds back to a common number of decimal places, as in
+/// `restore_zeros()`.
 #[allow(clippy::too_many_arguments)]
 #[pyfunction(signature = (
-    pydf, x_col = ColumnInput::Default(0), sd_col = ColumnInput::Default(1), n_col = ColumnInput::Default(2), show_rec = false, symmetric = false, formula = "mean_n".to_string(), rounding = "up_or_down".to_string(), threshold = 5.0, silence_default_warning = false, silence_numeric_warning = false
+    pydf, x_col = ColumnInput::Default(0), sd_col = ColumnInput::Default(1), n_col = ColumnInput::Default(2), show_rec = false, symmetric = false, formula = "mean_n".to_string(), rounding = "up_or_down".to_string(), threshold = 5.0, silence_default_warning = false, silence_numeric_warning = false, progress = None, restore_zeros = false
 ))]
 #[cfg(not(tarpaulin_include))]
 pub fn debit_map_pl(
@@ -85,7 +94,10 @@ pub fn debit_map_pl(
     threshold: f64, 
     silence_default_warning: bool,
     silence_numeric_warning: bool,
+    progress: Option<&str>,
+    restore_zeros: bool,
 ) -> PyResult<(Vec<bool>, Option<Vec<usize>>)> {
+    let progress = Progress::parse(progress)?;
     let df: DataFrame = pydf.into();
 
     let warnings = py.import("warnings").unwrap();
@@ -114,8 +126,8 @@ pub fn debit_map_pl(
         return Err(PyTypeError::new_err("The n_col column is empty."));
     }
 
-    let xs_vec = process_series_to_string(py, xs, silence_numeric_warning, InputType::Xs)?;
-    let sds_vec = process_series_to_string(py, sds, silence_numeric_warning, InputType::Sds)?;
+    let xs_vec = process_series_to_string(py, xs, silence_numeric_warning, InputType::Xs, restore_zeros)?;
+    let sds_vec = process_series_to_string(py, sds, silence_numeric_warning, InputType::Sds, restore_zeros)?;
     let ns_vec = process_series_to_num(ns)?;
 
 
@@ -136,7 +148,14 @@ pub fn debit_map_pl(
         }
     }
 
-    let res = debit(xs, sds, ns, formula.as_str(), rounding.as_str(), threshold, symmetric, show_rec)?;
+    // check row by row so that the progress bar can follow along
+    let bar = progress.bar(xs.len() as u64);
+    let mut res = Vec::with_capacity(xs.len());
+    for ((x, sd), n) in xs.into_iter().zip(sds).zip(ns) {
+        res.extend(debit(vec![x], vec![sd], vec![n], formula.as_str(), rounding.as_str(), threshold, symmetric, show_rec)?);
+        bar.inc(1);
+    }
+    bar.finish();
 
     // if the length of err_inds is 0, ie if no errors occurred, our error return is Option<None>.
     // Otherwise, our error return is Option<ns_err_inds>
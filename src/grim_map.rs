@@ -23,6 +23,7 @@ use pyo3::types::PyString;
 /// tolerance : Numerical epsilon used in float comparisons. Defaults to square root of 64-bit floating point machine epsilon
 /// silence_default_warning : Suppresses warning about default column selection.
 /// silence_numeric_warning : Suppresses warning about using numeric types in `x_col`.
+/// restore_zeros : If `True`, pads the values in `x_col` with trailing zeros to their most decimal places.
 /// 
 /// Returns
 /// ----------
@@ -47,6 +48,8 @@ use pyo3::types::PyString;
      tolerance = f64::EPSILON.powf(0.5),
      silence_default_warning = false,
      silence_numeric_warning = false,
+     progress = None,
+     restore_zeros = false,
  ))]
 #[allow(clippy::too_many_arguments)]
 /// Runs a GRIM consistency check across a pandas DataFrame.
@@ -72,6 +75,10 @@ use pyo3::types::PyString;
 /// floating point machine epsilon
 /// - `silence_default_warning`: Suppresses warning about default column selection.
 /// - `silence_numeric_warning`: Suppresses warning about using numeric types in `x_col`.
+/// - `progress`: When to show a progress bar over the rows: `"auto"`, only if stderr is a
+///   terminal, `"always"` or `"never"`. Defaults to `None`, which uses the mode set by `set_progress()`.
+/// - `restore_zeros`: If `True`, pads the values in `x_col` with trailing zeros to the most decimal
+///   places of any of them, as `restore_zeros()` does, for numeric columns which have lost them.
 ///
 /// # Returns
 /// A tuple of:
@@ -98,6 +105,8 @@ pub fn grim_map<'py>(
      tolerance: f64,
      silence_default_warning: bool,
      silence_numeric_warning: bool,
+     progress: Option<&str>,
+     restore_zeros: bool,
 ) -> PyResult<(Vec<bool>, Option<Vec<usize>>)> {
      let polars = py.import("polars").map_err(|_| {
         PyImportError::new_err(
@@ -134,5 +143,7 @@ pub fn grim_map<'py>(
          tolerance,
          silence_default_warning,
          silence_numeric_warning,
+         progress,
+         restore_zeros,
     )
 }
@@ -5,6 +5,7 @@ use pyo3::{pyfunction, FromPyObject, PyResult, Python,
     types::{PyAnyMethods, PyString}};
 use pyo3_polars::PyDataFrame;
 use crate::grim::grim_rust;
+use crate::progress::Progress;
 use crate::utils::{process_series_to_num, process_series_to_string, InputType};
 
 /// Implements grim_map over the columns of a Python dataframe. 
@@ -12,7 +13,9 @@ use crate::utils::{process_series_to_num, process_series_to_string, InputType};
 /// Takes the provided dataframe as well as inputs indicating the columns to be used as xs and ns.
 /// If one or more columns are not indicated, it will take the first column as xs and the second
 /// column as ns by default. All other grim_map arguments can be provided as keyword arguments.
-/// default respectively. 
+///
+/// `progress` chooses when to draw a progress bar over the rows, as in `set_progress()`.
+/// `restore_zeros` pads the xs back to a common number of decimal places, as in `restore_zeros()`.
 #[allow(clippy::too_many_arguments)]
 #[cfg(not(tarpaulin_include))] // since this function is only meant to be called from Python and
 // requires certain PyO3 types which are tedious to recreate within Rust, I find it acceptable to
@@ -31,6 +34,8 @@ use crate::utils::{process_series_to_num, process_series_to_string, InputType};
     tolerance = f64::EPSILON.powf(0.5),
     silence_default_warning = false,
     silence_numeric_warning = false,
+    progress = None,
+    restore_zeros = false,
 ))]
 pub fn grim_map_pl(
     py: Python, 
@@ -46,8 +51,11 @@ pub fn grim_map_pl(
     tolerance: f64,
     silence_default_warning: bool,
     silence_numeric_warning: bool,
+    progress: Option<&str>,
+    restore_zeros: bool,
 ) -> PyResult<(Vec<bool>, Option<Vec<usize>>)>
 {
+    let progress = Progress::parse(progress)?;
     let df: DataFrame = pydf.into();
 
     let warnings = py.import("warnings").unwrap();
@@ -101,7 +109,7 @@ pub fn grim_map_pl(
         return Err(PyTypeError::new_err("The n_col column is empty."));
     }
 
-    let xs_vec = process_series_to_string(py, xs, silence_numeric_warning, InputType::Xs)?;
+    let xs_vec = process_series_to_string(py, xs, silence_numeric_warning, InputType::Xs, restore_zeros)?;
 
     let ns_vec = process_series_to_num(ns)?;
 
@@ -129,7 +137,16 @@ pub fn grim_map_pl(
         Some(i) => i,
     };
 
-    let res = grim_rust(xs, ns.clone(), vec![percent, show_rec, symmetric], revised_items, rounding.as_str(), threshold, tolerance);
+    // check row by row so that the progress bar can follow along
+    let bar = progress.bar(xs.len() as u64);
+    let res: Vec<bool> = xs.iter().zip(ns.iter()).zip(revised_items.iter())
+        .flat_map(|((x, n), item)| {
+            let row = grim_rust(vec![*x], vec![*n], vec![percent, show_rec, symmetric], vec![*item], rounding.as_str(), threshold, tolerance);
+            bar.inc(1);
+            row
+        })
+        .collect();
+    bar.finish();
 
     // if the length of ns_err_inds is 0, ie if no errors occurred, our error return is Option<None>.
     // Otherwise, our error return is Option<ns_err_inds>
@@ -1,10 +1,22 @@
+use indicatif::ProgressBar;
 use rand::rng;
 use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+use rand::seq::SliceRandom;
 use pyo3::prelude::*;
 use rayon::prelude::*;
 use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::ops::AddAssign;
+use num::{One, Zero};
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+use thiserror::Error;
+use crate::confusion::{ReportedMetric, ROUNDING_SCHEMES};
+use crate::distributions::{normal_cdf, normal_sf};
+use crate::utils::stream_rng;
+use crate::grim::is_near;
+use crate::progress::Progress;
+use crate::utils::{decimal_places_scalar, reround};
 
 
 #[pyclass]
@@ -16,6 +28,27 @@ pub struct SimRank {
     pub n2: Vec<usize>,
 }
 
+impl SimRank {
+    /// Builds a SimRank from the sorted ranks of group 1, giving group 2 the other ranks up to
+    /// `n_total`.
+    fn from_group1(group1_ranks: Vec<usize>, n_total: usize) -> Self {
+        let mut group2_ranks = Vec::with_capacity(n_total - group1_ranks.len());
+        let mut g1_iter = group1_ranks.iter().peekable();
+        for i in 1..=n_total {
+            if g1_iter.peek() == Some(&&i) {
+                g1_iter.next();
+            } else {
+                group2_ranks.push(i);
+            }
+        }
+
+        SimRank {
+            n1: group1_ranks,
+            n2: group2_ranks,
+        }
+    }
+}
+
 #[pymethods]
 impl SimRank {
     #[new]
@@ -35,6 +68,13 @@ impl SimRank {
 
         (u_val_1, u_val_2)
     }
+
+    /// The AUC with group 1 as the positives: the proportion of pairs in which the group 1
+    /// observation ranks above the group 2 one.
+    fn auc(&self) -> f64 {
+        self.u_values().1 / (self.n1.len() * self.n2.len()) as f64
+    }
+
     fn __repr__(&self) -> String {
         let (u1, u2) = self.u_values();
         format!(
@@ -78,6 +118,28 @@ impl SimRankTied {
 
         (u_val_1, u_val_2)
     }
+
+    /// The AUC with group 1 as the positives, counting tied pairs as half, as in `SimRank.auc()`.
+    fn auc(&self) -> f64 {
+        self.u_values().1 / (self.n1.len() * self.n2.len()) as f64
+    }
+
+    /// The sizes of the blocks of tied ranks across both groups, in order of rank.
+    fn tie_sizes(&self) -> Vec<usize> {
+        let mut ranks: Vec<f64> = self.n1.iter().chain(self.n2.iter()).copied().collect();
+        ranks.sort_by(|a, b| a.total_cmp(b));
+        ranks
+            .chunk_by(|a, b| a == b)
+            .map(|block| block.len())
+            .filter(|&size| size > 1)
+            .collect()
+    }
+
+    /// The variance of U under the null hypothesis, corrected for the ties in these ranks.
+    fn u_variance(&self) -> f64 {
+        tie_corrected_variance(self.n1.len(), self.n2.len(), &self.tie_sizes())
+    }
+
     fn __repr__(&self) -> String {
         let (u1, u2) = self.u_values();
         format!(
@@ -87,13 +149,6 @@ impl SimRankTied {
     }
 }
 
-// current issue: we want to allow the user to input half integers, since it is possible for a
-// u-value to not be a whole number, if there was a tie among the tests. 
-//
-// we also want to check up-front whether the u-value provided is not within the possible range: if
-// so, we should exit immediately with an informative error and pass it up to Python. Otherwise, we
-// could end up spending lots of cycles on useless work.
-
 /// Generates simulated rank tests based on rank counts and U-score.
 ///
 /// This function takes integers n1 and n2, decimal U-value u_target (which may be an integer or
@@ -108,6 +163,12 @@ impl SimRankTied {
 /// * `length` - How many simulated rank tests to generate.
 /// * `max_iter` - The maximum number of samples the function will take before terminating, if it
 /// has not already found `length` valid samples.
+/// * `progress` - When to draw a progress bar over the samples: "auto", only if stderr is a
+/// terminal, "always" or "never". None uses the mode set by `set_progress()`.
+/// * `seed` - Seed for the random number generator. If None, a random seed is used. Each
+/// sample has its own generator, seeded from `seed` and the index of the sample, and results
+/// are returned in order of that index, so the same seed gives the same output whatever the
+/// number of threads.
 ///
 /// # Returns
 ///
@@ -119,83 +180,208 @@ impl SimRankTied {
 ///
 /// # Notes
 ///
+/// `u_target` must be an integer from 0 to n1 * n2, or a `ValueError` is raised before any
+/// sampling; half-integer U-values need ties, see `simrank_tied`. For small groups,
+/// `simrank_all` lists every valid combination instead of sampling.
+///
 /// Being a stochastic process, it is possible that the sampler will fail to find some valid
 /// simrank combinations, even with an extremely high `max_iter`. It is also possible that it will
 /// fail to find up to `length` elements, even if they do exist. Thus, the output vectors are not
 /// guaranteed to be exactly `length` in size, and if their exact dimensions are relevant to any
 /// analysis, that must be checked by the caller.
-#[pyfunction(signature = (n1, n2, u_target, length=1, max_iter=100000))]
+#[pyfunction(signature = (n1, n2, u_target, length=1, max_iter=100000, progress=None, seed=None))]
 pub fn simrank(
     n1: usize, 
     n2: usize, 
     u_target: f64,
     length: usize,
-    max_iter: usize
-) -> Vec<SimRank> {
+    max_iter: usize,
+    progress: Option<&str>,
+    seed: Option<u64>,
+) -> PyResult<Vec<SimRank>> {
 // ) -> Vec<(Vec<usize>, Vec<usize>, f64)> {
+    check_u_target(n1, n2, u_target, false)?;
+    let bar = Progress::parse(progress)?.bar(max_iter as u64);
+    let seed = seed.unwrap_or_else(|| rng().random());
     let r1_target = u_target + (n1 as f64) * (n1 as f64 + 1.0) / 2.0;
     let n_total = n1 + n2;
 
-    // use a Mutex to collect results across threads
-    let results = Arc::new(Mutex::new(Vec::with_capacity(length)));
-    // use an Atomic counter to stop work early
-    let count = Arc::new(AtomicUsize::new(0));
-
-    (0..max_iter).into_par_iter().for_each(|_| {
-        // Early exit: if we already have enough results, stop trying
-        if count.load(Ordering::Relaxed) >= length {
-            return;
-        }
-
-        let mut rng = rng();
-        let indices = rand::seq::index::sample(&mut rng, n_total, n1);
+    let results = sample_in_order(length, max_iter, seed, &bar, |rng| {
+        let indices = rand::seq::index::sample(rng, n_total, n1);
         let sum_1_based = indices.iter().sum::<usize>() + n1;
+        if sum_1_based as f64 != r1_target {
+            return None;
+        }
 
-        if sum_1_based as f64 == r1_target {
-            // Check again before expensive work
-            if count.load(Ordering::Relaxed) < length {
-                let mut group1_ranks = indices.into_vec();
-                for val in group1_ranks.iter_mut() { *val += 1; }
-                group1_ranks.sort_unstable();
-
-                let mut group2_ranks = Vec::with_capacity(n2);
-                let mut g1_iter = group1_ranks.iter().peekable();
-                for i in 1..=n_total {
-                    if g1_iter.peek() == Some(&&i) {
-                        g1_iter.next();
-                    } else {
-                        group2_ranks.push(i);
-                    }
-                }
-
-                let sr = SimRank {
-                    n1: group1_ranks,
-                    n2: group2_ranks,
-                };
+        let mut group1_ranks = indices.into_vec();
+        for val in group1_ranks.iter_mut() { *val += 1; }
+        group1_ranks.sort_unstable();
 
-                let mut res_guard = results.lock().unwrap();
-                // Push and increment
-                if res_guard.len() < length {
-                    res_guard.push(sr);
-                    count.fetch_add(1, Ordering::SeqCst);
-                }
-            }
-        }
+        Some(SimRank::from_group1(group1_ranks, n_total))
     });
 
-    // Unwrap the Arc and Mutex to return the inner Vec
-    Arc::try_unwrap(results).expect("Arc has other owners").into_inner().unwrap()
+    bar.finish();
+    Ok(results)
 }
 
-#[pyfunction(signature = (n1, n2, u_target, max_iter=100000))]
+#[pyfunction(signature = (n1, n2, u_target, max_iter=100000, progress=None, seed=None))]
 pub fn simrank_single(
     n1: usize,
     n2: usize,
     u_target: f64,
-    max_iter: usize
-) -> Option<SimRank> {
-    let s = simrank(n1, n2, u_target, 1, max_iter);
-    s.into_iter().next()
+    max_iter: usize,
+    progress: Option<&str>,
+    seed: Option<u64>,
+) -> PyResult<Option<SimRank>> {
+    let s = simrank(n1, n2, u_target, 1, max_iter, progress, seed)?;
+    Ok(s.into_iter().next())
+}
+
+/// The number of iterations the samplers run in parallel before checking whether they have
+/// found enough results.
+const SAMPLE_CHUNK: usize = 4096;
+
+/// Runs `draw` for iterations 0 to `max_iter` in parallel, and returns the first `length`
+/// results by iteration index.
+///
+/// Each iteration has its own random number generator, the stream of `seed` for the iteration
+/// index from `stream_rng()`, and the iterations run in chunks, stopping after the first chunk which brings the
+/// results up to `length`. The output therefore depends only on `seed`, not on the number of
+/// threads or how they were scheduled.
+fn sample_in_order<T, F>(length: usize, max_iter: usize, seed: u64, bar: &ProgressBar, draw: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&mut ChaCha20Rng) -> Option<T> + Sync,
+{
+    let mut results = Vec::with_capacity(length);
+    let mut start = 0;
+
+    while start < max_iter && results.len() < length {
+        let end = (start + SAMPLE_CHUNK).min(max_iter);
+        let found: Vec<T> = (start..end)
+            .into_par_iter()
+            .filter_map(|iter| draw(&mut stream_rng(seed, iter as u64)))
+            .collect();
+        results.extend(found);
+        bar.inc((end - start) as u64);
+        start = end;
+    }
+
+    results.truncate(length);
+    results
+}
+
+/// The default limit on the number of combinations `simrank_all` returns.
+const MAX_ENUMERATED: usize = 1_000_000;
+
+#[pyfunction(signature = (n1, n2, u_target))]
+/// Counts the rank combinations of two groups, without ties, with a given U-value.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///     u_target (int): The U-value of group 1.
+///
+/// Returns:
+///     int: The number of sets of group 1 ranks which give `u_target`. Their share of all
+///     C(n1 + n2, n1) sets is the exact probability of `u_target`, see `u_distribution()`.
+///
+/// Raises:
+///     ValueError: If either group is empty, u_target is not an integer from 0 to n1 * n2, or
+///     n1 + n2 is over 127.
+///
+/// Usage Example:
+///     >>> from scrutipy import simrank_count
+///     >>> simrank_count(5, 5, 2)
+///     2
+pub fn simrank_count(n1: usize, n2: usize, u_target: f64) -> PyResult<u128> {
+    check_u_target(n1, n2, u_target, false)?;
+    Ok(count_combinations(n1, n2, u_target)?)
+}
+
+/// The exact number of rank combinations with U-value `u_target`, see `simrank_count`.
+fn count_combinations(n1: usize, n2: usize, u_target: f64) -> Result<u128, GrimUError> {
+    // C(n1 + n2, n1) < 2^(n1 + n2), so the counts fit in a u128
+    if n1 + n2 > 127 {
+        return Err(GrimUError::CountTooLarge(n1 + n2));
+    }
+    Ok(u_counts::<u128>(n1, n2)?[u_target as usize])
+}
+
+#[pyfunction(signature = (n1, n2, u_target, max_results=MAX_ENUMERATED))]
+/// Lists every rank combination of two groups, without ties, with a given U-value.
+///
+/// Unlike `simrank`, which samples combinations at random and may miss some, this enumerates
+/// the sets of group 1 ranks in lexicographic order, pruning partial sets whose sum can no
+/// longer reach the target. It is meant for small samples, where the number of combinations
+/// is manageable.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///     u_target (int): The U-value of group 1.
+///     max_results (int): The most combinations to return. If there are more, a ValueError is
+///     raised before any are listed. Defaults to 1,000,000.
+///
+/// Returns:
+///     List[SimRank]: Every combination of ranks with the target U-value.
+///
+/// Raises:
+///     ValueError: If either group is empty, u_target is not an integer from 0 to n1 * n2,
+///     n1 + n2 is over 127, or there are more than max_results combinations.
+///
+/// Usage Example:
+///     >>> from scrutipy import simrank_all
+///     >>> [r.n1 for r in simrank_all(5, 5, 2)]
+///     [[1, 2, 3, 4, 7], [1, 2, 3, 5, 6]]
+pub fn simrank_all(n1: usize, n2: usize, u_target: f64, max_results: usize) -> PyResult<Vec<SimRank>> {
+    check_u_target(n1, n2, u_target, false)?;
+    let count = count_combinations(n1, n2, u_target)?;
+    if count > max_results as u128 {
+        return Err(GrimUError::TooManyCombinations(count, max_results).into());
+    }
+
+    let n_total = n1 + n2;
+    let r1_target = u_target as usize + n1 * (n1 + 1) / 2;
+    let mut results = Vec::with_capacity(count as usize);
+    enumerate_rank_sets(1, n_total, n1, r1_target, &mut Vec::with_capacity(n1), &mut |ranks| {
+        results.push(SimRank::from_group1(ranks.to_vec(), n_total));
+    });
+    Ok(results)
+}
+
+/// Calls `visit` on every increasing set of `k` ranks from `start` to `n_total` summing to
+/// `target`, appended to `current`.
+fn enumerate_rank_sets(
+    start: usize,
+    n_total: usize,
+    k: usize,
+    target: usize,
+    current: &mut Vec<usize>,
+    visit: &mut impl FnMut(&[usize]),
+) {
+    if k == 0 {
+        if target == 0 {
+            visit(current);
+        }
+        return;
+    }
+
+    for rank in start..=(n_total + 1 - k) {
+        // the smallest and largest sums of the k - 1 ranks left after this one
+        let lowest = (k - 1) * (rank + 1) + (k - 1) * k.saturating_sub(2) / 2;
+        let highest = (k - 1) * n_total - (k - 1) * k.saturating_sub(2) / 2;
+        if rank + lowest > target {
+            break;
+        }
+        if rank + highest < target {
+            continue;
+        }
+
+        current.push(rank);
+        enumerate_rank_sets(rank + 1, n_total, k - 1, target - rank, current, visit);
+        current.pop();
+    }
 }
 
 /// Generates simulated rank tests with tied values for half-integer U-scores.
@@ -217,6 +403,8 @@ pub fn simrank_single(
 /// * `u_target` - The target U-value (must be a half-integer like 6.5, 14.5, etc.).
 /// * `length` - How many simulated rank tests to generate.
 /// * `max_iter` - Maximum samples before terminating if `length` results not found.
+/// * `progress` - When to draw a progress bar over the samples, as in `simrank`.
+/// * `seed` - Seed for the random number generator, as in `simrank`.
 ///
 /// # Returns
 ///
@@ -225,20 +413,28 @@ pub fn simrank_single(
 ///
 /// # Notes
 ///
+/// `u_target` must be a half-integer between 0 and n1 * n2, or a `ValueError` is raised before
+/// any sampling.
+///
 /// This function only generates configurations with exactly one tied pair split across
 /// groups. For the case where both tied values are in the same group (resulting in an
-/// integer U despite ties), use the standard `simrank` function.
-#[pyfunction(signature = (n1, n2, u_target, length=1, max_iter=100000))]
+/// integer U despite ties), use the standard `simrank` function, and for other tie structures,
+/// `simrank_ties`.
+#[pyfunction(signature = (n1, n2, u_target, length=1, max_iter=100000, progress=None, seed=None))]
 pub fn simrank_tied(
     n1: usize,
     n2: usize,
     u_target: f64,
     length: usize,
     max_iter: usize,
-) -> Vec<SimRankTied> {
-    // Early return for invalid inputs
-    if n1 == 0 || n2 == 0 || length == 0 {
-        return Vec::new();
+    progress: Option<&str>,
+    seed: Option<u64>,
+) -> PyResult<Vec<SimRankTied>> {
+    check_u_target(n1, n2, u_target, true)?;
+    let progress = Progress::parse(progress)?;
+
+    if length == 0 {
+        return Ok(Vec::new());
     }
 
     let r1_target = u_target + (n1 as f64) * (n1 as f64 + 1.0) / 2.0;
@@ -246,19 +442,13 @@ pub fn simrank_tied(
 
     // Need at least 2 positions to have a tie
     if n_total < 2 {
-        return Vec::new();
+        return Ok(Vec::new());
     }
 
-    let results = Arc::new(Mutex::new(Vec::with_capacity(length)));
-    let count = Arc::new(AtomicUsize::new(0));
-
-    (0..max_iter).into_par_iter().for_each(|_| {
-        if count.load(Ordering::Relaxed) >= length {
-            return;
-        }
-
-        let mut rng = rng();
+    let bar = progress.bar(max_iter as u64);
+    let seed = seed.unwrap_or_else(|| rng().random());
 
+    let results = sample_in_order(length, max_iter, seed, &bar, |rng| {
         // Pick a random tie position (1 to n_total-1)
         // This means positions tie_pos and tie_pos+1 will share rank tie_pos+0.5
         let tie_pos: usize = rng.random_range(1..n_total);
@@ -272,59 +462,906 @@ pub fn simrank_tied(
         // We need n1-1 positions from remaining for group 1 (one position comes from the tie)
         let needed = n1.saturating_sub(1);
         if remaining.len() < needed {
-            return;
+            return None;
         }
 
         // Sample n1-1 positions from remaining for group 1
-        let sample_indices = rand::seq::index::sample(&mut rng, remaining.len(), needed);
+        let sample_indices = rand::seq::index::sample(rng, remaining.len(), needed);
         let other_g1: Vec<usize> = sample_indices.iter().map(|i| remaining[i]).collect();
 
         // Calculate rank sum for group 1: tied_rank + sum of other selected ranks
         let other_sum: usize = other_g1.iter().sum();
         let r1_actual = tied_rank + other_sum as f64;
-
-        if r1_actual == r1_target 
-        && count.load(Ordering::Relaxed) < length {
-            // Build group 1 ranks: the tied rank + other selected integer ranks
-            let mut g1: Vec<f64> = other_g1.iter().map(|&r| r as f64).collect();
-            g1.push(tied_rank);
-            g1.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-            // Build group 2 ranks: the other tied rank + unselected remaining positions
-            let g1_set: HashSet<usize> = other_g1.iter().cloned().collect();
-            let mut g2: Vec<f64> = remaining
-                .iter()
-                .filter(|r| !g1_set.contains(r))
-                .map(|&r| r as f64)
-                .collect();
-            g2.push(tied_rank);
-            g2.sort_by(|a, b| a.partial_cmp(b).unwrap());
-
-            let sr = SimRankTied { n1: g1, n2: g2 };
-
-            let mut res_guard = results.lock().unwrap();
-            if res_guard.len() < length {
-                res_guard.push(sr);
-                count.fetch_add(1, Ordering::SeqCst);
-            }
+        if r1_actual != r1_target {
+            return None;
         }
+
+        // Build group 1 ranks: the tied rank + other selected integer ranks
+        let mut g1: Vec<f64> = other_g1.iter().map(|&r| r as f64).collect();
+        g1.push(tied_rank);
+        g1.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        // Build group 2 ranks: the other tied rank + unselected remaining positions
+        let g1_set: HashSet<usize> = other_g1.iter().cloned().collect();
+        let mut g2: Vec<f64> = remaining
+            .iter()
+            .filter(|r| !g1_set.contains(r))
+            .map(|&r| r as f64)
+            .collect();
+        g2.push(tied_rank);
+        g2.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        Some(SimRankTied { n1: g1, n2: g2 })
     });
 
-    Arc::try_unwrap(results)
-        .expect("Arc has other owners")
-        .into_inner()
-        .unwrap()
+    bar.finish();
+    Ok(results)
 }
 
 /// Single-result convenience wrapper for `simrank_tied`.
-#[pyfunction(signature = (n1, n2, u_target, max_iter=100000))]
+#[pyfunction(signature = (n1, n2, u_target, max_iter=100000, progress=None, seed=None))]
 pub fn simrank_tied_single(
     n1: usize,
     n2: usize,
     u_target: f64,
     max_iter: usize,
-) -> Option<SimRankTied> {
-    let s = simrank_tied(n1, n2, u_target, 1, max_iter);
-    s.into_iter().next()
+    progress: Option<&str>,
+    seed: Option<u64>,
+) -> PyResult<Option<SimRankTied>> {
+    let s = simrank_tied(n1, n2, u_target, 1, max_iter, progress, seed)?;
+    Ok(s.into_iter().next())
+}
+
+/// The tie structure sampled by `simrank_ties`.
+#[derive(Clone, Debug, PartialEq)]
+enum TieStructure {
+    /// Blocks of tied observations with these sizes, placed at random among the ranks.
+    Blocks(Vec<usize>),
+    /// Any structure with between 1 and this many ties, counting a block of t tied observations
+    /// as t - 1 ties.
+    MaxTies(usize),
+}
+
+impl TieStructure {
+    fn new(n_total: usize, ties: Option<Vec<usize>>, max_ties: Option<usize>) -> Result<Self, GrimUError> {
+        match (ties, max_ties) {
+            (Some(ties), None) => {
+                check_ties(n_total, &ties)?;
+                Ok(TieStructure::Blocks(ties))
+            }
+            (None, Some(max_ties)) if (1..n_total).contains(&max_ties) => Ok(TieStructure::MaxTies(max_ties)),
+            (None, Some(max_ties)) => Err(GrimUError::InvalidTies(format!(
+                "max_ties must be from 1 to n1 + n2 - 1 = {}, but got {max_ties}",
+                n_total.saturating_sub(1)
+            ))),
+            _ => Err(GrimUError::InvalidTies("give exactly one of ties and max_ties".to_string())),
+        }
+    }
+
+    /// Draws the sizes of the blocks of equal ranks, in order of rank, with untied observations
+    /// as blocks of 1.
+    fn draw_blocks(&self, n_total: usize, rng: &mut ChaCha20Rng) -> Vec<usize> {
+        match self {
+            TieStructure::Blocks(ties) => {
+                let untied = n_total - ties.iter().sum::<usize>();
+                let mut blocks: Vec<usize> = ties.iter().copied().chain(std::iter::repeat_n(1, untied)).collect();
+                blocks.shuffle(rng);
+                blocks
+            }
+            TieStructure::MaxTies(max_ties) => {
+                // each tie joins an observation to the next one up, so runs of ties form blocks
+                let n_ties = rng.random_range(1..=*max_ties);
+                let mut tied = vec![false; n_total - 1];
+                for gap in rand::seq::index::sample(rng, n_total - 1, n_ties) {
+                    tied[gap] = true;
+                }
+                let mut blocks = vec![1];
+                for joined in tied {
+                    match (joined, blocks.last_mut()) {
+                        (true, Some(last)) => *last += 1,
+                        _ => blocks.push(1),
+                    }
+                }
+                blocks
+            }
+        }
+    }
+}
+
+/// Checks that tie blocks hold at least 2 observations each, and at most `n_total` in all.
+fn check_ties(n_total: usize, ties: &[usize]) -> Result<(), GrimUError> {
+    if let Some(size) = ties.iter().find(|&&size| size < 2) {
+        return Err(GrimUError::InvalidTies(format!(
+            "tie blocks must hold at least 2 observations, but got {size}"
+        )));
+    }
+    let tied: usize = ties.iter().sum();
+    if tied > n_total {
+        return Err(GrimUError::InvalidTies(format!(
+            "the tie blocks hold {tied} observations, more than n1 + n2 = {n_total}"
+        )));
+    }
+    Ok(())
+}
+
+/// The variance of U under the null hypothesis for groups of `n1` and `n2` observations with
+/// blocks of tied observations of sizes `ties`:
+/// n1 * n2 / 12 * ((N + 1) - sum(t^3 - t) / (N * (N - 1))), with N = n1 + n2.
+pub fn tie_corrected_variance(n1: usize, n2: usize, ties: &[usize]) -> f64 {
+    let n = (n1 + n2) as f64;
+    let base = (n1 * n2) as f64 / 12.0;
+    if n < 2.0 {
+        return base * (n + 1.0);
+    }
+    let correction: f64 = ties.iter().map(|&t| (t as f64).powi(3) - t as f64).sum();
+    base * ((n + 1.0) - correction / (n * (n - 1.0)))
+}
+
+/// Generates simulated rank tests with a general structure of ties.
+///
+/// Unlike `simrank_tied`, which splits exactly one tied pair across the groups, this places
+/// blocks of tied observations of any size anywhere among the ranks, gives each block its
+/// midrank, and assigns observations to the groups at random.
+///
+/// # Arguments
+///
+/// * `n1` - The number of observations in group 1.
+/// * `n2` - The number of observations in group 2.
+/// * `u_target` - The target U-value, a multiple of 0.5.
+/// * `ties` - The sizes of the blocks of tied observations, e.g. `[2, 3]` for a tied pair and a
+/// tied triple. Each sample places the blocks at random among the ranks.
+/// * `max_ties` - Instead of `ties`, draw a random tie structure for each sample, with from 1
+/// to `max_ties` ties, counting a block of t tied observations as t - 1 ties.
+/// * `length` - How many simulated rank tests to generate.
+/// * `max_iter` - Maximum samples before terminating if `length` results not found.
+/// * `progress` - When to draw a progress bar over the samples, as in `simrank`.
+/// * `seed` - Seed for the random number generator, as in `simrank`.
+///
+/// # Returns
+///
+/// Returns a `Vec<SimRankTied>` with U-value `u_target`. Their `tie_sizes()` give the blocks of
+/// tied ranks and `u_variance()` the tie-corrected variance of U, which `u_pvalue` uses with
+/// `method="normal"` when given the same `ties`.
+///
+/// # Notes
+///
+/// Exactly one of `ties` and `max_ties` must be given, and `u_target` must be a multiple of 0.5
+/// between 0 and n1 * n2, or a `ValueError` is raised before any sampling. As with `simrank`,
+/// fewer than `length` results may be found.
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (n1, n2, u_target, ties=None, max_ties=None, length=1, max_iter=100000, progress=None, seed=None))]
+pub fn simrank_ties(
+    n1: usize,
+    n2: usize,
+    u_target: f64,
+    ties: Option<Vec<usize>>,
+    max_ties: Option<usize>,
+    length: usize,
+    max_iter: usize,
+    progress: Option<&str>,
+    seed: Option<u64>,
+) -> PyResult<Vec<SimRankTied>> {
+    check_u_range(n1, n2, u_target)?;
+    if (u_target * 2.0).fract() != 0.0 {
+        return Err(GrimUError::UParity(u_target, "a multiple of 0.5").into());
+    }
+    let n_total = n1 + n2;
+    let structure = TieStructure::new(n_total, ties, max_ties)?;
+    let bar = Progress::parse(progress)?.bar(max_iter as u64);
+    let seed = seed.unwrap_or_else(|| rng().random());
+    let r1_target = u_target + (n1 as f64) * (n1 as f64 + 1.0) / 2.0;
+
+    let results = sample_in_order(length, max_iter, seed, &bar, |rng| {
+        let mut midranks = Vec::with_capacity(n_total);
+        for size in structure.draw_blocks(n_total, rng) {
+            let midrank = midranks.len() as f64 + (size as f64 + 1.0) / 2.0;
+            midranks.extend(std::iter::repeat_n(midrank, size));
+        }
+
+        let mut in_group1 = vec![false; n_total];
+        for position in rand::seq::index::sample(rng, n_total, n1) {
+            in_group1[position] = true;
+        }
+        let r1: f64 = (0..n_total).filter(|&i| in_group1[i]).map(|i| midranks[i]).sum();
+        if (r1 - r1_target).abs() > 1e-9 {
+            return None;
+        }
+
+        // midranks are already in order, so both groups come out sorted
+        let group = |in_group: bool| (0..n_total).filter(|&i| in_group1[i] == in_group).map(|i| midranks[i]).collect();
+        Some(SimRankTied { n1: group(true), n2: group(false) })
+    });
+
+    bar.finish();
+    Ok(results)
+}
+
+#[pyfunction(signature = (n1, n2, ties=None))]
+/// Computes the variance of the Mann-Whitney U statistic under the null hypothesis, corrected
+/// for ties.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///     ties (List[int], optional): The sizes of the blocks of tied observations, e.g. [2, 3] for
+///     a tied pair and a tied triple. Defaults to no ties.
+///
+/// Returns:
+///     float: n1 * n2 / 12 * ((N + 1) - sum(t^3 - t) / (N * (N - 1))), with N = n1 + n2 and t
+///     the size of each block of ties.
+///
+/// Raises:
+///     ValueError: If either group is empty, or the tie blocks are smaller than 2 or hold more
+///     than n1 + n2 observations.
+///
+/// Usage Example:
+///     >>> from scrutipy import u_variance
+///     >>> u_variance(5, 5)
+///     22.916666666666668
+///     >>> u_variance(5, 5, [2])
+///     22.77777777777778
+pub fn u_variance(n1: usize, n2: usize, ties: Option<Vec<usize>>) -> PyResult<f64> {
+    check_u_range(n1, n2, 0.0)?;
+    let ties = ties.unwrap_or_default();
+    check_ties(n1 + n2, &ties)?;
+    Ok(tie_corrected_variance(n1, n2, &ties))
+}
+
+
+/// The largest exact U distribution computed, in cells of the dynamic-programming table:
+/// min(n1, n2) rows of n1 * n2 + 1 counts. Beyond this, use the normal approximation.
+const MAX_EXACT_CELLS: usize = 50_000_000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum GrimUError {
+    #[error("Both groups must contain at least one observation, but got n1 = {0} and n2 = {1}")]
+    EmptyGroup(usize, usize),
+    #[error("The U-value {0} is outside the possible range from 0 to n1 * n2 = {1}")]
+    UOutOfRange(f64, usize),
+    #[error("The U-value {0} must be {1} here")]
+    UParity(f64, &'static str),
+    #[error("There are {0} rank combinations with this U-value, more than the limit of {1}. Use simrank_count() to count them, or simrank() to sample them")]
+    TooManyCombinations(u128, usize),
+    #[error("Combinations can only be counted exactly for n1 + n2 up to 127, but got {0}")]
+    CountTooLarge(usize),
+    #[error("{0} is not a valid alternative. Use \"two-sided\", \"less\" or \"greater\"")]
+    InvalidAlternative(String),
+    #[error("{0} is not a valid method. Use \"exact\" or \"normal\"")]
+    InvalidMethod(String),
+    #[error("The exact distribution of U for n1 = {0} and n2 = {1} is too large to compute. Use method = \"normal\"")]
+    ExactTooLarge(usize, usize),
+    #[error("The reported p-value {0} is not a number")]
+    NotANumber(String),
+    #[error("{0} is not a valid rounding scheme. Use one of {1:?}")]
+    InvalidRounding(String, Vec<&'static str>),
+    #[error("Invalid ties: {0}")]
+    InvalidTies(String),
+    #[error("The AUC {0} must be a number from 0 to 1")]
+    InvalidAuc(String),
 }
 
+impl From<GrimUError> for PyErr {
+    fn from(err: GrimUError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The alternative hypothesis of a Mann-Whitney test, in terms of the U of group 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Alternative {
+    TwoSided,
+    Less,
+    Greater,
+}
+
+impl Alternative {
+    pub fn new(alternative: &str) -> Result<Self, GrimUError> {
+        match alternative {
+            "two-sided" | "two_sided" => Ok(Alternative::TwoSided),
+            "less" => Ok(Alternative::Less),
+            "greater" => Ok(Alternative::Greater),
+            _ => Err(GrimUError::InvalidAlternative(alternative.to_string())),
+        }
+    }
+}
+
+/// Checks that both groups are non-empty and that `u` lies in the range of U.
+fn check_u_range(n1: usize, n2: usize, u: f64) -> Result<(), GrimUError> {
+    if n1 == 0 || n2 == 0 {
+        return Err(GrimUError::EmptyGroup(n1, n2));
+    }
+    if !(0.0..=(n1 * n2) as f64).contains(&u) {
+        return Err(GrimUError::UOutOfRange(u, n1 * n2));
+    }
+    Ok(())
+}
+
+/// Checks up front that `u_target` is a possible U-value: in range, and an integer for untied
+/// ranks or a half-integer for a tied pair.
+fn check_u_target(n1: usize, n2: usize, u_target: f64, tied: bool) -> Result<(), GrimUError> {
+    check_u_range(n1, n2, u_target)?;
+    let half_integer = (u_target.fract() - 0.5).abs() < 1e-9;
+    match (tied, half_integer) {
+        (false, _) if u_target.fract() != 0.0 => {
+            Err(GrimUError::UParity(u_target, "an integer without ties; use simrank_tied() for half-integers"))
+        }
+        (true, false) => Err(GrimUError::UParity(u_target, "a half-integer with a tied pair; use simrank() for integers")),
+        _ => Ok(()),
+    }
+}
+
+/// Counts the rank configurations of two groups of `n1` and `n2` untied observations with each
+/// value of U, from 0 to n1 * n2.
+///
+/// With f(m, n)[u] the number of arrangements of m observations from group 1 and n from group 2
+/// with U = u, the largest observation is either from group 1, beating all n observations from
+/// group 2, or from group 2, so f(m, n)[u] = f(m - 1, n)[u - n] + f(m, n - 1)[u]. The counts
+/// grow like the binomial coefficient C(n1 + n2, n1), so use floats for probabilities and
+/// `u128` for exact counts of up to 127 observations.
+///
+/// # Errors
+///
+/// `ExactTooLarge` if the table would exceed `MAX_EXACT_CELLS`.
+pub fn u_counts<T>(n1: usize, n2: usize) -> Result<Vec<T>, GrimUError>
+where
+    T: Copy + Zero + One + AddAssign,
+{
+    // the distribution is the same with the groups swapped, so keep the fewer rows
+    let (rows_n, cols_n) = if n1 <= n2 { (n1, n2) } else { (n2, n1) };
+    let max_u = n1 * n2;
+    if rows_n.saturating_mul(max_u + 1) > MAX_EXACT_CELLS {
+        return Err(GrimUError::ExactTooLarge(n1, n2));
+    }
+
+    // rows[m] holds f(m, n) for the current n, starting from f(m, 0), which is 1 at U = 0
+    let mut rows: Vec<Vec<T>> = vec![vec![T::zero(); max_u + 1]; rows_n + 1];
+    for row in rows.iter_mut() {
+        row[0] = T::one();
+    }
+
+    for n in 1..=cols_n {
+        // rows[m - 1] already holds f(m - 1, n), and rows[m] still holds f(m, n - 1)
+        for m in 1..=rows_n {
+            let (lower, upper) = rows.split_at_mut(m);
+            let (previous, current) = (&lower[m - 1], &mut upper[0]);
+            for u in n..=max_u {
+                current[u] += previous[u - n];
+            }
+        }
+    }
+
+    Ok(rows.swap_remove(rows_n))
+}
+
+/// The exact p-value of `u` from the counts of `u_counts()`, or of any other statistic from
+/// the counts of its integer values.
+pub(crate) fn exact_p(counts: &[f64], u: f64, alternative: Alternative) -> f64 {
+    let total: f64 = counts.iter().sum();
+    // half-integer U-values from ties fall between two untied values
+    let at_most: f64 = counts.iter().take((u + 1e-9).floor() as usize + 1).sum::<f64>() / total;
+    let at_least: f64 = counts.iter().skip((u - 1e-9).ceil() as usize).sum::<f64>() / total;
+
+    match alternative {
+        Alternative::Less => at_most,
+        Alternative::Greater => at_least,
+        Alternative::TwoSided => (2.0 * at_most.min(at_least)).min(1.0),
+    }
+}
+
+/// The normal approximation to the p-value of `u`, with or without a continuity correction of
+/// 1/2 towards the mean, and with the variance corrected for blocks of `ties`.
+fn normal_p(n1: usize, n2: usize, u: f64, alternative: Alternative, continuity: bool, ties: &[usize]) -> f64 {
+    let mean = (n1 * n2) as f64 / 2.0;
+    let sd = tie_corrected_variance(n1, n2, ties).sqrt();
+    normal_tail_p(u, mean, sd, alternative, continuity)
+}
+
+/// The p-value of `statistic` under a normal distribution with this mean and standard
+/// deviation, with or without a continuity correction of 1/2 towards the mean.
+pub(crate) fn normal_tail_p(statistic: f64, mean: f64, sd: f64, alternative: Alternative, continuity: bool) -> f64 {
+    if sd == 0.0 {
+        // every observation is tied, so the statistic is always the mean
+        return 1.0;
+    }
+    let correction = if continuity { 0.5 } else { 0.0 };
+
+    match alternative {
+        Alternative::Less => normal_cdf((statistic - mean + correction) / sd),
+        Alternative::Greater => normal_sf((statistic - mean - correction) / sd),
+        Alternative::TwoSided => {
+            let z = ((statistic - mean).abs() - correction).max(0.0) / sd;
+            (2.0 * normal_sf(z)).min(1.0)
+        }
+    }
+}
+
+#[pyfunction(signature = (n1, n2))]
+/// Computes the exact null distribution of the Mann-Whitney U statistic.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///
+/// Returns:
+///     List[float]: The probability of each U-value from 0 to n1 * n2, assuming no ties.
+///
+/// Raises:
+///     ValueError: If either group is empty, or the distribution is too large to compute
+///     exactly.
+///
+/// Usage Example:
+///     >>> from scrutipy import u_distribution
+///     >>> u_distribution(2, 2)
+///     [0.16666666666666666, 0.16666666666666666, 0.3333333333333333, 0.16666666666666666, 0.16666666666666666]
+pub fn u_distribution(n1: usize, n2: usize) -> PyResult<Vec<f64>> {
+    check_u_range(n1, n2, 0.0)?;
+    let counts = u_counts::<f64>(n1, n2)?;
+    let total: f64 = counts.iter().sum();
+    Ok(counts.into_iter().map(|count| count / total).collect())
+}
+
+#[pyfunction(signature = (n1, n2, u, alternative="two-sided", method="exact", continuity=true, ties=None))]
+/// Computes the p-value of a Mann-Whitney U-value.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///     u (float): The U-value of group 1, which may be a half-integer if there were ties.
+///     alternative (str): "two-sided", "less" or "greater". Defaults to "two-sided".
+///     method (str): "exact", for the exact distribution without ties, or "normal", for the
+///     normal approximation. Defaults to "exact".
+///     continuity (bool): Whether the normal approximation uses a continuity correction.
+///     Ignored by the exact method. Defaults to True.
+///     ties (List[int], optional): The sizes of the blocks of tied observations, which correct
+///     the variance of the normal approximation, as in `u_variance()`. Only the normal method
+///     accepts ties. Defaults to no ties.
+///
+/// Returns:
+///     float: The p-value.
+///
+/// Raises:
+///     ValueError: If either group is empty, u is out of range, alternative or method are
+///     invalid, the ties are invalid or given with the exact method, or the exact distribution
+///     is too large to compute.
+///
+/// Usage Example:
+///     >>> from scrutipy import u_pvalue
+///     >>> u_pvalue(5, 5, 2)
+///     0.031746031746031744
+pub fn u_pvalue(
+    n1: usize,
+    n2: usize,
+    u: f64,
+    alternative: &str,
+    method: &str,
+    continuity: bool,
+    ties: Option<Vec<usize>>,
+) -> PyResult<f64> {
+    check_u_range(n1, n2, u)?;
+    let alternative = Alternative::new(alternative)?;
+    let ties = ties.unwrap_or_default();
+    check_ties(n1 + n2, &ties)?;
+    match method {
+        "exact" if !ties.is_empty() => Err(GrimUError::InvalidTies(
+            "the exact method assumes no ties; use method=\"normal\"".to_string(),
+        ).into()),
+        "exact" => Ok(exact_p(&u_counts(n1, n2)?, u, alternative)),
+        "normal" => Ok(normal_p(n1, n2, u, alternative, continuity, &ties)),
+        _ => Err(GrimUError::InvalidMethod(method.to_string()).into()),
+    }
+}
+
+/// Checks that `rounding` is one of the rounding schemes.
+pub(crate) fn check_rounding(rounding: &str) -> Result<(), GrimUError> {
+    if ROUNDING_SCHEMES.contains(&rounding) {
+        Ok(())
+    } else {
+        Err(GrimUError::InvalidRounding(rounding.to_string(), ROUNDING_SCHEMES.to_vec()))
+    }
+}
+
+/// A reported p-value: either equal to a value, with its decimal places if it was given as a
+/// string, below a bound, as in "p < .001", or above one, as in "p > .05".
+#[derive(Debug, PartialEq)]
+pub(crate) enum ReportedP {
+    Equal(f64, Option<i32>),
+    Below(f64),
+    Above(f64),
+}
+
+impl ReportedP {
+    pub(crate) fn new(reported: &ReportedMetric) -> Result<Self, GrimUError> {
+        let text = match reported {
+            ReportedMetric::Num(p) => return Ok(ReportedP::Equal(*p, None)),
+            ReportedMetric::Str(text) => text.trim(),
+        };
+        let (comparison, value) = match text.chars().next() {
+            Some(c @ ('<' | '>' | '=')) => (c, text[1..].trim()),
+            _ => ('=', text),
+        };
+        let p: f64 = value.parse().map_err(|_| GrimUError::NotANumber(text.to_string()))?;
+
+        match comparison {
+            '<' => Ok(ReportedP::Below(p)),
+            '>' => Ok(ReportedP::Above(p)),
+            _ => Ok(ReportedP::Equal(p, decimal_places_scalar(Some(value), "."))),
+        }
+    }
+
+    /// The reported value, or the bound of a value below or above a bound.
+    pub(crate) fn value(&self) -> f64 {
+        match *self {
+            ReportedP::Equal(value, _) | ReportedP::Below(value) | ReportedP::Above(value) => value,
+        }
+    }
+
+    pub(crate) fn matches(&self, p: f64, tolerance: f64, rounding: &str, threshold: f64, symmetric: bool) -> bool {
+        match *self {
+            ReportedP::Below(bound) => p < bound,
+            ReportedP::Above(bound) => p > bound,
+            ReportedP::Equal(reported, None) => (p - reported).abs() <= tolerance,
+            ReportedP::Equal(reported, Some(digits)) => reround(vec![p], digits, rounding, threshold, symmetric)
+                .into_iter()
+                .any(|rounded| is_near(rounded, reported, f64::EPSILON.powf(0.5))),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (n1, n2, u, p_reported, alternative="two-sided", tolerance=1e-6, rounding="up_or_down", threshold=5.0, symmetric=false))]
+/// Checks whether a reported Mann-Whitney p-value is consistent with the group sizes and U-value.
+///
+/// Parameters:
+///     n1 (int): The number of observations in group 1.
+///     n2 (int): The number of observations in group 2.
+///     u (float): The reported U-value of group 1, which may be a half-integer if there were ties.
+///     p_reported (float | str): The reported p-value. Strings, e.g. ".034", match when a
+///     p-value rounds to them, and strings starting with "<", e.g. "<.001", match any smaller
+///     p-value. Numbers match within `tolerance`.
+///     alternative (str): "two-sided", "less" or "greater". Defaults to "two-sided".
+///     tolerance (float): The allowed error for p-values given as numbers. Defaults to 1e-6.
+///     rounding (str): The rounding scheme for p-values given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///
+/// Returns:
+///     dict: The p-values Exact_P, Normal_P and Normal_CC_P (with continuity correction), and
+///     whether each matches the reported p-value, in Exact_Consistent, Normal_Consistent and
+///     Normal_CC_Consistent. Consistent is True if any of them matches. Achievable is True if the
+///     exact p-value of any U-value for these group sizes matches, so that False shows the
+///     reported p-value is impossible for an exact test whatever the U-value.
+///
+/// Raises:
+///     ValueError: If either group is empty, u is out of range, or alternative, rounding or
+///     the reported p-value are invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import grim_u_check
+///     >>> res = grim_u_check(5, 5, 2, ".032")
+///     >>> res["Exact_Consistent"], res["Normal_Consistent"]
+///     (True, False)
+///
+/// Notes:
+///     - The exact distribution assumes no ties. For very large groups it is not computed, and
+///     the exact fields are None.
+pub fn grim_u_check(
+    py: Python,
+    n1: usize,
+    n2: usize,
+    u: f64,
+    p_reported: ReportedMetric,
+    alternative: &str,
+    tolerance: f64,
+    rounding: &str,
+    threshold: f64,
+    symmetric: bool,
+) -> PyResult<PyObject> {
+    check_u_range(n1, n2, u)?;
+    let alternative = Alternative::new(alternative)?;
+    check_rounding(rounding)?;
+    let reported = ReportedP::new(&p_reported)?;
+    let matches = |p: f64| reported.matches(p, tolerance, rounding, threshold, symmetric);
+
+    let normal = normal_p(n1, n2, u, alternative, false, &[]);
+    let normal_cc = normal_p(n1, n2, u, alternative, true, &[]);
+    let counts = u_counts(n1, n2).ok();
+    let exact = counts.as_ref().map(|counts| exact_p(counts, u, alternative));
+    let achievable = counts.as_ref().map(|counts| {
+        (0..counts.len()).any(|k| matches(exact_p(counts, k as f64, alternative)))
+    });
+
+    let dict = PyDict::new(py);
+    dict.set_item("Exact_P", exact)?;
+    dict.set_item("Normal_P", normal)?;
+    dict.set_item("Normal_CC_P", normal_cc)?;
+    dict.set_item("Exact_Consistent", exact.map(matches))?;
+    dict.set_item("Normal_Consistent", matches(normal))?;
+    dict.set_item("Normal_CC_Consistent", matches(normal_cc))?;
+    dict.set_item("Consistent", exact.is_some_and(matches) || matches(normal) || matches(normal_cc))?;
+    dict.set_item("Achievable", achievable)?;
+    Ok(dict.into())
+}
+
+/// The achievable AUCs for `n_pos` positives and `n_neg` negatives, as grid indices: index k is
+/// U = k * step, with step 1/2 if tied scores count as half a pair, or 1 otherwise.
+struct AucGrid {
+    pairs: f64,
+    step: f64,
+    last: usize,
+}
+
+impl AucGrid {
+    fn new(n_pos: usize, n_neg: usize, ties: bool) -> Self {
+        let step = if ties { 0.5 } else { 1.0 };
+        let pairs = (n_pos * n_neg) as f64;
+        AucGrid { pairs, step, last: (pairs / step) as usize }
+    }
+
+    fn u(&self, k: usize) -> f64 {
+        k as f64 * self.step
+    }
+
+    fn auc(&self, k: usize) -> f64 {
+        self.u(k) / self.pairs
+    }
+
+    /// The grid indices from `auc - radius` to `auc + radius`, and one more either side.
+    fn around(&self, auc: f64, radius: f64) -> std::ops::RangeInclusive<usize> {
+        let index = |auc: f64| auc * self.pairs / self.step;
+        let lower = (index(auc - radius).floor() - 1.0).max(0.0) as usize;
+        let upper = ((index(auc + radius).ceil() + 1.0) as usize).min(self.last);
+        lower..=upper
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (auc_reported, n_pos, n_neg, rounding="up_or_down", threshold=5.0, symmetric=false, ties=true, n_nearest=2))]
+/// Checks whether a reported ROC AUC is achievable with the numbers of positives and negatives.
+///
+/// The AUC is U / (n_pos * n_neg), with U the Mann-Whitney U of the positives, so it can only
+/// take multiples of 1 / (n_pos * n_neg), or of half that when tied scores count as half a pair.
+/// The reported AUC is consistent if one of these rounds to it at its reported precision.
+///
+/// Parameters:
+///     auc_reported (float | str): The reported AUC. Give it as a string, e.g. "0.830", to keep
+///     trailing zeros; numbers are taken to the precision they print with.
+///     n_pos (int): The number of positive cases.
+///     n_neg (int): The number of negative cases.
+///     rounding (str): The rounding scheme. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///     ties (bool): Whether tied scores, counting as half a pair, are possible, so that U may be
+///     a half-integer. Defaults to True.
+///     n_nearest (int): How many of the achievable AUCs nearest the reported value to list.
+///     Defaults to 2.
+///
+/// Returns:
+///     dict: Consistent, True if an achievable AUC rounds to the reported value; AUC_Values and
+///     U_Values, the achievable AUCs which do and their U-values; and Nearest, the n_nearest
+///     achievable AUCs closest to the reported value, in increasing order.
+///
+/// Raises:
+///     ValueError: If either count is 0, the reported AUC is not a number from 0 to 1, or the
+///     rounding scheme is invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import auc_check
+///     >>> res = auc_check("0.84", 5, 4)
+///     >>> res["Consistent"], res["Nearest"]
+///     (False, [0.825, 0.85])
+pub fn auc_check(
+    py: Python,
+    auc_reported: ReportedMetric,
+    n_pos: usize,
+    n_neg: usize,
+    rounding: &str,
+    threshold: f64,
+    symmetric: bool,
+    ties: bool,
+    n_nearest: usize,
+) -> PyResult<PyObject> {
+    check_u_range(n_pos, n_neg, 0.0)?;
+    check_rounding(rounding)?;
+    let text = match &auc_reported {
+        ReportedMetric::Num(auc) => auc.to_string(),
+        ReportedMetric::Str(text) => text.trim().to_string(),
+    };
+    let auc: f64 = text.parse().map_err(|_| GrimUError::InvalidAuc(text.clone()))?;
+    if !(0.0..=1.0).contains(&auc) {
+        return Err(GrimUError::InvalidAuc(text).into());
+    }
+    let digits = decimal_places_scalar(Some(&text), ".").unwrap_or(0);
+    let reported = ReportedP::Equal(auc, Some(digits));
+
+    let grid = AucGrid::new(n_pos, n_neg, ties);
+    // any value which rounds to the reported one is within one unit of its last digit
+    let matching: Vec<usize> = grid
+        .around(auc, 10f64.powi(-digits))
+        .filter(|&k| reported.matches(grid.auc(k), 0.0, rounding, threshold, symmetric))
+        .collect();
+
+    let mut nearest: Vec<usize> = grid.around(auc, n_nearest as f64 * grid.step / grid.pairs).collect();
+    nearest.sort_by(|&a, &b| (grid.auc(a) - auc).abs().total_cmp(&(grid.auc(b) - auc).abs()));
+    nearest.truncate(n_nearest);
+    nearest.sort_unstable();
+
+    let dict = PyDict::new(py);
+    dict.set_item("Consistent", !matching.is_empty())?;
+    dict.set_item("AUC_Values", matching.iter().map(|&k| grid.auc(k)).collect::<Vec<f64>>())?;
+    dict.set_item("U_Values", matching.iter().map(|&k| grid.u(k)).collect::<Vec<f64>>())?;
+    dict.set_item("Nearest", nearest.into_iter().map(|k| grid.auc(k)).collect::<Vec<f64>>())?;
+    Ok(dict.into())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn u_counts_small() {
+        // the 6 arrangements of two groups of 2
+        assert_eq!(u_counts::<u128>(2, 2).unwrap(), vec![1, 1, 2, 1, 1]);
+        assert_eq!(u_counts::<u128>(1, 3).unwrap(), vec![1; 4]);
+        // symmetric in the groups, and summing to C(n1 + n2, n1)
+        assert_eq!(u_counts::<u128>(3, 5).unwrap(), u_counts::<u128>(5, 3).unwrap());
+        assert_eq!(u_counts::<u128>(5, 5).unwrap().iter().sum::<u128>(), 252);
+    }
+
+    #[test]
+    fn exact_pvalues() {
+        let counts = u_counts::<f64>(5, 5).unwrap();
+        // P(U <= 2) = (1 + 1 + 2) / 252
+        assert!((exact_p(&counts, 2.0, Alternative::Less) - 4.0 / 252.0).abs() < 1e-15);
+        assert!((exact_p(&counts, 2.0, Alternative::TwoSided) - 8.0 / 252.0).abs() < 1e-15);
+        assert_eq!(exact_p(&counts, 12.5, Alternative::TwoSided), 1.0);
+        assert_eq!(exact_p(&counts, 23.0, Alternative::Greater), exact_p(&counts, 2.0, Alternative::Less));
+    }
+
+    #[test]
+    fn normal_pvalues() {
+        // U = 20 for n1 = n2 = 10 lies 30 below the mean of 50, with sd sqrt(175)
+        assert!((normal_p(10, 10, 20.0, Alternative::TwoSided, true, &[]) - 0.025748080821108).abs() < 1e-10);
+        assert!((normal_p(10, 10, 20.0, Alternative::TwoSided, false, &[]) - 0.023342202012891).abs() < 1e-10);
+        assert_eq!(normal_p(10, 10, 50.0, Alternative::TwoSided, true, &[]), 1.0);
+
+        // far in the upper tail the p-value keeps its precision instead of cancelling to 0
+        let p = normal_tail_p(10.0, 0.0, 1.0, Alternative::Greater, false);
+        assert!((p / 7.619853024160527e-24 - 1.0).abs() < 1e-10);
+        assert!((normal_tail_p(10.0, 0.0, 1.0, Alternative::TwoSided, false) / p - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn reported_p() {
+        let parse = |s: &str| ReportedP::new(&ReportedMetric::Str(s.to_string())).unwrap();
+        assert_eq!(parse(".034"), ReportedP::Equal(0.034, Some(3)));
+        assert_eq!(parse("< .001"), ReportedP::Below(0.001));
+        assert_eq!(parse("> .05"), ReportedP::Above(0.05));
+        assert!(parse(">.05").matches(0.2, 1e-6, "up_or_down", 5.0, false));
+        assert!(parse(".032").matches(8.0 / 252.0, 1e-6, "up_or_down", 5.0, false));
+        assert!(!parse(".04").matches(8.0 / 252.0, 1e-6, "up_or_down", 5.0, false));
+        assert!(parse("<.05").matches(8.0 / 252.0, 1e-6, "up_or_down", 5.0, false));
+        assert!(ReportedP::new(&ReportedMetric::Str("p".to_string())).is_err());
+    }
+
+    #[test]
+    fn enumerate_all() {
+        let mut sets = Vec::new();
+        enumerate_rank_sets(1, 10, 5, 2 + 15, &mut Vec::new(), &mut |ranks| sets.push(ranks.to_vec()));
+        assert_eq!(sets, vec![vec![1, 2, 3, 4, 7], vec![1, 2, 3, 5, 6]]);
+
+        // every count from the distribution matches the enumeration
+        let counts = u_counts::<u128>(4, 6).unwrap();
+        for (u, count) in counts.iter().enumerate() {
+            let mut found = 0;
+            enumerate_rank_sets(1, 10, 4, u + 10, &mut Vec::new(), &mut |_| found += 1);
+            assert_eq!(found, *count);
+        }
+    }
+
+    #[test]
+    fn u_target_parity() {
+        assert!(check_u_target(5, 5, 7.0, false).is_ok());
+        assert!(check_u_target(5, 5, 7.5, true).is_ok());
+        assert!(matches!(check_u_target(5, 5, 7.5, false), Err(GrimUError::UParity(..))));
+        assert!(matches!(check_u_target(5, 5, 7.0, true), Err(GrimUError::UParity(..))));
+        assert_eq!(check_u_target(5, 5, 26.0, false), Err(GrimUError::UOutOfRange(26.0, 25)));
+        assert_eq!(count_combinations(100, 30, 5.0), Err(GrimUError::CountTooLarge(130)));
+    }
+
+    #[test]
+    fn simrank_seeded() {
+        let ranks = |results: Vec<SimRank>| results.into_iter().map(|r| r.n1).collect::<Vec<_>>();
+        let first = ranks(simrank(10, 12, 40.0, 20, 100000, Some("never"), Some(42)).unwrap());
+        assert_eq!(first.len(), 20);
+        assert_eq!(first, ranks(simrank(10, 12, 40.0, 20, 100000, Some("never"), Some(42)).unwrap()));
+
+        // the same output on a single thread
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let single = pool.install(|| ranks(simrank(10, 12, 40.0, 20, 100000, Some("never"), Some(42)).unwrap()));
+        assert_eq!(first, single);
+
+        // adjacent seeds do not share draws shifted by one iteration
+        let next = ranks(simrank(10, 12, 40.0, 20, 100000, Some("never"), Some(43)).unwrap());
+        assert_ne!(first[1..], next[..19]);
+        assert_ne!(first[..19], next[1..]);
+    }
+
+    #[test]
+    fn simrank_tied_seeded() {
+        let ranks = |results: Vec<SimRankTied>| results.into_iter().map(|r| r.n1).collect::<Vec<_>>();
+        let first = ranks(simrank_tied(6, 7, 20.5, 10, 100000, Some("never"), Some(7)).unwrap());
+        assert_eq!(first.len(), 10);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+        assert_eq!(first, pool.install(|| ranks(simrank_tied(6, 7, 20.5, 10, 100000, Some("never"), Some(7)).unwrap())));
+    }
+
+    #[test]
+    fn tie_variance() {
+        assert!((tie_corrected_variance(5, 5, &[]) - 25.0 * 11.0 / 12.0).abs() < 1e-12);
+        // a tied pair removes (8 - 2) / (10 * 9) from N + 1
+        assert!((tie_corrected_variance(5, 5, &[2]) - 25.0 / 12.0 * (11.0 - 6.0 / 90.0)).abs() < 1e-12);
+        assert!(normal_p(5, 5, 7.0, Alternative::TwoSided, false, &[2, 3])
+            < normal_p(5, 5, 7.0, Alternative::TwoSided, false, &[]));
+        assert!(check_ties(10, &[2, 1]).is_err());
+        assert!(check_ties(10, &[6, 5]).is_err());
+    }
+
+    #[test]
+    fn simrank_ties_blocks() {
+        let results = simrank_ties(6, 7, 20.5, Some(vec![2, 3]), None, 10, 100000, Some("never"), Some(3)).unwrap();
+        assert_eq!(results.len(), 10);
+        for result in &results {
+            assert_eq!(result.u_values().0, 6.0 * 7.0 - 20.5);
+            let mut sizes = result.tie_sizes();
+            sizes.sort_unstable();
+            assert_eq!(sizes, vec![2, 3]);
+            assert_eq!(result.u_variance(), tie_corrected_variance(6, 7, &[2, 3]));
+        }
+
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+        let again = pool.install(|| simrank_ties(6, 7, 20.5, Some(vec![2, 3]), None, 10, 100000, Some("never"), Some(3)).unwrap());
+        assert_eq!(
+            results.iter().map(|r| r.n1.clone()).collect::<Vec<_>>(),
+            again.iter().map(|r| r.n1.clone()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn simrank_ties_max_ties() {
+        let results = simrank_ties(5, 5, 12.0, None, Some(3), 20, 100000, Some("never"), Some(11)).unwrap();
+        assert_eq!(results.len(), 20);
+        for result in &results {
+            let n_ties: usize = result.tie_sizes().iter().map(|t| t - 1).sum();
+            assert!((1..=3).contains(&n_ties));
+            assert_eq!(result.u_values().0, 13.0);
+        }
+
+        let structure = |ties, max_ties| TieStructure::new(10, ties, max_ties);
+        assert!(structure(Some(vec![2]), Some(1)).is_err());
+        assert!(structure(None, None).is_err());
+        assert!(structure(None, Some(10)).is_err());
+        assert!(simrank_ties(5, 5, 12.25, None, Some(2), 1, 10, Some("never"), None).is_err());
+    }
+
+    #[test]
+    fn auc_grid() {
+        let grid = AucGrid::new(5, 4, true);
+        assert_eq!((grid.last, grid.auc(33), grid.u(33)), (40, 0.825, 16.5));
+        assert_eq!(grid.around(0.84, 0.01), 32..=35);
+        assert_eq!(grid.around(0.99, 0.05), 36..=40);
+        assert_eq!(AucGrid::new(5, 4, false).around(0.0, 0.01), 0..=2);
+
+        // group 1 holds the top 3 of 5 ranks, above every group 2 observation
+        let ranks = SimRank { n1: vec![3, 4, 5], n2: vec![1, 2] };
+        assert_eq!(ranks.auc(), 1.0);
+        let tied = SimRankTied { n1: vec![1.0, 2.5], n2: vec![2.5, 4.0] };
+        assert_eq!(tied.auc(), 0.125);
+    }
+
+    #[test]
+    fn u_range() {
+        assert_eq!(check_u_range(0, 5, 0.0), Err(GrimUError::EmptyGroup(0, 5)));
+        assert_eq!(check_u_range(3, 5, 15.5), Err(GrimUError::UOutOfRange(15.5, 15)));
+        assert!(check_u_range(3, 5, 7.5).is_ok());
+    }
+}
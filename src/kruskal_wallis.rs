@@ -0,0 +1,381 @@
+//! Granularity checks for the Kruskal-Wallis test.
+//!
+//! Without ties, H = 12 / (N(N + 1)) * sum(R_i^2 / n_i) - 3(N + 1) depends only on the rank sums
+//! R_i of the groups, so for given group sizes it takes finitely many values. Their exact null
+//! distribution comes from counting the ways to deal the ranks 1 to N into the groups. The checks
+//! mirror those of `grim_u`, and also test whether a reported H is one of the possible values.
+
+use std::collections::{BTreeMap, HashMap};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use thiserror::Error;
+
+use crate::confusion::ReportedMetric;
+use crate::distributions::gamma_inc_upper;
+use crate::grim_u::{check_rounding, ReportedP};
+
+/// The largest exact H distribution computed, in the bound of `rank_sums_bound()` on its number
+/// of distinct rank sums. Beyond this, use the chi-squared approximation.
+const MAX_EXACT_RANK_SUMS: f64 = 100_000.0;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum KruskalError {
+    #[error("The Kruskal-Wallis test needs at least 2 groups, but got {0}")]
+    TooFewGroups(usize),
+    #[error("Every group must contain at least one observation, but got sizes {0:?}")]
+    EmptyGroup(Vec<usize>),
+    #[error("H must be a non-negative number, but got {0}")]
+    InvalidH(String),
+    #[error("{0} is not a valid method. Use \"exact\" or \"chi2\"")]
+    InvalidMethod(String),
+    #[error("The exact distribution of H for group sizes {0:?} is too large to compute. Use method = \"chi2\"")]
+    ExactTooLarge(Vec<usize>),
+}
+
+impl From<KruskalError> for PyErr {
+    fn from(err: KruskalError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Checks that there are at least 2 groups and none is empty.
+fn check_sizes(sizes: &[usize]) -> Result<(), KruskalError> {
+    if sizes.len() < 2 {
+        return Err(KruskalError::TooFewGroups(sizes.len()));
+    }
+    if sizes.contains(&0) {
+        return Err(KruskalError::EmptyGroup(sizes.to_vec()));
+    }
+    Ok(())
+}
+
+/// Checks that `h` is a possible value of H.
+fn check_h(h: f64) -> Result<(), KruskalError> {
+    if h.is_finite() && h >= 0.0 {
+        Ok(())
+    } else {
+        Err(KruskalError::InvalidH(h.to_string()))
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// An upper bound on the number of distinct rank sums (R_1, ..., R_k) for these group sizes,
+/// which is close to the number of states `h_counts()` ends with.
+///
+/// Each R_i but the last, which is fixed by the others, lies in a range of n_i(N - n_i) + 1
+/// values, and the order of groups of the same size does not matter.
+fn rank_sums_bound(sizes: &[usize]) -> f64 {
+    let n_total: usize = sizes.iter().sum();
+    let ranges: f64 = sizes[..sizes.len() - 1]
+        .iter()
+        .map(|&size| (size * (n_total - size) + 1) as f64)
+        .product();
+    let mut same_size: BTreeMap<usize, usize> = BTreeMap::new();
+    for &size in sizes {
+        *same_size.entry(size).or_default() += 1;
+    }
+    let orders: f64 = same_size.values().map(|&count| (1..=count).product::<usize>() as f64).product();
+    ranges / orders
+}
+
+/// Counts the assignments of the ranks 1 to N to groups of the given sizes with each value of
+/// H, returning (H, count) pairs in increasing order of H.
+///
+/// Ranks are dealt out in increasing order, tracking how many ranks each group holds and their
+/// sum. Assignments with the same rank sums have the same H, so they are merged as they go, as
+/// are those which differ only by swapping groups of the same size. The final H-values are keyed
+/// by the integer L * sum(R_i^2 / n_i), with L the least common multiple of the sizes, so equal
+/// values are merged exactly.
+///
+/// # Errors
+///
+/// `ExactTooLarge` if `rank_sums_bound()` exceeds `MAX_EXACT_RANK_SUMS`.
+pub fn h_counts(sizes: &[usize]) -> Result<Vec<(f64, f64)>, KruskalError> {
+    check_sizes(sizes)?;
+    if rank_sums_bound(sizes) > MAX_EXACT_RANK_SUMS {
+        return Err(KruskalError::ExactTooLarge(sizes.to_vec()));
+    }
+    let k = sizes.len();
+    let n_total: usize = sizes.iter().sum();
+
+    // groups of the same size are interchangeable, so their states are kept sorted
+    let mut same_size: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (group, &size) in sizes.iter().enumerate() {
+        same_size.entry(size).or_default().push(group);
+    }
+    let canonical = |state: &mut Vec<usize>| {
+        for groups in same_size.values().filter(|groups| groups.len() > 1) {
+            let mut pairs: Vec<(usize, usize)> = groups.iter().map(|&g| (state[g], state[k + g])).collect();
+            pairs.sort_unstable();
+            for (&g, (dealt, sum)) in groups.iter().zip(pairs) {
+                state[g] = dealt;
+                state[k + g] = sum;
+            }
+        }
+    };
+
+    // each state holds the number of ranks in each group, followed by their rank sums
+    let mut states: HashMap<Vec<usize>, f64> = HashMap::from([(vec![0; 2 * k], 1.0)]);
+    for rank in 1..=n_total {
+        let mut next: HashMap<Vec<usize>, f64> = HashMap::with_capacity(states.len() * 2);
+        for (state, count) in states {
+            for group in (0..k).filter(|&group| state[group] < sizes[group]) {
+                let mut dealt = state.clone();
+                dealt[group] += 1;
+                dealt[k + group] += rank;
+                canonical(&mut dealt);
+                *next.entry(dealt).or_insert(0.0) += count;
+            }
+        }
+        states = next;
+    }
+
+    let lcm = sizes.iter().fold(1u128, |lcm, &size| lcm / gcd(lcm, size as u128) * size as u128);
+    let mut by_key: BTreeMap<u128, f64> = BTreeMap::new();
+    for (state, count) in states {
+        let key: u128 = (0..k)
+            .map(|group| (state[k + group] as u128).pow(2) * (lcm / sizes[group] as u128))
+            .sum();
+        *by_key.entry(key).or_insert(0.0) += count;
+    }
+
+    let n = n_total as f64;
+    Ok(by_key
+        .into_iter()
+        .map(|(key, count)| (12.0 / (n * (n + 1.0)) * key as f64 / lcm as f64 - 3.0 * (n + 1.0), count))
+        .collect())
+}
+
+/// The exact p-values P(H >= value) for each value in the counts of `h_counts()`.
+fn upper_tails(counts: &[(f64, f64)]) -> Vec<f64> {
+    let total: f64 = counts.iter().map(|(_, count)| count).sum();
+    let mut tails: Vec<f64> = counts
+        .iter()
+        .rev()
+        .scan(0.0, |tail, (_, count)| {
+            *tail += count;
+            Some(*tail / total)
+        })
+        .collect();
+    tails.reverse();
+    tails
+}
+
+/// The exact p-value of `h`, given the counts of `h_counts()` and their `upper_tails()`.
+fn exact_p(counts: &[(f64, f64)], tails: &[f64], h: f64) -> f64 {
+    // allow for H-values which were rounded down
+    let first = counts.partition_point(|(value, _)| *value < h - 1e-9);
+    tails.get(first).copied().unwrap_or(0.0)
+}
+
+/// The chi-squared approximation to the p-value of `h`, with k - 1 degrees of freedom.
+fn chi2_p(k: usize, h: f64) -> f64 {
+    gamma_inc_upper((k as f64 - 1.0) / 2.0, h / 2.0)
+}
+
+#[pyfunction(signature = (sizes))]
+/// Computes the exact null distribution of the Kruskal-Wallis statistic H.
+///
+/// Parameters:
+///     sizes (List[int]): The number of observations in each group.
+///
+/// Returns:
+///     List[Tuple[float, float]]: Every possible H-value with its probability, in increasing
+///     order of H, assuming no ties.
+///
+/// Raises:
+///     ValueError: If there are fewer than 2 groups, any group is empty, or the distribution is
+///     too large to compute exactly.
+///
+/// Usage Example:
+///     >>> from scrutipy import h_distribution
+///     >>> h_distribution([1, 1, 1])
+///     [(2.0, 1.0)]
+pub fn h_distribution(sizes: Vec<usize>) -> PyResult<Vec<(f64, f64)>> {
+    let counts = h_counts(&sizes)?;
+    let total: f64 = counts.iter().map(|(_, count)| count).sum();
+    Ok(counts.into_iter().map(|(h, count)| (h, count / total)).collect())
+}
+
+#[pyfunction(signature = (sizes, h, method="exact"))]
+/// Computes the p-value of a Kruskal-Wallis H-value.
+///
+/// Parameters:
+///     sizes (List[int]): The number of observations in each group.
+///     h (float): The H-value.
+///     method (str): "exact", for the exact distribution without ties, or "chi2", for the
+///     chi-squared approximation with len(sizes) - 1 degrees of freedom. Defaults to "exact".
+///
+/// Returns:
+///     float: The p-value, the probability of an H-value at least as large.
+///
+/// Raises:
+///     ValueError: If there are fewer than 2 groups, any group is empty, h is negative, method
+///     is invalid, or the exact distribution is too large to compute.
+///
+/// Usage Example:
+///     >>> from scrutipy import h_pvalue
+///     >>> h_pvalue([3, 3, 3], 7.2)
+///     0.0035714285714285713
+pub fn h_pvalue(sizes: Vec<usize>, h: f64, method: &str) -> PyResult<f64> {
+    check_sizes(&sizes)?;
+    check_h(h)?;
+    match method {
+        "exact" => {
+            let counts = h_counts(&sizes)?;
+            Ok(exact_p(&counts, &upper_tails(&counts), h))
+        }
+        "chi2" => Ok(chi2_p(sizes.len(), h)),
+        _ => Err(KruskalError::InvalidMethod(method.to_string()).into()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (sizes, h, p_reported, tolerance=1e-6, rounding="up_or_down", threshold=5.0, symmetric=false))]
+/// Checks whether a reported Kruskal-Wallis H-value and p-value are possible for the group
+/// sizes, and consistent with each other.
+///
+/// Parameters:
+///     sizes (List[int]): The number of observations in each group.
+///     h (float | str): The reported H-value. Strings, e.g. "7.20", match the H-values which
+///     round to them, and numbers those within `tolerance`.
+///     p_reported (float | str): The reported p-value, as in `grim_u_check()`.
+///     tolerance (float): The allowed error for values given as numbers. Defaults to 1e-6.
+///     rounding (str): The rounding scheme for values given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///
+/// Returns:
+///     dict: H_Values, the possible H-values which match the reported H, and H_Achievable,
+///     True if there are any. Exact_P is the exact p-value of the matching H-value closest to
+///     the reported one, and Chi2_P the chi-squared p-value of the reported H. Exact_Consistent
+///     and Chi2_Consistent show whether the reported p-value matches either, with the exact
+///     p-value of any matching H-value counting, and Consistent is True if either matches.
+///     Achievable is True if the exact p-value of any H-value for these group sizes matches.
+///
+/// Raises:
+///     ValueError: If there are fewer than 2 groups, any group is empty, or h, rounding or the
+///     reported p-value are invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import grim_h_check
+///     >>> res = grim_h_check([3, 3, 3], "7.20", ".004")
+///     >>> res["H_Achievable"], res["Exact_Consistent"], res["Chi2_Consistent"]
+///     (True, True, False)
+///
+/// Notes:
+///     - The exact distribution assumes no ties. For large groups it is not computed, and the
+///     exact fields and H_Values, H_Achievable and Achievable are None.
+pub fn grim_h_check(
+    py: Python,
+    sizes: Vec<usize>,
+    h: ReportedMetric,
+    p_reported: ReportedMetric,
+    tolerance: f64,
+    rounding: &str,
+    threshold: f64,
+    symmetric: bool,
+) -> PyResult<PyObject> {
+    check_sizes(&sizes)?;
+    check_rounding(rounding)?;
+    let reported_h = match ReportedP::new(&h) {
+        Ok(reported @ ReportedP::Equal(..)) => reported,
+        _ => {
+            let text = match h {
+                ReportedMetric::Num(value) => value.to_string(),
+                ReportedMetric::Str(text) => text,
+            };
+            return Err(KruskalError::InvalidH(text).into());
+        }
+    };
+    let h_value = reported_h.value();
+    check_h(h_value)?;
+    let reported_p = ReportedP::new(&p_reported)?;
+    let matches_h = |value: f64| reported_h.matches(value, tolerance, rounding, threshold, symmetric);
+    let matches_p = |p: f64| reported_p.matches(p, tolerance, rounding, threshold, symmetric);
+
+    let chi2 = chi2_p(sizes.len(), h_value);
+    let counts = h_counts(&sizes).ok();
+    let tails = counts.as_deref().map(upper_tails);
+
+    let mut h_values = None;
+    let mut exact = None;
+    let mut exact_consistent = None;
+    let mut achievable = None;
+    if let (Some(counts), Some(tails)) = (&counts, &tails) {
+        let matching: Vec<(f64, f64)> = counts
+            .iter()
+            .zip(tails)
+            .filter(|((value, _), _)| matches_h(*value))
+            .map(|((value, _), &p)| (*value, p))
+            .collect();
+        exact = matching
+            .iter()
+            .min_by(|a, b| (a.0 - h_value).abs().total_cmp(&(b.0 - h_value).abs()))
+            .map(|&(_, p)| p);
+        exact_consistent = Some(matching.iter().any(|&(_, p)| matches_p(p)));
+        h_values = Some(matching.into_iter().map(|(value, _)| value).collect::<Vec<f64>>());
+        achievable = Some(tails.iter().any(|&p| matches_p(p)));
+    }
+
+    let dict = PyDict::new(py);
+    dict.set_item("H_Achievable", h_values.as_ref().map(|values| !values.is_empty()))?;
+    dict.set_item("H_Values", h_values)?;
+    dict.set_item("Exact_P", exact)?;
+    dict.set_item("Chi2_P", chi2)?;
+    dict.set_item("Exact_Consistent", exact_consistent)?;
+    dict.set_item("Chi2_Consistent", matches_p(chi2))?;
+    dict.set_item("Consistent", exact_consistent == Some(true) || matches_p(chi2))?;
+    dict.set_item("Achievable", achievable)?;
+    Ok(dict.into())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn h_counts_small() {
+        // every assignment of one observation per group has rank sums 1, 2 and 3 in some order
+        assert_eq!(h_counts(&[1, 1, 1]).unwrap(), vec![(2.0, 6.0)]);
+
+        // 6! / (2! 2! 2!) = 90 assignments, and the largest H of 4.5714 comes from giving the
+        // groups {1, 2}, {3, 4} and {5, 6} in any order
+        let counts = h_counts(&[2, 2, 2]).unwrap();
+        assert_eq!(counts.iter().map(|(_, count)| count).sum::<f64>(), 90.0);
+        let (largest, count) = *counts.last().unwrap();
+        assert!((largest - 32.0 / 7.0).abs() < 1e-12);
+        assert_eq!(count, 6.0);
+        assert!(counts.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn pvalues() {
+        let counts = h_counts(&[3, 3, 3]).unwrap();
+        let tails = upper_tails(&counts);
+        assert_eq!(tails[0], 1.0);
+        assert!((exact_p(&counts, &tails, 7.2) - 6.0 / 1680.0).abs() < 1e-15);
+        // rounded down H-values still count
+        assert_eq!(exact_p(&counts, &tails, 7.2), exact_p(&counts, &tails, 7.2 + 1e-12));
+        assert_eq!(exact_p(&counts, &tails, 7.3), 0.0);
+        // with 2 degrees of freedom, the chi-squared p-value is exp(-h / 2)
+        assert!((chi2_p(3, 5.991464547107979) - 0.05).abs() < 1e-10);
+        assert!((chi2_p(3, 100.0) / (-50f64).exp() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert_eq!(check_sizes(&[5]), Err(KruskalError::TooFewGroups(1)));
+        assert_eq!(check_sizes(&[5, 0]), Err(KruskalError::EmptyGroup(vec![5, 0])));
+        assert!(check_h(-1.0).is_err());
+        assert_eq!(h_counts(&[40, 40, 40]), Err(KruskalError::ExactTooLarge(vec![40, 40, 40])));
+        // four groups of 5 have at most 76^3 / 4! rank sums
+        assert!((rank_sums_bound(&[5, 5, 5, 5]) - 76.0f64.powi(3) / 24.0).abs() < 1e-9);
+        assert!(rank_sums_bound(&[5, 5, 5]) >= h_counts(&[5, 5, 5]).unwrap().len() as f64);
+    }
+}
@@ -14,8 +14,17 @@ pub mod debit;
 pub mod debit_map;
 pub mod debit_map_df;
 pub mod closure;
+pub mod closure_checkpoint;
+pub mod sprite;
 pub mod rivets;
 pub mod confusion;
+pub mod distributions;
 pub mod grim_u;
+pub mod signed_rank;
+pub mod kruskal_wallis;
+pub mod statcheck;
+pub mod apa;
+pub mod split;
+pub mod progress;
 use utils::*;
 
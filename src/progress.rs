@@ -0,0 +1,200 @@
+//! Progress bars for the long-running functions.
+//!
+//! Every long-running function takes a `progress` argument, one of "auto", "always" and
+//! "never", and falls back on a module-wide default when it is not given. In "auto" mode a bar is
+//! only drawn when stderr is a terminal, so batch jobs and notebooks stay quiet.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, TermLike};
+use pyo3::{exceptions::PyValueError, pyfunction, PyErr, PyResult};
+use thiserror::Error;
+
+/// When to draw a progress bar.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// Draw a bar only if stderr is a terminal.
+    Auto,
+    /// Always draw a bar on stderr.
+    Always,
+    /// Never draw a bar.
+    Never,
+}
+
+/// The module-wide default mode, stored as the discriminant of a `Progress`.
+static DEFAULT_PROGRESS: AtomicU8 = AtomicU8::new(Progress::Auto as u8);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ProgressError {
+    #[error("progress must be one of \"auto\", \"always\" and \"never\", not \"{0}\"")]
+    InvalidMode(String),
+}
+
+impl From<ProgressError> for PyErr {
+    fn from(err: ProgressError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+impl Progress {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => Progress::Always,
+            2 => Progress::Never,
+            _ => Progress::Auto,
+        }
+    }
+
+    /// Parses a mode name, with None giving the module-wide default.
+    pub fn parse(mode: Option<&str>) -> Result<Self, ProgressError> {
+        match mode {
+            None => Ok(Progress::default_mode()),
+            Some("auto") => Ok(Progress::Auto),
+            Some("always") => Ok(Progress::Always),
+            Some("never") => Ok(Progress::Never),
+            Some(other) => Err(ProgressError::InvalidMode(other.to_string())),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Progress::Auto => "auto",
+            Progress::Always => "always",
+            Progress::Never => "never",
+        }
+    }
+
+    /// The module-wide default mode.
+    pub fn default_mode() -> Self {
+        Progress::from_u8(DEFAULT_PROGRESS.load(Ordering::Relaxed))
+    }
+
+    /// Whether this mode draws a bar in the current process.
+    pub fn enabled(self) -> bool {
+        match self {
+            Progress::Auto => io::stderr().is_terminal(),
+            Progress::Always => true,
+            Progress::Never => false,
+        }
+    }
+
+    /// A progress bar of length `len` which is drawn or hidden according to the mode.
+    pub fn bar(self, len: u64) -> ProgressBar {
+        match self {
+            Progress::Never => ProgressBar::hidden(),
+            Progress::Auto if !self.enabled() => ProgressBar::hidden(),
+            Progress::Auto => ProgressBar::new(len),
+            // indicatif hides its own stderr target when stderr is not a terminal, so forcing a
+            // bar needs a target which always draws
+            Progress::Always => ProgressBar::with_draw_target(
+                Some(len),
+                ProgressDrawTarget::term_like(Box::new(ForcedStderr)),
+            ),
+        }
+    }
+}
+
+/// A draw target which writes to stderr whether or not it is a terminal.
+#[derive(Debug)]
+struct ForcedStderr;
+
+impl ForcedStderr {
+    fn write_escape(&self, code: &str, n: usize) -> io::Result<()> {
+        if n == 0 {
+            return Ok(());
+        }
+        write!(io::stderr(), "\x1b[{n}{code}")
+    }
+}
+
+impl TermLike for ForcedStderr {
+    fn width(&self) -> u16 {
+        80
+    }
+
+    fn move_cursor_up(&self, n: usize) -> io::Result<()> {
+        self.write_escape("A", n)
+    }
+
+    fn move_cursor_down(&self, n: usize) -> io::Result<()> {
+        self.write_escape("B", n)
+    }
+
+    fn move_cursor_right(&self, n: usize) -> io::Result<()> {
+        self.write_escape("C", n)
+    }
+
+    fn move_cursor_left(&self, n: usize) -> io::Result<()> {
+        self.write_escape("D", n)
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        writeln!(io::stderr(), "{s}")
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        write!(io::stderr(), "{s}")
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        write!(io::stderr(), "\r\x1b[2K")
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+#[pyfunction(signature = (mode))]
+/// Sets the default progress mode of every long-running function.
+///
+/// Parameters:
+///     mode (str): One of "auto", which shows progress bars only when stderr is a terminal,
+///     "always" or "never". The default is "auto".
+///
+/// Raises:
+///     ValueError: If mode is not one of "auto", "always" and "never".
+///
+/// Usage Example:
+///     >>> from scrutipy import set_progress, get_progress
+///     >>> set_progress("never")
+///     >>> get_progress()
+///     'never'
+pub fn set_progress(mode: &str) -> PyResult<()> {
+    let progress = Progress::parse(Some(mode))?;
+    DEFAULT_PROGRESS.store(progress as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+#[pyfunction]
+/// Returns the default progress mode of every long-running function, as set by `set_progress()`.
+///
+/// Returns:
+///     str: One of "auto", "always" and "never".
+pub fn get_progress() -> &'static str {
+    Progress::default_mode().name()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_modes() {
+        assert_eq!(Progress::parse(Some("auto")), Ok(Progress::Auto));
+        assert_eq!(Progress::parse(Some("always")), Ok(Progress::Always));
+        assert_eq!(Progress::parse(Some("never")), Ok(Progress::Never));
+        assert_eq!(
+            Progress::parse(Some("sometimes")),
+            Err(ProgressError::InvalidMode("sometimes".to_string()))
+        );
+    }
+
+    #[test]
+    fn never_is_hidden() {
+        assert!(Progress::Never.bar(10).is_hidden());
+        assert!(!Progress::Never.enabled());
+        assert!(!Progress::Always.bar(10).is_hidden());
+    }
+}
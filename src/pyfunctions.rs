@@ -5,12 +5,20 @@ use crate::grim::grim_scalar;
 use crate::grimmer::grimmer;
 use crate::grim_map_df::grim_map_pl;
 use crate::grim_map::grim_map;
-use crate::closure::closure;
+use crate::closure::{closure, closure_sample, closure_estimate, ClosureEstimate};
+use crate::sprite::sprite;
 use crate::debit::debit;
 use crate::debit_map_df::debit_map_pl;
 use crate::debit_map::debit_map;
-use crate::confusion::{calculate_snspn, calculate_ppvnpv, calculate_likelihoodratios, calculate_metrics_from_counts};
-use crate::grim_u::{simrank, simrank_single, simrank_tied, simrank_tied_single, SimRank, SimRankTied};
+use crate::rivets::rivets;
+use crate::confusion::{calculate_snspn, calculate_ppvnpv, calculate_likelihoodratios, calculate_metrics_from_counts, reconstruct_confusion, check_confusion_ci, calculate_kappa, kappa_from_counts};
+use crate::grim_u::{simrank, simrank_single, simrank_tied, simrank_tied_single, SimRank, SimRankTied, u_distribution, u_pvalue, grim_u_check, simrank_all, simrank_count, simrank_ties, u_variance, auc_check};
+use crate::signed_rank::{w_distribution, w_pvalue, grim_w_check};
+use crate::kruskal_wallis::{h_distribution, h_pvalue, grim_h_check};
+use crate::statcheck::{t_test_check, paired_t_check, anova_check};
+use crate::apa::{parse_apa, apa_check};
+use crate::split::{split_by_parens, split_mean_sd, restore_zeros};
+use crate::progress::{set_progress, get_progress};
 
 /// Scrutipy: A library for scientific error checking and fraud detection.
 ///
@@ -26,18 +34,51 @@ fn scrutipy(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(grim_map_pl, module)?)?;
     module.add_function(wrap_pyfunction!(grim_map, module)?)?;
     module.add_function(wrap_pyfunction!(closure, module)?)?;
+    module.add_function(wrap_pyfunction!(closure_sample, module)?)?;
+    module.add_function(wrap_pyfunction!(closure_estimate, module)?)?;
+    module.add_function(wrap_pyfunction!(sprite, module)?)?;
+    module.add_class::<ClosureEstimate>()?;
     module.add_function(wrap_pyfunction!(debit, module)?)?;
     module.add_function(wrap_pyfunction!(debit_map_pl, module)?)?;
     module.add_function(wrap_pyfunction!(debit_map, module)?)?;
+    module.add_function(wrap_pyfunction!(rivets, module)?)?;
     module.add_function(wrap_pyfunction!(calculate_snspn, module)?)?;
     module.add_function(wrap_pyfunction!(calculate_ppvnpv, module)?)?;
     module.add_function(wrap_pyfunction!(calculate_likelihoodratios, module)?)?;
     module.add_function(wrap_pyfunction!(calculate_metrics_from_counts, module)?)?;
+    module.add_function(wrap_pyfunction!(reconstruct_confusion, module)?)?;
+    module.add_function(wrap_pyfunction!(check_confusion_ci, module)?)?;
+    module.add_function(wrap_pyfunction!(calculate_kappa, module)?)?;
+    module.add_function(wrap_pyfunction!(kappa_from_counts, module)?)?;
     module.add_function(wrap_pyfunction!(simrank, module)?)?;
     module.add_function(wrap_pyfunction!(simrank_single, module)?)?;
     module.add_class::<SimRank>()?;
     module.add_class::<SimRankTied>()?;
     module.add_function(wrap_pyfunction!(simrank_tied, module)?)?;
     module.add_function(wrap_pyfunction!(simrank_tied_single, module)?)?;
+    module.add_function(wrap_pyfunction!(simrank_all, module)?)?;
+    module.add_function(wrap_pyfunction!(simrank_count, module)?)?;
+    module.add_function(wrap_pyfunction!(simrank_ties, module)?)?;
+    module.add_function(wrap_pyfunction!(u_variance, module)?)?;
+    module.add_function(wrap_pyfunction!(u_distribution, module)?)?;
+    module.add_function(wrap_pyfunction!(u_pvalue, module)?)?;
+    module.add_function(wrap_pyfunction!(grim_u_check, module)?)?;
+    module.add_function(wrap_pyfunction!(auc_check, module)?)?;
+    module.add_function(wrap_pyfunction!(w_distribution, module)?)?;
+    module.add_function(wrap_pyfunction!(w_pvalue, module)?)?;
+    module.add_function(wrap_pyfunction!(grim_w_check, module)?)?;
+    module.add_function(wrap_pyfunction!(h_distribution, module)?)?;
+    module.add_function(wrap_pyfunction!(h_pvalue, module)?)?;
+    module.add_function(wrap_pyfunction!(grim_h_check, module)?)?;
+    module.add_function(wrap_pyfunction!(t_test_check, module)?)?;
+    module.add_function(wrap_pyfunction!(paired_t_check, module)?)?;
+    module.add_function(wrap_pyfunction!(anova_check, module)?)?;
+    module.add_function(wrap_pyfunction!(parse_apa, module)?)?;
+    module.add_function(wrap_pyfunction!(apa_check, module)?)?;
+    module.add_function(wrap_pyfunction!(split_by_parens, module)?)?;
+    module.add_function(wrap_pyfunction!(split_mean_sd, module)?)?;
+    module.add_function(wrap_pyfunction!(restore_zeros, module)?)?;
+    module.add_function(wrap_pyfunction!(set_progress, module)?)?;
+    module.add_function(wrap_pyfunction!(get_progress, module)?)?;
     Ok(())
 }
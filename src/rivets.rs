@@ -1,14 +1,27 @@
-use std::num::ParseFloatError;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
 use pyo3::FromPyObject;
+use thiserror::Error;
 
-use crate::{debit::unround, grim::{grim_scalar, GRIMInput, GrimScalarError}, utils::decimal_places_scalar};
+use crate::confusion::UNROUND_SCHEMES;
+use crate::debit::{unround, RoundingBoundError, UnroundReturn};
 
+/// How far a value may stray past a bound of its rounding interval through floating point error
+/// and still be inside it.
+pub(crate) const BOUND_TOLERANCE: f64 = 1e-9;
 
-pub fn rivets_t_test(x1: f64, sd1: f64, n1: f64, x2: f64, sd2: f64, n2: f64) -> f64 {
-    let p_var = (((sd1.powi(2)) * (n1 - 1.0)) + ((sd2.powi(2)) * (n2 - 1.0))) / (n1 + n2 - 2.0);
-    let p_se = ((p_var / n1) + (p_var / n2)).powf(0.5);
+/// The t statistic for the difference in means x1 - x2, with the pooled standard error of
+/// Student's t-test, or the unpooled one of Welch's.
+pub fn rivets_t_test(x1: f64, sd1: f64, n1: f64, x2: f64, sd2: f64, n2: f64, welch: bool) -> f64 {
+    let se = if welch {
+        ((sd1.powi(2) / n1) + (sd2.powi(2) / n2)).powf(0.5)
+    } else {
+        let p_var = (((sd1.powi(2)) * (n1 - 1.0)) + ((sd2.powi(2)) * (n2 - 1.0))) / (n1 + n2 - 2.0);
+        ((p_var / n1) + (p_var / n2)).powf(0.5)
+    };
 
-    (x1-x2) / p_se
+    (x1-x2) / se
 }
 
 #[derive(FromPyObject)]
@@ -18,95 +31,243 @@ pub enum RIVETSInput {
 }
 
 impl RIVETSInput {
-    fn to_grim(&self) -> GRIMInput {
+    pub(crate) fn to_str(&self) -> String {
         match self {
-            RIVETSInput::Str(s) => GRIMInput::Str(s.to_string()),
-            RIVETSInput::Num(n) => GRIMInput::Num(*n),
+            RIVETSInput::Str(s) => s.trim().to_string(),
+            RIVETSInput::Num(n) => n.to_string(),
         }
     }
+}
 
-    fn to_some_str(&self) -> Option<String> {
-        match self {
-            RIVETSInput::Str(s) => Some(s.to_string()),
-            RIVETSInput::Num(n) => Some(n.to_string()),
-        }
-    }
+#[derive(Debug, Error)]
+pub enum RivetsError {
+    #[error("{0} is not a number")]
+    NotANumber(String),
+    #[error("Each group needs at least 2 observations and each observation at least 1 item, but got n = {0} and items = {1}")]
+    TooFewObservations(u32, u32),
+    #[error("{0} is not a valid rounding scheme. Use one of {1:?}")]
+    InvalidRounding(String, Vec<&'static str>),
+    #[error("There is no interval of values which round to {0} with anti_trunc rounding")]
+    ZeroAntiTrunc(String),
 }
 
-#[allow(unused_variables)]
-#[allow(clippy::too_many_arguments)]
-pub fn rivets_possible_values(x: RIVETSInput, 
-    n: u32, 
-    items: u32, 
-    dispersion: u32, 
-    rounding: String, 
-    threshold: f64, 
-    symmetric: bool, 
-    tolerance: f64, 
-    digits: i32) -> Result<Vec<f64>, GrimScalarError> {
-
-    // moved this up first to avoid a clone
-    let x_unrounded = unround(&x.to_some_str().unwrap(), &rounding, threshold).unwrap();
-
-    let x_lower = x_unrounded.lower;
-    let x_upper = x_unrounded.upper;
-    let x_incl_lower = x_unrounded.incl_lower;
-    let x_incl_upper = x_unrounded.incl_upper;
-
-    let grim_consistent = grim_scalar(x.to_grim(), n, rounding, items, false, false, threshold, symmetric, tolerance);
-
-    if !grim_consistent {
-        todo!()
+impl From<RivetsError> for PyErr {
+    fn from(err: RivetsError) -> PyErr {
+        PyValueError::new_err(err.to_string())
     }
+}
 
-    let digits = decimal_places_scalar(x.to_some_str().as_deref(), ".");
-    let p10 = 10.0f64.powi(digits.unwrap() + 1);
+/// The interval of values which round to the reported value `x`.
+pub(crate) fn rounding_interval(x: &RIVETSInput, rounding: &str, threshold: f64) -> Result<UnroundReturn, RivetsError> {
+    let text = x.to_str();
+    if text.parse::<f64>().is_err() {
+        return Err(RivetsError::NotANumber(text));
+    }
+    unround(&text, rounding, threshold).map_err(|err| match err {
+        RoundingBoundError::ZeroError => RivetsError::ZeroAntiTrunc(text),
+        RoundingBoundError::RoundingError => {
+            RivetsError::InvalidRounding(rounding.to_string(), UNROUND_SCHEMES.to_vec())
+        }
+    })
+}
 
-    let granularity = 1.0 / (n*items) as f64;
+/// Lists the GRIM-consistent means within the rounding interval of the reported mean `x`: the
+/// multiples of 1 / (n * items) which round to it. An empty list means `x` is GRIM-inconsistent.
+pub fn rivets_possible_values(
+    x: &RIVETSInput,
+    n: u32,
+    items: u32,
+    rounding: &str,
+    threshold: f64,
+) -> Result<Vec<f64>, RivetsError> {
+    if n == 0 || items == 0 {
+        return Err(RivetsError::TooFewObservations(n, items));
+    }
+    let bounds = rounding_interval(x, rounding, threshold)?;
+    let scale = (n * items) as f64;
 
+    let first = (bounds.lower * scale - 1.0).floor() as i64;
+    let last = (bounds.upper * scale + 1.0).ceil() as i64;
 
-    let Ok(x): Result<f64, ParseFloatError> = x.to_some_str().unwrap().parse() else {
-        return Err(GrimScalarError::ParseFloatError)
-    };
+    Ok((first..=last)
+        .map(|k| k as f64 / scale)
+        .filter(|&mean| {
+            !transgresses_at_lower(mean, bounds.lower, bounds.incl_lower)
+                && !transgresses_at_upper(mean, bounds.upper, bounds.incl_upper)
+        })
+        .collect())
+}
 
-    // let transgresses_at_lower = if x_incl_lower {"<"} else {"<="};
-    //let transgresses_at_upper = if x_incl_upper {">"} else {">="};
+/// Whether `a` lies below the lower bound `b`, which is part of the interval if `strict`, so
+/// that only values strictly below it transgress.
+pub fn transgresses_at_lower(a: f64, b: f64, strict: bool) -> bool {
+    if strict { a < b - BOUND_TOLERANCE } else { a <= b + BOUND_TOLERANCE }
+}
 
-    let mut x_possible = vec![x];
+/// Whether `a` lies above the upper bound `b`, which is part of the interval if `strict`, as in
+/// `transgresses_at_lower()`.
+pub fn transgresses_at_upper(a: f64, b: f64, strict: bool) -> bool {
+    if strict { a > b + BOUND_TOLERANCE } else { a >= b - BOUND_TOLERANCE }
+}
 
-    let x_current = x;
+/// The smallest and largest t statistics from any of the means `x1` and `x2` and standard
+/// deviations in the intervals `sd1` and `sd2`.
+///
+/// t is linear in each mean, and for a given difference in means it moves monotonically with the
+/// standard error, which grows with each standard deviation, so the extremes lie at the corners
+/// of the ranges.
+pub(crate) fn t_range(x1: &[f64], sd1: (f64, f64), n1: f64, x2: &[f64], sd2: (f64, f64), n2: f64, welch: bool) -> Option<(f64, f64)> {
+    let (x1_min, x1_max) = (x1.first()?, x1.last()?);
+    let (x2_min, x2_max) = (x2.first()?, x2.last()?);
 
-    loop {
-        let x_current = x_current - granularity;
-        if transgresses_at_lower(x_current, x_lower, x_incl_lower) {
-            break
+    let mut ts = Vec::with_capacity(16);
+    for (mean1, mean2) in [(x1_min, x2_min), (x1_min, x2_max), (x1_max, x2_min), (x1_max, x2_max)] {
+        for s1 in [sd1.0, sd1.1] {
+            for s2 in [sd2.0, sd2.1] {
+                ts.push(rivets_t_test(*mean1, s1, n1, *mean2, s2, n2, welch));
+            }
         }
-        x_possible.push(x_current)
     }
+    // a difference of 0 over a standard error of 0 has no t statistic
+    let ts = ts.into_iter().filter(|t| !t.is_nan());
+    let (t_min, t_max) = ts.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    (t_min <= t_max).then_some((t_min, t_max))
+}
 
-    loop {
-        let x_current = x_current - granularity;
-        if transgresses_at_upper(x_current, x_upper, x_incl_upper) {
-            break
-        }
-        x_possible.push(x_current)
-    }
+/// The interval of standard deviations which round to the reported `sd`, which cannot be negative.
+fn sd_interval(sd: &RIVETSInput, rounding: &str, threshold: f64) -> Result<(f64, f64), RivetsError> {
+    let bounds = rounding_interval(sd, rounding, threshold)?;
+    Ok((bounds.lower.max(0.0), bounds.upper))
+}
 
-    if x_possible.len() == 1 {
-        x_possible.push(x_possible[0])
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (x1, sd1, n1, x2, sd2, n2, t_reported, items=1, rounding="up_or_down", threshold=5.0, welch=false))]
+/// Checks whether a reported two-sample t statistic could have arisen from the reported means,
+/// standard deviations and group sizes (RIVETS).
+///
+/// Each reported mean stands for any unrounded mean which rounds to it, but with n observations
+/// of `items` items each, only multiples of 1 / (n * items) are possible (GRIM). RIVETS lists
+/// these for both groups, takes the standard deviations anywhere in their rounding intervals,
+/// and finds the full range of t statistics they allow. The reported t is consistent if any
+/// value in its own rounding interval falls in that range.
+///
+/// Parameters:
+///     x1 (str | float): The reported mean of group 1. Give it as a string, e.g. "5.20", to keep
+///     trailing zeros.
+///     sd1 (str | float): The reported standard deviation of group 1.
+///     n1 (int): The size of group 1.
+///     x2 (str | float): The reported mean of group 2.
+///     sd2 (str | float): The reported standard deviation of group 2.
+///     n2 (int): The size of group 2.
+///     t_reported (str | float): The reported t statistic, for the difference x1 - x2.
+///     items (int): The number of items averaged into each observation. Defaults to 1.
+///     rounding (str): The rounding scheme of all reported values, one of "up_or_down", "up",
+///     "down", "even", "ceiling", "floor", "trunc" and "anti_trunc". Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     welch (bool): Whether the test is Welch's t-test rather than Student's, with a pooled
+///     standard deviation. Defaults to False.
+///
+/// Returns:
+///     dict: X1_Values and X2_Values, the GRIM-consistent means of each group; T_Min and T_Max,
+///     the range of t statistics they allow, or None if either group has no consistent mean;
+///     and Consistent, whether the reported t could have arisen.
+///
+/// Raises:
+///     ValueError: If a group has fewer than 2 observations, items is 0, a reported value is
+///     not a number, or the rounding scheme is invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import rivets
+///     >>> res = rivets("5.25", "1.10", 20, "4.60", "1.30", 20, "1.71")
+///     >>> res["X1_Values"], res["Consistent"]
+///     ([5.25], True)
+///     >>> rivets("5.25", "1.10", 20, "4.60", "1.30", 20, "1.80")["Consistent"]
+///     False
+///
+/// Notes:
+///     - t is computed as (x1 - x2) / se, so a t reported for x2 - x1, or as an absolute value,
+///     needs the groups swapped.
+pub fn rivets(
+    py: Python,
+    x1: RIVETSInput,
+    sd1: RIVETSInput,
+    n1: u32,
+    x2: RIVETSInput,
+    sd2: RIVETSInput,
+    n2: u32,
+    t_reported: RIVETSInput,
+    items: u32,
+    rounding: &str,
+    threshold: f64,
+    welch: bool,
+) -> PyResult<PyObject> {
+    for n in [n1, n2] {
+        if n < 2 {
+            return Err(RivetsError::TooFewObservations(n, items).into());
+        }
     }
+    let x1_values = rivets_possible_values(&x1, n1, items, rounding, threshold)?;
+    let x2_values = rivets_possible_values(&x2, n2, items, rounding, threshold)?;
+    let sd1 = sd_interval(&sd1, rounding, threshold)?;
+    let sd2 = sd_interval(&sd2, rounding, threshold)?;
+    let t_bounds = rounding_interval(&t_reported, rounding, threshold)?;
 
-    Ok(x_possible)
+    let range = t_range(&x1_values, sd1, n1 as f64, &x2_values, sd2, n2 as f64, welch);
+    let consistent = range.is_some_and(|(t_min, t_max)| {
+        !transgresses_at_lower(t_max, t_bounds.lower, t_bounds.incl_lower)
+            && !transgresses_at_upper(t_min, t_bounds.upper, t_bounds.incl_upper)
+    });
 
+    let dict = PyDict::new(py);
+    dict.set_item("X1_Values", x1_values)?;
+    dict.set_item("X2_Values", x2_values)?;
+    dict.set_item("T_Min", range.map(|(t_min, _)| t_min))?;
+    dict.set_item("T_Max", range.map(|(_, t_max)| t_max))?;
+    dict.set_item("Consistent", consistent)?;
+    Ok(dict.into())
 }
 
-pub fn transgresses_at_lower(a: f64, b: f64, strict: bool) -> bool {
-    if strict { a < b } else { a <= b }
-}
+#[cfg(test)]
+pub mod tests {
+    use super::*;
 
-pub fn transgresses_at_upper(a: f64, b: f64, strict: bool) -> bool {
-    if strict { a > b } else { a >= b }
-}
+    fn values(x: &str, n: u32) -> Vec<f64> {
+        rivets_possible_values(&RIVETSInput::Str(x.to_string()), n, 1, "up_or_down", 5.0).unwrap()
+    }
+
+    #[test]
+    fn possible_values() {
+        // with 20 observations, means move in steps of 0.05
+        assert_eq!(values("5.25", 20), vec![5.25]);
+        assert!(values("5.27", 20).is_empty());
+        // both ends of an up_or_down interval are included
+        assert_eq!(values("5.3", 40), vec![5.25, 5.275, 5.3, 5.325, 5.35]);
+        assert_eq!(values("-1.2", 20), vec![-1.25, -1.2, -1.15]);
+        // the interval of "up" rounding excludes its upper end
+        let up = rivets_possible_values(&RIVETSInput::Num(5.3), 40, 1, "up", 5.0).unwrap();
+        assert_eq!(up, vec![5.25, 5.275, 5.3, 5.325]);
+    }
 
+    #[test]
+    fn t_statistics() {
+        // pooled variance (1.21 + 1.69) / 2 = 1.45, so se = sqrt(0.145)
+        let t = rivets_t_test(5.25, 1.1, 20.0, 4.6, 1.3, 20.0, false);
+        assert!((t - 0.65 / 0.145f64.sqrt()).abs() < 1e-12);
+        // with equal group sizes, Welch's t is the same
+        assert!((rivets_t_test(5.25, 1.1, 20.0, 4.6, 1.3, 20.0, true) - t).abs() < 1e-12);
 
+        let (t_min, t_max) = t_range(&[5.25], (1.095, 1.105), 20.0, &[4.6], (1.295, 1.305), 20.0, false).unwrap();
+        assert!(t_min < t && t < t_max);
+        assert!((t_min - 0.65 / ((1.105f64.powi(2) + 1.305f64.powi(2)) / 20.0).sqrt()).abs() < 1e-12);
+        assert!(t_range(&[], (1.0, 1.1), 20.0, &[4.6], (1.0, 1.1), 20.0, false).is_none());
+    }
 
+    #[test]
+    fn invalid_input() {
+        let x = RIVETSInput::Str("5.25".to_string());
+        assert!(matches!(rivets_possible_values(&x, 20, 1, "up_from", 5.0), Err(RivetsError::InvalidRounding(..))));
+        assert!(matches!(rivets_possible_values(&x, 0, 1, "up_or_down", 5.0), Err(RivetsError::TooFewObservations(0, 1))));
+        let text = RIVETSInput::Str("five".to_string());
+        assert!(matches!(rivets_possible_values(&text, 20, 1, "up_or_down", 5.0), Err(RivetsError::NotANumber(_))));
+    }
+}
@@ -0,0 +1,262 @@
+//! Granularity checks for the Wilcoxon signed-rank test.
+//!
+//! With n pairs and no ties, W, the sum of the ranks of the positive differences, is an integer
+//! from 0 to n(n + 1) / 2, and its exact null distribution comes from counting the subsets of
+//! the ranks 1 to n with each sum. The checks mirror those of `grim_u` for the Mann-Whitney U.
+
+use std::ops::AddAssign;
+
+use num::{One, Zero};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use thiserror::Error;
+
+use crate::confusion::ReportedMetric;
+use crate::grim_u::{check_rounding, exact_p, normal_tail_p, Alternative, ReportedP};
+
+/// The largest exact W distribution computed, in steps of the dynamic-programming recursion:
+/// n passes over n(n + 1) / 2 + 1 counts. Beyond this, use the normal approximation.
+const MAX_EXACT_CELLS: usize = 50_000_000;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SignedRankError {
+    #[error("There must be at least one pair, but got n = 0")]
+    NoPairs,
+    #[error("The W-value {0} is outside the possible range from 0 to n * (n + 1) / 2 = {1}")]
+    WOutOfRange(f64, usize),
+    #[error("The W-value {0} must be an integer, or a half-integer with tied ranks")]
+    WParity(f64),
+    #[error("{0} is not a valid method. Use \"exact\" or \"normal\"")]
+    InvalidMethod(String),
+    #[error("The exact distribution of W for n = {0} pairs is too large to compute. Use method = \"normal\"")]
+    ExactTooLarge(usize),
+}
+
+impl From<SignedRankError> for PyErr {
+    fn from(err: SignedRankError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The largest possible W for `n` pairs, when every difference is positive.
+fn max_w(n: usize) -> usize {
+    n * (n + 1) / 2
+}
+
+/// Checks that there is at least one pair and that `w` is a possible W-value: in range, and a
+/// multiple of 0.5.
+fn check_w(n: usize, w: f64) -> Result<(), SignedRankError> {
+    if n == 0 {
+        return Err(SignedRankError::NoPairs);
+    }
+    if !(0.0..=max_w(n) as f64).contains(&w) {
+        return Err(SignedRankError::WOutOfRange(w, max_w(n)));
+    }
+    if (w * 2.0).fract() != 0.0 {
+        return Err(SignedRankError::WParity(w));
+    }
+    Ok(())
+}
+
+/// Counts the subsets of the ranks 1 to `n` with each sum W, from 0 to n(n + 1) / 2.
+///
+/// Each rank is either among the positive differences or not, so adding rank r to the subsets
+/// of 1 to r - 1 gives g(r)[w] = g(r - 1)[w] + g(r - 1)[w - r]. The counts sum to 2^n, so use
+/// floats for probabilities and `u128` for exact counts of up to 127 pairs.
+///
+/// # Errors
+///
+/// `ExactTooLarge` if the recursion would exceed `MAX_EXACT_CELLS`.
+pub fn w_counts<T>(n: usize) -> Result<Vec<T>, SignedRankError>
+where
+    T: Copy + Zero + One + AddAssign,
+{
+    let max = max_w(n);
+    if n.saturating_mul(max + 1) > MAX_EXACT_CELLS {
+        return Err(SignedRankError::ExactTooLarge(n));
+    }
+
+    let mut counts = vec![T::zero(); max + 1];
+    counts[0] = T::one();
+    for rank in 1..=n {
+        // run downwards so that each rank is added at most once
+        for w in (rank..=max_w(rank)).rev() {
+            let without = counts[w - rank];
+            counts[w] += without;
+        }
+    }
+    Ok(counts)
+}
+
+/// The normal approximation to the p-value of `w`, with mean n(n + 1) / 4 and variance
+/// n(n + 1)(2n + 1) / 24.
+fn normal_p(n: usize, w: f64, alternative: Alternative, continuity: bool) -> f64 {
+    let n = n as f64;
+    let mean = n * (n + 1.0) / 4.0;
+    let sd = (n * (n + 1.0) * (2.0 * n + 1.0) / 24.0).sqrt();
+    normal_tail_p(w, mean, sd, alternative, continuity)
+}
+
+#[pyfunction(signature = (n))]
+/// Computes the exact null distribution of the Wilcoxon signed-rank statistic W.
+///
+/// Parameters:
+///     n (int): The number of pairs, after dropping any zero differences.
+///
+/// Returns:
+///     List[float]: The probability of each W-value from 0 to n * (n + 1) / 2, assuming no ties.
+///
+/// Raises:
+///     ValueError: If n is 0, or the distribution is too large to compute exactly.
+///
+/// Usage Example:
+///     >>> from scrutipy import w_distribution
+///     >>> w_distribution(2)
+///     [0.25, 0.25, 0.25, 0.25]
+pub fn w_distribution(n: usize) -> PyResult<Vec<f64>> {
+    check_w(n, 0.0)?;
+    let counts = w_counts::<f64>(n)?;
+    let total: f64 = counts.iter().sum();
+    Ok(counts.into_iter().map(|count| count / total).collect())
+}
+
+#[pyfunction(signature = (n, w, alternative="two-sided", method="exact", continuity=true))]
+/// Computes the p-value of a Wilcoxon signed-rank W-value.
+///
+/// Parameters:
+///     n (int): The number of pairs, after dropping any zero differences.
+///     w (float): The sum of the ranks of the positive differences, which may be a
+///     half-integer if there were ties.
+///     alternative (str): "two-sided", "less" or "greater". Defaults to "two-sided".
+///     method (str): "exact", for the exact distribution without ties, or "normal", for the
+///     normal approximation. Defaults to "exact".
+///     continuity (bool): Whether the normal approximation uses a continuity correction.
+///     Ignored by the exact method. Defaults to True.
+///
+/// Returns:
+///     float: The p-value.
+///
+/// Raises:
+///     ValueError: If n is 0, w is out of range or not a multiple of 0.5, alternative or
+///     method are invalid, or the exact distribution is too large to compute.
+///
+/// Usage Example:
+///     >>> from scrutipy import w_pvalue
+///     >>> w_pvalue(10, 8)
+///     0.048828125
+pub fn w_pvalue(n: usize, w: f64, alternative: &str, method: &str, continuity: bool) -> PyResult<f64> {
+    check_w(n, w)?;
+    let alternative = Alternative::new(alternative)?;
+    match method {
+        "exact" => Ok(exact_p(&w_counts(n)?, w, alternative)),
+        "normal" => Ok(normal_p(n, w, alternative, continuity)),
+        _ => Err(SignedRankError::InvalidMethod(method.to_string()).into()),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (n, w, p_reported, alternative="two-sided", tolerance=1e-6, rounding="up_or_down", threshold=5.0, symmetric=false))]
+/// Checks whether a reported Wilcoxon signed-rank p-value is consistent with the number of
+/// pairs and the W-value.
+///
+/// Parameters:
+///     n (int): The number of pairs, after dropping any zero differences.
+///     w (float): The reported sum of the ranks of the positive differences, which may be a
+///     half-integer if there were ties.
+///     p_reported (float | str): The reported p-value. Strings, e.g. ".049", match when a
+///     p-value rounds to them, and strings starting with "<", e.g. "<.05", match any smaller
+///     p-value. Numbers match within `tolerance`.
+///     alternative (str): "two-sided", "less" or "greater". Defaults to "two-sided".
+///     tolerance (float): The allowed error for p-values given as numbers. Defaults to 1e-6.
+///     rounding (str): The rounding scheme for p-values given as strings. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///     symmetric (bool): Whether rounding is symmetric around zero. Defaults to False.
+///
+/// Returns:
+///     dict: The same fields as `grim_u_check()`: Exact_P, Normal_P and Normal_CC_P, whether
+///     each matches the reported p-value, Consistent if any of them does, and Achievable if the
+///     exact p-value of any W-value for n pairs matches.
+///
+/// Raises:
+///     ValueError: If n is 0, w is out of range or not a multiple of 0.5, or alternative,
+///     rounding or the reported p-value are invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import grim_w_check
+///     >>> res = grim_w_check(10, 8, ".049")
+///     >>> res["Exact_Consistent"], res["Normal_Consistent"]
+///     (True, False)
+///
+/// Notes:
+///     - The exact distribution assumes no ties. For very large n it is not computed, and the
+///     exact fields are None.
+pub fn grim_w_check(
+    py: Python,
+    n: usize,
+    w: f64,
+    p_reported: ReportedMetric,
+    alternative: &str,
+    tolerance: f64,
+    rounding: &str,
+    threshold: f64,
+    symmetric: bool,
+) -> PyResult<PyObject> {
+    check_w(n, w)?;
+    let alternative = Alternative::new(alternative)?;
+    check_rounding(rounding)?;
+    let reported = ReportedP::new(&p_reported)?;
+    let matches = |p: f64| reported.matches(p, tolerance, rounding, threshold, symmetric);
+
+    let normal = normal_p(n, w, alternative, false);
+    let normal_cc = normal_p(n, w, alternative, true);
+    let counts = w_counts(n).ok();
+    let exact = counts.as_ref().map(|counts| exact_p(counts, w, alternative));
+    let achievable = counts.as_ref().map(|counts| {
+        (0..counts.len()).any(|k| matches(exact_p(counts, k as f64, alternative)))
+    });
+
+    let dict = PyDict::new(py);
+    dict.set_item("Exact_P", exact)?;
+    dict.set_item("Normal_P", normal)?;
+    dict.set_item("Normal_CC_P", normal_cc)?;
+    dict.set_item("Exact_Consistent", exact.map(matches))?;
+    dict.set_item("Normal_Consistent", matches(normal))?;
+    dict.set_item("Normal_CC_Consistent", matches(normal_cc))?;
+    dict.set_item("Consistent", exact.is_some_and(matches) || matches(normal) || matches(normal_cc))?;
+    dict.set_item("Achievable", achievable)?;
+    Ok(dict.into())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn w_counts_small() {
+        // the subsets of {1, 2, 3}, with sums 0, 1, 2, 3, 3, 4, 5, 6
+        assert_eq!(w_counts::<u128>(3).unwrap(), vec![1, 1, 1, 2, 1, 1, 1]);
+        assert_eq!(w_counts::<u128>(12).unwrap().iter().sum::<u128>(), 4096);
+        let counts = w_counts::<u128>(9).unwrap();
+        assert!(counts.iter().eq(counts.iter().rev()));
+    }
+
+    #[test]
+    fn pvalues() {
+        let counts = w_counts::<f64>(10).unwrap();
+        // P(W <= 8) = 25 / 1024
+        assert_eq!(exact_p(&counts, 8.0, Alternative::Less), 25.0 / 1024.0);
+        assert_eq!(exact_p(&counts, 8.0, Alternative::TwoSided), 50.0 / 1024.0);
+        // 19.5 below the mean of 27.5, with sd sqrt(96.25)
+        assert!((normal_p(10, 8.0, Alternative::TwoSided, false) - 0.046853285).abs() < 1e-8);
+        assert!((normal_p(10, 8.0, Alternative::TwoSided, true) - 0.052787001).abs() < 1e-8);
+    }
+
+    #[test]
+    fn w_range() {
+        assert_eq!(check_w(0, 0.0), Err(SignedRankError::NoPairs));
+        assert_eq!(check_w(4, 10.5), Err(SignedRankError::WOutOfRange(10.5, 10)));
+        assert_eq!(check_w(4, 3.25), Err(SignedRankError::WParity(3.25)));
+        assert!(check_w(4, 3.5).is_ok());
+    }
+}
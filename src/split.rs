@@ -0,0 +1,279 @@
+//! Tidying columns for `grim_map()` and `debit_map()`: splitting cells which report a mean and
+//! standard deviation together, such as "5.3 (1.20)" or "5.3 ± 1.2", into separate columns, and
+//! restoring the trailing zeros which numeric columns lose.
+//!
+//! Numbers are kept as strings, so that their trailing zeros, and with them their reported
+//! precision, survive.
+
+use polars::prelude::*;
+use pyo3::exceptions::{PyImportError, PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3_polars::PySeries;
+use thiserror::Error;
+
+use crate::utils::restore_zeros_vec;
+
+/// The means, the standard deviations, and the indices of any cells which could not be split,
+/// or None if every cell could. The means and standard deviations of those cells are None.
+type SplitColumns = (Vec<Option<String>>, Vec<Option<String>>, Option<Vec<usize>>);
+
+/// The ways of writing plus or minus between a mean and a standard deviation.
+const PLUS_MINUS: [&str; 3] = ["±", "+/-", "+-"];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SplitError {
+    #[error("{0} is not a valid separator. Use \"parens\", \"brackets\", \"braces\" or \"plus_minus\"")]
+    InvalidSeparator(String),
+}
+
+impl From<SplitError> for PyErr {
+    fn from(err: SplitError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// How the standard deviation is set off from the mean.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Separator {
+    Parens,
+    Brackets,
+    Braces,
+    PlusMinus,
+}
+
+impl Separator {
+    pub fn new(sep: &str) -> Result<Self, SplitError> {
+        match sep {
+            "parens" => Ok(Separator::Parens),
+            "brackets" => Ok(Separator::Brackets),
+            "braces" => Ok(Separator::Braces),
+            "plus_minus" => Ok(Separator::PlusMinus),
+            _ => Err(SplitError::InvalidSeparator(sep.to_string())),
+        }
+    }
+
+    /// Splits `cell` into its mean and standard deviation, if it has this form and both are
+    /// numbers.
+    pub fn split(&self, cell: &str) -> Option<(String, String)> {
+        let cell = cell.trim();
+        let (mean, sd) = match self {
+            Separator::Parens => split_enclosed(cell, '(', ')')?,
+            Separator::Brackets => split_enclosed(cell, '[', ']')?,
+            Separator::Braces => split_enclosed(cell, '{', '}')?,
+            Separator::PlusMinus => PLUS_MINUS.iter().find_map(|sep| cell.split_once(sep))?,
+        };
+        Some((as_number(mean)?, as_number(sd)?))
+    }
+}
+
+/// Splits "mean (sd)" at `open`, with `close` ending the cell.
+fn split_enclosed(cell: &str, open: char, close: char) -> Option<(&str, &str)> {
+    let (mean, rest) = cell.split_once(open)?;
+    Some((mean, rest.strip_suffix(close)?))
+}
+
+/// Trims `x` and checks that it is written as a plain decimal number, such as "-1.20" or ".5",
+/// returning it with any Unicode minus sign replaced.
+fn as_number(x: &str) -> Option<String> {
+    let x = x.trim().replace('−', "-");
+    let digits = x.strip_prefix('-').unwrap_or(&x);
+    let plain = digits.chars().all(|c| c.is_ascii_digit() || c == '.') && digits.chars().any(|c| c.is_ascii_digit());
+    (plain && x.parse::<f64>().is_ok()).then_some(x)
+}
+
+/// Extracts a Polars Series, converting anything else Polars can read, such as a pandas Series.
+fn to_series(py: Python, series: &Bound<PyAny>) -> PyResult<Series> {
+    if let Ok(PySeries(series)) = series.extract() {
+        return Ok(series);
+    }
+    let polars = py.import("polars").map_err(|_| {
+        PyImportError::new_err(
+            "The 'polars' package is required for this function but is not installed.\n\
+                 You can install it with: pip install polars"
+        )
+    })?;
+    let PySeries(series) = polars.getattr("Series")?.call1((series,))?.extract()?;
+    Ok(series)
+}
+
+/// Splits each cell with the first separator which fits it.
+fn split_cells(series: &Series, separators: &[Separator]) -> PyResult<SplitColumns> {
+    let cells = series.str().map_err(|_| {
+        PyTypeError::new_err(format!("The column must be made up of strings, but has type {}.", series.dtype()))
+    })?;
+
+    let mut means = Vec::with_capacity(cells.len());
+    let mut sds = Vec::with_capacity(cells.len());
+    let mut err_inds = Vec::new();
+    for (i, cell) in cells.into_iter().enumerate() {
+        let split = cell.and_then(|cell| separators.iter().find_map(|sep| sep.split(cell)));
+        if split.is_none() {
+            err_inds.push(i);
+        }
+        let (mean, sd) = split.unzip();
+        means.push(mean);
+        sds.push(sd);
+    }
+
+    let err_output = if err_inds.is_empty() { None } else { Some(err_inds) };
+    Ok((means, sds, err_output))
+}
+
+#[pyfunction(signature = (series, sep="parens"))]
+/// Splits a column of cells such as "5.3 (1.20)" into means and standard deviations, like
+/// scrutiny's split_by_parens().
+///
+/// Parameters:
+///     series (polars.Series | pandas.Series): The column of strings to split.
+///     sep (str): How the standard deviation is set off: "parens" for "5.3 (1.20)", "brackets"
+///     for "5.3 [1.20]", "braces" for "5.3 {1.20}" or "plus_minus" for "5.3 ± 1.20" (or "+/-").
+///     Defaults to "parens".
+///
+/// Returns:
+///     tuple: (List of means, List of standard deviations, Optional list of the indices of cells
+///     which could not be split). Both numbers are kept as strings, with their trailing zeros,
+///     and are None for the cells which could not be split.
+///
+/// Raises:
+///     ValueError: If sep is invalid.
+///     TypeError: If the column is not made up of strings.
+///
+/// Usage Example:
+///     >>> import polars as pl
+///     >>> from scrutipy import split_by_parens
+///     >>> split_by_parens(pl.Series(["5.3 (1.20)", "4.10 (0.9)", "n/a"]))
+///     (['5.3', '4.10', None], ['1.20', '0.9', None], [2])
+pub fn split_by_parens(py: Python, series: Bound<PyAny>, sep: &str) -> PyResult<SplitColumns> {
+    let separator = Separator::new(sep)?;
+    split_cells(&to_series(py, &series)?, &[separator])
+}
+
+#[pyfunction(signature = (series))]
+/// Splits a column of cells reporting a mean and standard deviation as either "M (SD)" or
+/// "M ± SD" into separate columns, ready for `grim_map()` and `debit_map()`.
+///
+/// Parameters:
+///     series (polars.Series | pandas.Series): The column of strings to split. Cells may mix
+///     the two forms.
+///
+/// Returns:
+///     tuple: (List of means, List of standard deviations, Optional list of the indices of cells
+///     which could not be split), as in `split_by_parens()`.
+///
+/// Raises:
+///     TypeError: If the column is not made up of strings.
+///
+/// Usage Example:
+///     >>> import pandas as pd
+///     >>> from scrutipy import split_mean_sd, debit_map
+///     >>> df = pd.DataFrame({"m_sd": ["0.30 (0.46)", "0.28 ± 0.45"], "n": [100, 100]})
+///     >>> df["x"], df["sd"], errors = split_mean_sd(df["m_sd"])
+///     >>> debit_map(df, x_col="x", sd_col="sd", n_col="n")
+pub fn split_mean_sd(py: Python, series: Bound<PyAny>) -> PyResult<SplitColumns> {
+    split_cells(&to_series(py, &series)?, &[Separator::Parens, Separator::PlusMinus])
+}
+
+#[pyfunction(signature = (series, width=None))]
+/// Restores the trailing zeros lost when reported numbers were stored as floats, like scrutiny's
+/// restore_zeros().
+///
+/// Parameters:
+///     series (polars.Series | pandas.Series): The column of numbers, or of strings.
+///     width (int, optional): The number of decimal places to pad each value to. Defaults to
+///     None, which takes the most decimal places of any value in the column.
+///
+/// Returns:
+///     List[str]: The values as strings, padded with trailing zeros. Values which already have
+///     at least `width` decimal places are left as they are, and missing values are None.
+///
+/// Raises:
+///     TypeError: If the column is made up of neither numbers nor strings.
+///
+/// Usage Example:
+///     >>> import polars as pl
+///     >>> from scrutipy import restore_zeros
+///     >>> restore_zeros(pl.Series([5.3, 4.25, 6.0]))
+///     ['5.30', '4.25', '6.00']
+///     >>> restore_zeros(pl.Series([5.3, 4.25]), width=3)
+///     ['5.300', '4.250']
+///
+/// Notes:
+///     - Whether a value had trailing zeros can't be known from a float, so this assumes that
+///     every value in the column was reported to the same number of decimal places.
+///     grim_map() and debit_map() do the same with `restore_zeros=True`.
+pub fn restore_zeros(py: Python, series: Bound<PyAny>, width: Option<usize>) -> PyResult<Vec<Option<String>>> {
+    let series = to_series(py, &series)?;
+    if !series.dtype().is_primitive_numeric() && series.dtype() != &DataType::String {
+        return Err(PyTypeError::new_err(format!(
+            "The column must be made up of numbers or strings, but has type {}.",
+            series.dtype()
+        )));
+    }
+    let cells = series.cast(&DataType::String).map_err(|err| PyTypeError::new_err(err.to_string()))?;
+    let cells: Vec<Option<String>> = cells.str().map_err(|err| PyTypeError::new_err(err.to_string()))?
+        .into_iter()
+        .map(|cell| cell.map(str::to_string))
+        .collect();
+
+    let present: Vec<String> = cells.iter().flatten().cloned().collect();
+    let mut restored = restore_zeros_vec(&present, width).into_iter();
+    Ok(cells
+        .into_iter()
+        .map(|cell| cell.and_then(|_| restored.next()))
+        .collect())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn split(sep: Separator, cell: &str) -> Option<(String, String)> {
+        sep.split(cell)
+    }
+
+    fn pair(mean: &str, sd: &str) -> Option<(String, String)> {
+        Some((mean.to_string(), sd.to_string()))
+    }
+
+    #[test]
+    fn split_forms() {
+        assert_eq!(split(Separator::Parens, " 5.3 (1.20) "), pair("5.3", "1.20"));
+        assert_eq!(split(Separator::Parens, "−0.50(.10)"), pair("-0.50", ".10"));
+        assert_eq!(split(Separator::Brackets, "5.30 [1.2]"), pair("5.30", "1.2"));
+        assert_eq!(split(Separator::Braces, "5 {1}"), pair("5", "1"));
+        assert_eq!(split(Separator::PlusMinus, "5.3 ± 1.20"), pair("5.3", "1.20"));
+        assert_eq!(split(Separator::PlusMinus, "5.3+/-1.20"), pair("5.3", "1.20"));
+    }
+
+    #[test]
+    fn split_failures() {
+        assert_eq!(split(Separator::Parens, "5.3 ± 1.20"), None);
+        assert_eq!(split(Separator::Parens, "5.3 (1.20"), None);
+        assert_eq!(split(Separator::Parens, "5.3 (1.20) a"), None);
+        assert_eq!(split(Separator::Parens, "n/a (1.20)"), None);
+        assert_eq!(split(Separator::Parens, "inf (1.20)"), None);
+        assert_eq!(split(Separator::Parens, ". (1.20)"), None);
+        assert_eq!(Separator::new("commas"), Err(SplitError::InvalidSeparator("commas".to_string())));
+    }
+
+    #[test]
+    fn split_series() {
+        let series = Series::new("m_sd".into(), &[Some("5.3 (1.20)"), Some("4.10 ± 0.9"), None, Some("x")]);
+        let (means, sds, errors) = split_cells(&series, &[Separator::Parens, Separator::PlusMinus]).unwrap();
+        assert_eq!(means, vec![Some("5.3".to_string()), Some("4.10".to_string()), None, None]);
+        assert_eq!(sds, vec![Some("1.20".to_string()), Some("0.9".to_string()), None, None]);
+        assert_eq!(errors, Some(vec![2, 3]));
+
+        let series = Series::new("m_sd".into(), &["5.3 (1.20)"]);
+        assert_eq!(split_cells(&series, &[Separator::Parens]).unwrap().2, None);
+    }
+
+    #[test]
+    fn float_cells() {
+        // floats print without their trailing zeros, which restore_zeros_vec() puts back
+        let series = Series::new("x".into(), &[5.3, 4.25, 6.0]);
+        let cells = series.cast(&DataType::String).unwrap();
+        let cells: Vec<String> = cells.str().unwrap().into_iter().flatten().map(str::to_string).collect();
+        assert_eq!(restore_zeros_vec(&cells, None), vec!["5.30", "4.25", "6.00"]);
+    }
+}
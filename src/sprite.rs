@@ -0,0 +1,555 @@
+//! SPRITE: sample parameter reconstruction via iterative techniques
+//!
+//! SPRITE searches heuristically for datasets which match a mean and standard deviation, as a
+//! complement to CLOSURE for problems too large to enumerate. It starts from a random dataset
+//! with the right mean, then repeatedly moves one value up and another down, keeping the mean
+//! within its rounding error while pushing the standard deviation towards the target.
+//!
+//! Unlike CLOSURE, SPRITE does not find every solution, and failing to find one does not prove
+//! that none exist. This version follows rsprite2 by Lukas Wallrich, and works in the same units
+//! as `closure::dfs_parallel()`: observations are sums of `items` integer items.
+
+use std::collections::HashSet;
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::PyDict;
+use rand::Rng;
+use rand_chacha::ChaCha20Rng;
+use thiserror::Error;
+use crate::closure::{check_items, items_to_scale, ClosureResults};
+use crate::utils::stream_rng;
+
+/// The number of consecutive searches which may fail, or find only known solutions, before
+/// `sprite_search()` gives up on finding more.
+const MAX_UNPRODUCTIVE: usize = 20;
+
+#[pyfunction(signature = (mean, sd, n, scale_min, scale_max, rounding_error_mean = 0.05, rounding_error_sd = 0.05, items = 1, restrictions_exact = None, restrictions_minimum = None, n_distributions = 10, max_iter = 100000, seed = None))]
+#[cfg(not(tarpaulin_include))]
+/// A Python implementation of the SPRITE algorithm for finding datasets which match summary
+/// statistics.
+///
+/// Parameters:
+///     mean (float): The target mean of the datasets.
+///     sd (float): The target standard deviation of the datasets.
+///     n (int): The number of values in each dataset.
+///     scale_min (int): The minimum scale value (inclusive).
+///     scale_max (int): The maximum scale value (inclusive).
+///     rounding_error_mean (float): The allowable rounding error for the mean.
+///     rounding_error_sd (float): The allowable rounding error for the standard deviation.
+///     items (int): The number of items averaged into each observation. Defaults to 1. For a
+///     multi-item scale, observations lie on a grid from scale_min to scale_max with step 1/items.
+///     restrictions_exact (Optional[dict]): Scale values which must appear an exact number of
+///     times, e.g. {1: 3, 7: 0} for exactly three 1s and no 7s. Defaults to None.
+///     restrictions_minimum (Optional[dict]): Scale values which must appear at least a given
+///     number of times, e.g. {4: 2} for at least two 4s. Defaults to None.
+///     n_distributions (int): The number of distinct datasets to look for. Defaults to 10.
+///     max_iter (int): The maximum number of steps in each search. Defaults to 100000.
+///     seed (Optional[int]): Seed for the random number generator. If None, a random seed is used.
+///
+/// Returns:
+///     List[List[int]]: Up to n_distributions distinct datasets, each sorted in ascending order.
+///     If items > 1, the inner lists contain floats on the 1/items grid instead.
+///
+/// Raises:
+///     ValueError: If n or items is less than 1.
+///     ValueError: If a restricted value does not lie on the scale, appears in both kinds of
+///     restriction, or the restrictions fix more than n values.
+///     ValueError: If the mean or standard deviation cannot be reached with the restrictions and
+///     scale range. The message gives the range which can be reached.
+///
+/// Usage Example:
+///     >>> from scrutipy import sprite
+///     >>> datasets = sprite(2.2, 1.3, 20, 1, 5, n_distributions = 5, seed = 1)
+///     >>> print(len(datasets))
+///     5
+///
+/// Notes:
+///     - SPRITE stops early if 20 searches in a row find no new dataset, so the output may be
+///     shorter than n_distributions.
+///     - An empty output does not prove that no dataset matches the statistics. Use `closure()`
+///     for a complete answer.
+#[allow(clippy::too_many_arguments)]
+pub fn sprite(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    restrictions_exact: Option<Bound<'_, PyDict>>,
+    restrictions_minimum: Option<Bound<'_, PyDict>>,
+    n_distributions: usize,
+    max_iter: usize,
+    seed: Option<u64>,
+) -> PyResult<ClosureResults> {
+    check_items(items)?;
+    let restrictions_exact = extract_restrictions(restrictions_exact)?;
+    let restrictions_minimum = extract_restrictions(restrictions_minimum)?;
+    let seed = seed.unwrap_or_else(|| rand::rng().random());
+
+    let results = sprite_search(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+        &restrictions_exact,
+        &restrictions_minimum,
+        n_distributions,
+        max_iter,
+        seed,
+    )?;
+
+    if items == 1 {
+        Ok(ClosureResults::Integer(results))
+    } else {
+        Ok(ClosureResults::Items(items_to_scale(results, items)))
+    }
+}
+
+/// Reads a dict of restrictions, mapping scale values to counts, into pairs.
+fn extract_restrictions(restrictions: Option<Bound<'_, PyDict>>) -> PyResult<Vec<(f64, usize)>> {
+    match restrictions {
+        Some(dict) => dict
+            .iter()
+            .map(|(value, count)| Ok((value.extract::<f64>()?, count.extract::<usize>()?)))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SpriteError {
+    #[error("n must be at least 1, but got {0}")]
+    TooFewObservations(i32),
+    #[error("The restricted value {0} does not lie on the scale from {1} to {2} with {3} items")]
+    RestrictionOffScale(f64, i32, i32, i32),
+    #[error("The restrictions fix {0} values, more than the sample size {1}")]
+    TooManyRestricted(usize, i32),
+    #[error("Every scale value is restricted, but the restrictions only fix {0} of {1} values")]
+    NoFreeValues(usize, i32),
+    #[error("The value {0} has both an exact and a minimum restriction")]
+    RestrictionConflict(f64),
+    #[error("The mean {0} cannot be reached with these restrictions and scale range, which allow means from {1} to {2}")]
+    MeanUnreachable(f64, f64, f64),
+    #[error("The standard deviation {0} cannot be reached with this mean, these restrictions and scale range, which allow standard deviations from {1} to {2}")]
+    SdUnreachable(f64, f64, f64),
+}
+
+impl From<SpriteError> for PyErr {
+    fn from(err: SpriteError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// The constraints of a SPRITE problem, on the scale of item sums.
+struct SpriteParams {
+    n: usize,
+    /// The values which unrestricted observations may take, in ascending order.
+    values: Vec<i32>,
+    /// The observations fixed by the restrictions.
+    fixed: Vec<i32>,
+    sum_lower: i64,
+    sum_upper: i64,
+    m2_lower: f64,
+    m2_upper: f64,
+}
+
+impl SpriteParams {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        mean: f64,
+        sd: f64,
+        n: i32,
+        scale_min: i32,
+        scale_max: i32,
+        rounding_error_mean: f64,
+        rounding_error_sd: f64,
+        items: i32,
+        restrictions_exact: &[(f64, usize)],
+        restrictions_minimum: &[(f64, usize)],
+    ) -> Result<Self, SpriteError> {
+        if n < 1 {
+            return Err(SpriteError::TooFewObservations(n));
+        }
+        let fuzz = 1e-9;
+        let items_float = items as f64;
+        let n_float = n as f64;
+        let scale_min_sum = scale_min * items;
+        let scale_max_sum = scale_max * items;
+
+        let on_scale = |value: f64| -> Result<i32, SpriteError> {
+            let value_sum = (value * items_float).round();
+            if ((value * items_float) - value_sum).abs() > fuzz
+                || value_sum < scale_min_sum as f64
+                || value_sum > scale_max_sum as f64
+            {
+                return Err(SpriteError::RestrictionOffScale(value, scale_min, scale_max, items));
+            }
+            Ok(value_sum as i32)
+        };
+
+        // values with an exact count are fixed and closed to the free observations, while values
+        // with a minimum count are fixed but stay open to them
+        let mut fixed = Vec::new();
+        let mut restricted = Vec::new();
+        for &(value, count) in restrictions_exact {
+            let value_sum = on_scale(value)?;
+            restricted.push(value_sum);
+            fixed.extend(std::iter::repeat_n(value_sum, count));
+        }
+        for &(value, count) in restrictions_minimum {
+            let value_sum = on_scale(value)?;
+            if restricted.contains(&value_sum) {
+                return Err(SpriteError::RestrictionConflict(value));
+            }
+            fixed.extend(std::iter::repeat_n(value_sum, count));
+        }
+
+        if fixed.len() > n as usize {
+            return Err(SpriteError::TooManyRestricted(fixed.len(), n));
+        }
+
+        let values: Vec<i32> = (scale_min_sum..=scale_max_sum)
+            .filter(|v| !restricted.contains(v))
+            .collect();
+
+        if values.is_empty() && fixed.len() < n as usize {
+            return Err(SpriteError::NoFreeValues(fixed.len(), n));
+        }
+
+        let sd_lower = ((sd - rounding_error_sd) * items_float).max(0.0);
+        let sd_upper = (sd + rounding_error_sd) * items_float;
+
+        Ok(SpriteParams {
+            n: n as usize,
+            values,
+            fixed,
+            sum_lower: (n_float * (mean - rounding_error_mean) * items_float - fuzz).ceil() as i64,
+            sum_upper: (n_float * (mean + rounding_error_mean) * items_float + fuzz).floor() as i64,
+            m2_lower: sd_lower.powi(2) * (n_float - 1.0) * (1.0 - fuzz),
+            m2_upper: sd_upper.powi(2) * (n_float - 1.0) * (1.0 + fuzz),
+        })
+    }
+
+    /// Checks that the mean and standard deviation can be reached under the restrictions.
+    ///
+    /// These are necessary conditions only. The free observations can reach any sum between
+    /// n_free times the lowest and the highest open value, give or take gaps left by restricted
+    /// values. For a given sum, the sum of their squares is at least that of equal values, and
+    /// at most that of values piled onto the lowest and highest open values, since every open
+    /// value y satisfies y^2 <= (a + b) y - ab.
+    fn check_reachable(&self, mean: f64, sd: f64, items: i32) -> Result<(), SpriteError> {
+        let n_free = (self.n - self.fixed.len()) as i64;
+        let fixed_sum: i64 = self.fixed.iter().map(|&v| v as i64).sum();
+        let fixed_squares: i64 = self.fixed.iter().map(|&v| (v as i64).pow(2)).sum();
+        let (low, high) = match (self.values.first(), self.values.last()) {
+            (Some(&low), Some(&high)) if n_free > 0 => (low as i64, high as i64),
+            _ => (0, 0),
+        };
+
+        let reachable_lower = fixed_sum + n_free * low;
+        let reachable_upper = fixed_sum + n_free * high;
+        let sum_lower = self.sum_lower.max(reachable_lower);
+        let sum_upper = self.sum_upper.min(reachable_upper);
+
+        let to_scale = |sum: i64| sum as f64 / (self.n as f64 * items as f64);
+        if sum_lower > sum_upper {
+            return Err(SpriteError::MeanUnreachable(mean, to_scale(reachable_lower), to_scale(reachable_upper)));
+        }
+
+        let mut m2_min = f64::INFINITY;
+        let mut m2_max = f64::NEG_INFINITY;
+        for sum in sum_lower..=sum_upper {
+            let free_sum = sum - fixed_sum;
+            let (squares_min, squares_max) = if n_free == 0 {
+                (0.0, 0.0)
+            } else {
+                (
+                    (free_sum as f64).powi(2) / n_free as f64,
+                    ((low + high) * free_sum - n_free * low * high) as f64,
+                )
+            };
+            let lowest = self.m2(sum, fixed_squares) + squares_min;
+            let highest = self.m2(sum, fixed_squares) + squares_max;
+            if lowest <= self.m2_upper && highest >= self.m2_lower {
+                return Ok(());
+            }
+            m2_min = m2_min.min(lowest);
+            m2_max = m2_max.max(highest);
+        }
+
+        let to_sd = |m2: f64| (m2.max(0.0) / (self.n as f64 - 1.0)).sqrt() / items as f64;
+        Err(SpriteError::SdUnreachable(sd, to_sd(m2_min), to_sd(m2_max)))
+    }
+
+    /// The second moment of a dataset with the given sum and sum of squares.
+    fn m2(&self, sum: i64, sum_squares: i64) -> f64 {
+        sum_squares as f64 - (sum as f64).powi(2) / self.n as f64
+    }
+}
+
+/// Runs SPRITE, collecting up to `n_distributions` distinct datasets.
+///
+/// # Arguments
+///
+/// See `closure::dfs_parallel()` for the parameters of the problem, which SPRITE shares.
+///
+/// - `restrictions_exact`: Pairs of a scale value and the exact number of times it must appear.
+///   A count of 0 rules the value out.
+/// - `restrictions_minimum`: Pairs of a scale value and the least number of times it must appear.
+/// - `n_distributions`: The number of distinct datasets to look for.
+/// - `max_iter`: The maximum number of steps in each search.
+/// - `seed`: Seed for the random number generator, so that runs can be reproduced.
+///
+/// # Returns
+///
+/// The datasets found, each sorted in ascending order, in the units of `dfs_parallel()`. The
+/// search stops early once `MAX_UNPRODUCTIVE` searches in a row find no new dataset.
+///
+/// # Errors
+///
+/// Returns an error if the restrictions are invalid, or if `SpriteParams::check_reachable()`
+/// shows that no dataset can match the mean and standard deviation, so that the search is
+/// never run in vain.
+#[allow(clippy::too_many_arguments)]
+pub fn sprite_search(
+    mean: f64,
+    sd: f64,
+    n: i32,
+    scale_min: i32,
+    scale_max: i32,
+    rounding_error_mean: f64,
+    rounding_error_sd: f64,
+    items: i32,
+    restrictions_exact: &[(f64, usize)],
+    restrictions_minimum: &[(f64, usize)],
+    n_distributions: usize,
+    max_iter: usize,
+    seed: u64,
+) -> Result<Vec<Vec<i32>>, SpriteError> {
+    let params = SpriteParams::new(
+        mean,
+        sd,
+        n,
+        scale_min,
+        scale_max,
+        rounding_error_mean,
+        rounding_error_sd,
+        items,
+        restrictions_exact,
+        restrictions_minimum,
+    )?;
+    params.check_reachable(mean, sd, items)?;
+
+    let mut rng = stream_rng(seed, 0);
+    let mut seen = HashSet::new();
+    let mut results = Vec::new();
+    let mut unproductive = 0;
+
+    while results.len() < n_distributions && unproductive < MAX_UNPRODUCTIVE {
+        match find_distribution(&params, &mut rng, max_iter) {
+            Some(dataset) if seen.insert(dataset.clone()) => {
+                results.push(dataset);
+                unproductive = 0;
+            }
+            _ => unproductive += 1,
+        }
+    }
+
+    Ok(results)
+}
+
+/// Runs a single SPRITE search from a random starting point.
+///
+/// Free observations are stored as indices into `params.values`, so that moving one up or down
+/// skips over restricted values.
+fn find_distribution(params: &SpriteParams, rng: &mut ChaCha20Rng, max_iter: usize) -> Option<Vec<i32>> {
+    let n_values = params.values.len();
+    let n_free = params.n - params.fixed.len();
+
+    let mut free: Vec<usize> = (0..n_free).map(|_| rng.random_range(0..n_values)).collect();
+
+    let value = |i: usize| params.values[i] as i64;
+    let mut sum: i64 = params.fixed.iter().map(|&v| v as i64).sum::<i64>()
+        + free.iter().map(|&i| value(i)).sum::<i64>();
+    let mut sum_squares: i64 = params.fixed.iter().map(|&v| (v as i64).pow(2)).sum::<i64>()
+        + free.iter().map(|&i| value(i).pow(2)).sum::<i64>();
+
+    // first bring the mean within its rounding error, one step at a time
+    let mut steps = 0;
+    while (sum < params.sum_lower || sum > params.sum_upper) && n_free > 0 && steps < max_iter {
+        steps += 1;
+        let k = rng.random_range(0..n_free);
+        let old = free[k];
+        let new = if sum < params.sum_lower {
+            if old + 1 == n_values {
+                continue;
+            }
+            old + 1
+        } else {
+            if old == 0 {
+                continue;
+            }
+            old - 1
+        };
+        free[k] = new;
+        sum += value(new) - value(old);
+        sum_squares += value(new).pow(2) - value(old).pow(2);
+    }
+
+    // then move pairs of values apart or together until the SD is within its rounding error
+    loop {
+        if sum < params.sum_lower || sum > params.sum_upper {
+            return None;
+        }
+
+        let m2 = params.m2(sum, sum_squares);
+        if m2 >= params.m2_lower && m2 <= params.m2_upper {
+            break;
+        }
+
+        if n_free < 2 || steps >= max_iter {
+            return None;
+        }
+        steps += 1;
+
+        let up = rng.random_range(0..n_free);
+        let down = rng.random_range(0..n_free);
+        if up == down || free[up] + 1 == n_values || free[down] == 0 {
+            continue;
+        }
+
+        let (up_old, down_old) = (free[up], free[down]);
+        let new_sum = sum + value(up_old + 1) - value(up_old) + value(down_old - 1) - value(down_old);
+        if new_sum < params.sum_lower || new_sum > params.sum_upper {
+            continue;
+        }
+        let new_sum_squares = sum_squares + value(up_old + 1).pow(2) - value(up_old).pow(2)
+            + value(down_old - 1).pow(2)
+            - value(down_old).pow(2);
+
+        let new_m2 = params.m2(new_sum, new_sum_squares);
+        let towards_target = if m2 < params.m2_lower { new_m2 > m2 } else { new_m2 < m2 };
+        if towards_target {
+            free[up] += 1;
+            free[down] -= 1;
+            sum = new_sum;
+            sum_squares = new_sum_squares;
+        }
+    }
+
+    let mut dataset: Vec<i32> = params.fixed.clone();
+    dataset.extend(free.iter().map(|&i| params.values[i]));
+    dataset.sort_unstable();
+    Some(dataset)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn mean_sd(dataset: &[i32], items: i32) -> (f64, f64) {
+        let n = dataset.len() as f64;
+        let values: Vec<f64> = dataset.iter().map(|&v| v as f64 / items as f64).collect();
+        let mean = values.iter().sum::<f64>() / n;
+        let m2: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+        (mean, (m2 / (n - 1.0)).sqrt())
+    }
+
+    #[test]
+    fn sprite_test_valid() {
+        let results = sprite_search(2.2, 1.3, 20, 1, 5, 0.05, 0.05, 1, &[], &[], 10, 100000, 1).unwrap();
+        assert_eq!(results.len(), 10);
+        for dataset in results {
+            let (mean, sd) = mean_sd(&dataset, 1);
+            assert!((mean - 2.2).abs() <= 0.05 + 1e-9);
+            assert!((sd - 1.3).abs() <= 0.05 + 1e-9);
+            assert!(dataset.iter().all(|&v| (1..=5).contains(&v)));
+        }
+    }
+
+    #[test]
+    fn sprite_test_seeded() {
+        let a = sprite_search(2.2, 1.3, 20, 1, 5, 0.05, 0.05, 1, &[], &[], 5, 100000, 7).unwrap();
+        let b = sprite_search(2.2, 1.3, 20, 1, 5, 0.05, 0.05, 1, &[], &[], 5, 100000, 7).unwrap();
+        assert_eq!(a, b);
+
+        // the same seed gives the same datasets across rand releases
+        let pinned = sprite_search(2.2, 1.3, 20, 1, 5, 0.05, 0.05, 1, &[], &[], 2, 100000, 7).unwrap();
+        assert_eq!(pinned, vec![
+            vec![1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 3, 3, 3, 4, 4, 4, 5],
+            vec![1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 3, 3, 4, 4, 4, 4, 5],
+        ]);
+    }
+
+    #[test]
+    fn sprite_test_restrictions() {
+        let results = sprite_search(3.0, 1.5, 30, 1, 7, 0.05, 0.05, 1, &[(1.0, 4), (7.0, 0)], &[], 10, 100000, 3).unwrap();
+        assert!(!results.is_empty());
+        for dataset in results {
+            assert_eq!(dataset.iter().filter(|&&v| v == 1).count(), 4);
+            assert!(!dataset.contains(&7));
+            let (mean, sd) = mean_sd(&dataset, 1);
+            assert!((mean - 3.0).abs() <= 0.05 + 1e-9);
+            assert!((sd - 1.5).abs() <= 0.05 + 1e-9);
+        }
+    }
+
+    #[test]
+    fn sprite_test_items() {
+        // all three solutions found by closure for the same problem
+        let mut results = sprite_search(2.25, 0.87, 4, 1, 4, 0.005, 0.005, 2, &[], &[], 10, 100000, 5).unwrap();
+        results.sort();
+        assert_eq!(results, vec![vec![2, 5, 5, 6], vec![3, 3, 6, 6], vec![3, 4, 4, 7]]);
+    }
+
+    #[test]
+    fn sprite_test_minimum() {
+        let results = sprite_search(3.0, 1.5, 30, 1, 7, 0.05, 0.05, 1, &[], &[(5.0, 6)], 10, 100000, 2).unwrap();
+        assert!(!results.is_empty());
+        for dataset in results {
+            assert!(dataset.iter().filter(|&&v| v == 5).count() >= 6);
+        }
+    }
+
+    #[test]
+    fn sprite_test_unreachable() {
+        // ten 7s out of 20 put the mean at no less than 4
+        let err = sprite_search(3.0, 2.0, 20, 1, 7, 0.05, 0.05, 1, &[], &[(7.0, 10)], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::MeanUnreachable(3.0, 4.0, 7.0));
+
+        // with no 1s or 5s, a mean of 3 leaves too little room for an SD of 1.5
+        let err = sprite_search(3.0, 1.5, 20, 1, 5, 0.05, 0.05, 1, &[(1.0, 0), (5.0, 0)], &[], 10, 100000, 1).unwrap_err();
+        assert!(matches!(err, SpriteError::SdUnreachable(..)));
+
+        let err = sprite_search(3.0, 1.5, 20, 1, 5, 0.05, 0.05, 1, &[(1.0, 0)], &[(1.0, 2)], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::RestrictionConflict(1.0));
+    }
+
+    #[test]
+    fn sprite_test_invalid_restriction() {
+        let err = sprite_search(2.2, 1.3, 20, 1, 5, 0.05, 0.05, 1, &[(1.5, 2)], &[], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::RestrictionOffScale(1.5, 1, 5, 1));
+
+        let err = sprite_search(2.2, 1.3, 2, 1, 5, 0.05, 0.05, 1, &[(1.0, 3)], &[], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::TooManyRestricted(3, 2));
+    }
+
+    #[test]
+    fn sprite_test_invalid_size() {
+        let err = sprite_search(2.2, 1.3, 0, 1, 5, 0.05, 0.05, 1, &[], &[], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::TooFewObservations(0));
+        let err = sprite_search(2.2, 1.3, -3, 1, 5, 0.05, 0.05, 1, &[], &[], 10, 100000, 1).unwrap_err();
+        assert_eq!(err, SpriteError::TooFewObservations(-3));
+
+        // sprite() rejects items below 1 before the search
+        assert!(check_items(0).is_err());
+        assert!(check_items(-2).is_err());
+    }
+}
@@ -0,0 +1,660 @@
+//! Recomputing t and F statistics and their p-values from summary statistics, in the manner of
+//! statcheck.
+//!
+//! Each reported mean and standard deviation stands for any value which rounds to it, so the
+//! inputs allow a range of statistics and p-values rather than a single one. A reported
+//! statistic or p-value is consistent if it could have been rounded from a value in that range.
+//! An inconsistent p-value is also a decision error if every possible p-value falls on the other
+//! side of alpha from the reported one.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use thiserror::Error;
+
+use crate::confusion::ReportedMetric;
+use crate::distributions::beta_inc;
+use crate::grim_u::{GrimUError, ReportedP};
+use crate::rivets::{rivets_t_test, rounding_interval, t_range, RIVETSInput, RivetsError, BOUND_TOLERANCE};
+
+/// The most ANOVA groups whose rounded means are accounted for, as the largest F comes from
+/// checking every corner of the 2^k rounding intervals.
+const MAX_ROUNDED_GROUPS: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum StatcheckError {
+    #[error("means, sds and ns must have the same length, but got {0}, {1} and {2}")]
+    LengthMismatch(usize, usize, usize),
+    #[error("An ANOVA needs at least 2 groups, but got {0}")]
+    TooFewGroups(usize),
+    #[error("{0}")]
+    TooFewObservations(String),
+    #[error("The rounding of means can only be accounted for in up to {1} groups, but got {0}")]
+    TooManyGroups(usize, usize),
+    #[error("alpha must be between 0 and 1, but got {0}")]
+    InvalidAlpha(f64),
+    #[error(transparent)]
+    Reported(#[from] RivetsError),
+    #[error(transparent)]
+    ReportedP(#[from] GrimUError),
+}
+
+impl From<StatcheckError> for PyErr {
+    fn from(err: StatcheckError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// A reported value and the interval of values which round to it.
+#[derive(Clone, Copy, Debug)]
+struct Rounded {
+    value: f64,
+    lower: f64,
+    upper: f64,
+}
+
+impl Rounded {
+    fn new(x: &RIVETSInput, rounding: &str, threshold: f64) -> Result<Self, StatcheckError> {
+        let bounds = rounding_interval(x, rounding, threshold)?;
+        // rounding_interval() has already checked that x is a number
+        let value = x.to_str().parse().unwrap_or(f64::NAN);
+        Ok(Rounded { value, lower: bounds.lower, upper: bounds.upper })
+    }
+
+    /// A standard deviation, which cannot be negative.
+    fn sd(x: &RIVETSInput, rounding: &str, threshold: f64) -> Result<Self, StatcheckError> {
+        let rounded = Rounded::new(x, rounding, threshold)?;
+        Ok(Rounded { lower: rounded.lower.max(0.0), ..rounded })
+    }
+
+    fn corners(&self) -> [f64; 2] {
+        [self.lower, self.upper]
+    }
+}
+
+/// A statistic recomputed from reported summary statistics, at the reported values and over the
+/// rounding intervals of the inputs.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Recomputed {
+    pub(crate) statistic: f64,
+    pub(crate) statistic_range: (f64, f64),
+    pub(crate) p: f64,
+    pub(crate) p_range: (f64, f64),
+}
+
+/// How a reported statistic and p-value compare with a recomputed one.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Comparison {
+    pub(crate) statistic: Option<bool>,
+    pub(crate) p: Option<bool>,
+    pub(crate) decision_error: Option<bool>,
+}
+
+/// The two-sided p-value of `t` with `df` degrees of freedom.
+pub(crate) fn t_p(t: f64, df: f64) -> f64 {
+    beta_inc(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// The p-value of `f` with `df1` and `df2` degrees of freedom.
+pub(crate) fn f_p(f: f64, df1: f64, df2: f64) -> f64 {
+    beta_inc(df2 / 2.0, df1 / 2.0, df2 / (df2 + df1 * f))
+}
+
+/// The Welch-Satterthwaite degrees of freedom of Welch's t-test.
+fn welch_df(sd1: f64, n1: f64, sd2: f64, n2: f64) -> f64 {
+    let (a, b) = (sd1.powi(2) / n1, sd2.powi(2) / n2);
+    (a + b).powi(2) / (a.powi(2) / (n1 - 1.0) + b.powi(2) / (n2 - 1.0))
+}
+
+/// The smallest and largest of `values`, skipping any NaN.
+pub(crate) fn min_max(values: impl IntoIterator<Item = f64>) -> (f64, f64) {
+    values
+        .into_iter()
+        .filter(|value| !value.is_nan())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), value| (lo.min(value), hi.max(value)))
+}
+
+/// The range of |t| for t from `lower` to `upper`.
+pub(crate) fn abs_range((lower, upper): (f64, f64)) -> (f64, f64) {
+    if lower <= 0.0 && upper >= 0.0 {
+        (0.0, lower.abs().max(upper))
+    } else {
+        min_max([lower.abs(), upper.abs()])
+    }
+}
+
+fn overlaps(a: (f64, f64), b: (f64, f64)) -> bool {
+    a.0 <= b.1 + BOUND_TOLERANCE && b.0 <= a.1 + BOUND_TOLERANCE
+}
+
+/// Recomputes an independent-samples t statistic, which is the absolute value of
+/// `rivets_t_test()`, with its range over the rounding intervals from `rivets::t_range()`.
+///
+/// The p-value falls with |t| and with the degrees of freedom, so its extremes pair the extremes
+/// of each. Welch's degrees of freedom depend on the standard deviations only through the ratio
+/// r = (sd2^2 / n2) / (sd1^2 / n1), and rise from n1 - 1 at r = 0 to their maximum of n1 + n2 - 2
+/// at r = (n2 - 1) / (n1 - 1), then fall towards n2 - 1. The corners bound r, so they give the
+/// smallest degrees of freedom, and the largest too unless r can reach the peak inside the box.
+fn t_independent(x1: Rounded, sd1: Rounded, n1: f64, x2: Rounded, sd2: Rounded, n2: f64, welch: bool) -> (Recomputed, f64) {
+    let df = |s1: f64, s2: f64| if welch { welch_df(s1, n1, s2, n2) } else { n1 + n2 - 2.0 };
+    let statistic = rivets_t_test(x1.value, sd1.value, n1, x2.value, sd2.value, n2, welch).abs();
+    let point_df = df(sd1.value, sd2.value);
+
+    let (sd1_range, sd2_range) = ((sd1.lower, sd1.upper), (sd2.lower, sd2.upper));
+    let statistic_range = t_range(&x1.corners(), sd1_range, n1, &x2.corners(), sd2_range, n2, welch)
+        .map_or((f64::NAN, f64::NAN), abs_range);
+
+    let mut dfs = vec![point_df];
+    for s1 in sd1.corners() {
+        for s2 in sd2.corners() {
+            dfs.push(df(s1, s2));
+        }
+    }
+    // r falls with sd1 and rises with sd2, so it is smallest at (sd1.upper, sd2.lower)
+    let past_peak = |s1: f64, s2: f64| s2.powi(2) / n2 * (n1 - 1.0) - s1.powi(2) / n1 * (n2 - 1.0);
+    if welch && past_peak(sd1.upper, sd2.lower) <= 0.0 && past_peak(sd1.lower, sd2.upper) >= 0.0 {
+        dfs.push(n1 + n2 - 2.0);
+    }
+    let df_range = min_max(dfs);
+
+    let recomputed = Recomputed {
+        statistic,
+        statistic_range,
+        p: t_p(statistic, point_df),
+        p_range: (t_p(statistic_range.1, df_range.1), t_p(statistic_range.0, df_range.0)),
+    };
+    (recomputed, point_df)
+}
+
+/// Recomputes a paired t statistic, as the absolute value of the mean difference over its
+/// standard error.
+fn t_paired(mean_diff: Rounded, sd_diff: Rounded, n: f64) -> Recomputed {
+    let t = |diff: f64, sd: f64| diff / (sd / n.sqrt());
+    let df = n - 1.0;
+    let statistic = t(mean_diff.value, sd_diff.value).abs();
+
+    let ts = mean_diff
+        .corners()
+        .into_iter()
+        .flat_map(|diff| sd_diff.corners().map(|sd| t(diff, sd)));
+    let statistic_range = abs_range(min_max(ts));
+
+    Recomputed {
+        statistic,
+        statistic_range,
+        p: t_p(statistic, df),
+        p_range: (t_p(statistic_range.1, df), t_p(statistic_range.0, df)),
+    }
+}
+
+/// The between-groups sum of squares of groups with these means and sizes.
+fn between_ss(means: &[f64], ns: &[f64]) -> f64 {
+    let grand_mean = means.iter().zip(ns).map(|(mean, n)| mean * n).sum::<f64>() / ns.iter().sum::<f64>();
+    means.iter().zip(ns).map(|(mean, n)| n * (mean - grand_mean).powi(2)).sum()
+}
+
+/// The smallest between-groups sum of squares with each mean in its rounding interval.
+///
+/// sum(n_i (m_i - M)^2) is the smallest value of sum(n_i (m_i - c)^2) over c, so swapping the
+/// minima, this is the smallest over c of sum(n_i d_i(c)^2), with d_i(c) the distance from c to
+/// the i-th interval. That is convex in c, so a ternary search finds it.
+fn min_between_ss(means: &[Rounded], ns: &[f64]) -> f64 {
+    let distance_ss = |c: f64| -> f64 {
+        means
+            .iter()
+            .zip(ns)
+            .map(|(mean, n)| n * (mean.lower - c).max(c - mean.upper).max(0.0).powi(2))
+            .sum()
+    };
+    let (mut lower, mut upper) = min_max(means.iter().flat_map(Rounded::corners));
+    for _ in 0..200 {
+        let third = (upper - lower) / 3.0;
+        if distance_ss(lower + third) <= distance_ss(upper - third) {
+            upper -= third;
+        } else {
+            lower += third;
+        }
+    }
+    distance_ss(0.5 * (lower + upper))
+}
+
+/// The largest between-groups sum of squares with each mean in its rounding interval. This is
+/// convex in the means, so it is largest at a corner of the intervals.
+fn max_between_ss(means: &[Rounded], ns: &[f64]) -> f64 {
+    let mut corner = vec![0.0; means.len()];
+    (0..1usize << means.len())
+        .map(|mask| {
+            for (i, mean) in means.iter().enumerate() {
+                corner[i] = if mask >> i & 1 == 1 { mean.upper } else { mean.lower };
+            }
+            between_ss(&corner, ns)
+        })
+        .fold(0.0, f64::max)
+}
+
+/// Recomputes the F statistic of a one-way ANOVA, returning it with its degrees of freedom.
+fn anova_f(means: &[Rounded], sds: &[Rounded], ns: &[f64]) -> (Recomputed, f64, f64) {
+    let df1 = (ns.len() - 1) as f64;
+    let df2 = ns.iter().sum::<f64>() - ns.len() as f64;
+    let within_ss = |sds: &mut dyn Iterator<Item = f64>| -> f64 {
+        sds.zip(ns).map(|(sd, n)| (n - 1.0) * sd.powi(2)).sum()
+    };
+    let f = |between: f64, within: f64| {
+        // without differences between the groups, F is 0 even without any within them
+        if between == 0.0 { 0.0 } else { (between / df1) / (within / df2) }
+    };
+
+    let point_means: Vec<f64> = means.iter().map(|mean| mean.value).collect();
+    let statistic = f(between_ss(&point_means, ns), within_ss(&mut sds.iter().map(|sd| sd.value)));
+    let statistic_range = (
+        f(min_between_ss(means, ns), within_ss(&mut sds.iter().map(|sd| sd.upper))),
+        f(max_between_ss(means, ns), within_ss(&mut sds.iter().map(|sd| sd.lower))),
+    );
+
+    let recomputed = Recomputed {
+        statistic,
+        statistic_range,
+        p: f_p(statistic, df1, df2),
+        p_range: (f_p(statistic_range.1, df1, df2), f_p(statistic_range.0, df1, df2)),
+    };
+    (recomputed, df1, df2)
+}
+
+/// Compares the reported statistic and p-value, where given, with a recomputed statistic.
+///
+/// Reported statistics are compared as absolute values. Numbers are taken to the precision they
+/// print with, like strings. A p-value at or below alpha counts as significant.
+pub(crate) fn compare(
+    recomputed: &Recomputed,
+    statistic: Option<&RIVETSInput>,
+    p: Option<&ReportedMetric>,
+    alpha: f64,
+    rounding: &str,
+    threshold: f64,
+) -> Result<Comparison, StatcheckError> {
+    let statistic = statistic
+        .map(|reported| -> Result<bool, StatcheckError> {
+            // the sign of a t statistic depends on the order of the groups
+            let text = reported.to_str();
+            let reported = RIVETSInput::Str(text.trim_start_matches('-').to_string());
+            let bounds = rounding_interval(&reported, rounding, threshold)?;
+            Ok(overlaps((bounds.lower, bounds.upper), recomputed.statistic_range))
+        })
+        .transpose()?;
+
+    let Some(p) = p else {
+        return Ok(Comparison { statistic, p: None, decision_error: None });
+    };
+    let reported = match p {
+        ReportedMetric::Num(p) => ReportedP::new(&ReportedMetric::Str(p.to_string()))?,
+        ReportedMetric::Str(_) => ReportedP::new(p)?,
+    };
+    let consistent = match reported {
+        ReportedP::Below(bound) => recomputed.p_range.0 < bound,
+        ReportedP::Above(bound) => recomputed.p_range.1 > bound,
+        ReportedP::Equal(value, digits) => {
+            let text = format!("{value:.*}", digits.unwrap_or(0).max(0) as usize);
+            let bounds = rounding_interval(&RIVETSInput::Str(text), rounding, threshold)?;
+            overlaps((bounds.lower, bounds.upper), recomputed.p_range)
+        }
+    };
+    let decision_error = !consistent
+        && match reported {
+            ReportedP::Equal(value, _) if value <= alpha => recomputed.p_range.0 > alpha,
+            ReportedP::Equal(..) => recomputed.p_range.1 <= alpha,
+            // a bound on the wrong side of alpha claims neither significance nor its absence
+            ReportedP::Below(bound) => bound <= alpha && recomputed.p_range.0 > alpha,
+            ReportedP::Above(bound) => bound >= alpha && recomputed.p_range.1 <= alpha,
+        };
+
+    Ok(Comparison { statistic, p: Some(consistent), decision_error: Some(decision_error) })
+}
+
+/// Collects a recomputed statistic and its comparison with the reported one into a dict.
+fn to_dict<'py>(py: Python<'py>, recomputed: &Recomputed, comparison: &Comparison) -> PyResult<Bound<'py, PyDict>> {
+    let consistent = match (comparison.statistic, comparison.p) {
+        (None, None) => None,
+        (statistic, p) => Some(statistic.unwrap_or(true) && p.unwrap_or(true)),
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item("Statistic", recomputed.statistic)?;
+    dict.set_item("Statistic_Min", recomputed.statistic_range.0)?;
+    dict.set_item("Statistic_Max", recomputed.statistic_range.1)?;
+    dict.set_item("P", recomputed.p)?;
+    dict.set_item("P_Min", recomputed.p_range.0)?;
+    dict.set_item("P_Max", recomputed.p_range.1)?;
+    dict.set_item("Statistic_Consistent", comparison.statistic)?;
+    dict.set_item("P_Consistent", comparison.p)?;
+    dict.set_item("Consistent", consistent)?;
+    dict.set_item("Decision_Error", comparison.decision_error)?;
+    Ok(dict)
+}
+
+pub(crate) fn check_alpha(alpha: f64) -> Result<(), StatcheckError> {
+    if alpha > 0.0 && alpha < 1.0 {
+        Ok(())
+    } else {
+        Err(StatcheckError::InvalidAlpha(alpha))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (x1, sd1, n1, x2, sd2, n2, t_reported=None, p_reported=None, welch=false, alpha=0.05, rounding="up_or_down", threshold=5.0))]
+/// Recomputes an independent-samples t-test from reported means, standard deviations and group
+/// sizes, and checks the reported t and p-value against it.
+///
+/// Parameters:
+///     x1 (str | float): The reported mean of group 1. Give reported values as strings, e.g.
+///     "5.20", to keep trailing zeros; numbers are taken to the precision they print with.
+///     sd1 (str | float): The reported standard deviation of group 1.
+///     n1 (int): The size of group 1.
+///     x2 (str | float): The reported mean of group 2.
+///     sd2 (str | float): The reported standard deviation of group 2.
+///     n2 (int): The size of group 2.
+///     t_reported (str | float, optional): The reported t statistic, compared as an absolute
+///     value.
+///     p_reported (str | float, optional): The reported two-sided p-value. Strings starting with
+///     "<", e.g. "<.001", match any smaller p-value, and those starting with ">" any larger one.
+///     welch (bool): Whether the test is Welch's t-test rather than Student's. Defaults to False.
+///     alpha (float): The significance level for decision errors. Defaults to 0.05.
+///     rounding (str): The rounding scheme of all reported values, one of "up_or_down", "up",
+///     "down", "even", "ceiling", "floor", "trunc" and "anti_trunc". Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///
+/// Returns:
+///     dict: Statistic, the |t| of the reported values, with Statistic_Min and Statistic_Max, its
+///     range over the rounding of the inputs; DF, the degrees of freedom; P, P_Min and P_Max, the
+///     two-sided p-value and its range; Statistic_Consistent and P_Consistent, whether the
+///     reported values could round from these ranges, or None if not reported; Consistent, if
+///     both could; and Decision_Error, True if the reported p-value is inconsistent and no
+///     possible p-value agrees with it on significance at alpha.
+///
+/// Raises:
+///     ValueError: If a group has fewer than 2 observations, alpha is not between 0 and 1, a
+///     reported value is not a number, or the rounding scheme is invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import t_test_check
+///     >>> res = t_test_check("5.25", "1.10", 20, "4.60", "1.30", 20, "1.71", ".10")
+///     >>> res["Statistic_Consistent"], res["P_Consistent"], res["Decision_Error"]
+///     (True, True, False)
+///
+/// Notes:
+///     - With welch=True, the range of p-values uses the exact range of Welch's degrees of
+///     freedom over the rounding intervals of the standard deviations. This reaches n1 + n2 - 2
+///     whenever the standard errors of the two groups could be in the ratio of n1 - 1 to n2 - 1.
+pub fn t_test_check(
+    py: Python,
+    x1: RIVETSInput,
+    sd1: RIVETSInput,
+    n1: u32,
+    x2: RIVETSInput,
+    sd2: RIVETSInput,
+    n2: u32,
+    t_reported: Option<RIVETSInput>,
+    p_reported: Option<ReportedMetric>,
+    welch: bool,
+    alpha: f64,
+    rounding: &str,
+    threshold: f64,
+) -> PyResult<PyObject> {
+    if n1 < 2 || n2 < 2 {
+        return Err(StatcheckError::TooFewObservations(format!(
+            "Each group needs at least 2 observations, but got n1 = {n1} and n2 = {n2}"
+        )).into());
+    }
+    check_alpha(alpha)?;
+    let (x1, x2) = (Rounded::new(&x1, rounding, threshold)?, Rounded::new(&x2, rounding, threshold)?);
+    let (sd1, sd2) = (Rounded::sd(&sd1, rounding, threshold)?, Rounded::sd(&sd2, rounding, threshold)?);
+
+    let (recomputed, df) = t_independent(x1, sd1, n1 as f64, x2, sd2, n2 as f64, welch);
+    let comparison = compare(&recomputed, t_reported.as_ref(), p_reported.as_ref(), alpha, rounding, threshold)?;
+
+    let dict = to_dict(py, &recomputed, &comparison)?;
+    dict.set_item("DF", df)?;
+    Ok(dict.into())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (mean_diff, sd_diff, n, t_reported=None, p_reported=None, alpha=0.05, rounding="up_or_down", threshold=5.0))]
+/// Recomputes a paired t-test from the reported mean and standard deviation of the differences,
+/// and checks the reported t and p-value against it.
+///
+/// Parameters:
+///     mean_diff (str | float): The reported mean of the paired differences.
+///     sd_diff (str | float): The reported standard deviation of the paired differences.
+///     n (int): The number of pairs.
+///     t_reported (str | float, optional): The reported t statistic, compared as an absolute
+///     value.
+///     p_reported (str | float, optional): The reported two-sided p-value, as in
+///     `t_test_check()`.
+///     alpha (float): The significance level for decision errors. Defaults to 0.05.
+///     rounding (str): The rounding scheme of all reported values. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///
+/// Returns:
+///     dict: The same fields as `t_test_check()`, with n - 1 degrees of freedom.
+///
+/// Raises:
+///     ValueError: If n is below 2, alpha is not between 0 and 1, a reported value is not a
+///     number, or the rounding scheme is invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import paired_t_check
+///     >>> res = paired_t_check("0.50", "1.00", 25, "2.50", ".06")
+///     >>> res["Statistic_Consistent"], res["Decision_Error"]
+///     (True, True)
+pub fn paired_t_check(
+    py: Python,
+    mean_diff: RIVETSInput,
+    sd_diff: RIVETSInput,
+    n: u32,
+    t_reported: Option<RIVETSInput>,
+    p_reported: Option<ReportedMetric>,
+    alpha: f64,
+    rounding: &str,
+    threshold: f64,
+) -> PyResult<PyObject> {
+    if n < 2 {
+        return Err(StatcheckError::TooFewObservations(format!(
+            "A paired t-test needs at least 2 pairs, but got n = {n}"
+        )).into());
+    }
+    check_alpha(alpha)?;
+    let mean_diff = Rounded::new(&mean_diff, rounding, threshold)?;
+    let sd_diff = Rounded::sd(&sd_diff, rounding, threshold)?;
+
+    let recomputed = t_paired(mean_diff, sd_diff, n as f64);
+    let comparison = compare(&recomputed, t_reported.as_ref(), p_reported.as_ref(), alpha, rounding, threshold)?;
+
+    let dict = to_dict(py, &recomputed, &comparison)?;
+    dict.set_item("DF", n - 1)?;
+    Ok(dict.into())
+}
+
+#[allow(clippy::too_many_arguments)]
+#[pyfunction(signature = (means, sds, ns, f_reported=None, p_reported=None, alpha=0.05, rounding="up_or_down", threshold=5.0))]
+/// Recomputes a one-way ANOVA from reported group means, standard deviations and sizes, and
+/// checks the reported F and p-value against it.
+///
+/// Parameters:
+///     means (List[str | float]): The reported mean of each group.
+///     sds (List[str | float]): The reported standard deviation of each group.
+///     ns (List[int]): The size of each group.
+///     f_reported (str | float, optional): The reported F statistic.
+///     p_reported (str | float, optional): The reported p-value, as in `t_test_check()`.
+///     alpha (float): The significance level for decision errors. Defaults to 0.05.
+///     rounding (str): The rounding scheme of all reported values. Defaults to "up_or_down".
+///     threshold (float): The threshold for the rounding schemes which need one. Defaults to 5.0.
+///
+/// Returns:
+///     dict: The same fields as `t_test_check()` for F, with its degrees of freedom in DF1, the
+///     number of groups less 1, and DF2, the total size less the number of groups.
+///
+/// Raises:
+///     ValueError: If the lists differ in length, there are fewer than 2 or more than 16 groups,
+///     any group is empty, there are no more observations than groups, alpha is not between 0
+///     and 1, a reported value is not a number, or the rounding scheme is invalid.
+///
+/// Usage Example:
+///     >>> from scrutipy import anova_check
+///     >>> res = anova_check(["1.0", "2.0", "3.0"], ["1.0", "1.0", "1.0"], [5, 5, 5], "5.00", ".026")
+///     >>> res["Statistic"], res["Consistent"]
+///     (5.0, True)
+pub fn anova_check(
+    py: Python,
+    means: Vec<RIVETSInput>,
+    sds: Vec<RIVETSInput>,
+    ns: Vec<u32>,
+    f_reported: Option<RIVETSInput>,
+    p_reported: Option<ReportedMetric>,
+    alpha: f64,
+    rounding: &str,
+    threshold: f64,
+) -> PyResult<PyObject> {
+    if means.len() != sds.len() || means.len() != ns.len() {
+        return Err(StatcheckError::LengthMismatch(means.len(), sds.len(), ns.len()).into());
+    }
+    if ns.len() < 2 {
+        return Err(StatcheckError::TooFewGroups(ns.len()).into());
+    }
+    if ns.len() > MAX_ROUNDED_GROUPS {
+        return Err(StatcheckError::TooManyGroups(ns.len(), MAX_ROUNDED_GROUPS).into());
+    }
+    let total: u32 = ns.iter().sum();
+    if ns.contains(&0) || total as usize <= ns.len() {
+        return Err(StatcheckError::TooFewObservations(format!(
+            "Every group needs an observation, with more observations than groups, but got ns = {ns:?}"
+        )).into());
+    }
+    check_alpha(alpha)?;
+    let means = means
+        .iter()
+        .map(|mean| Rounded::new(mean, rounding, threshold))
+        .collect::<Result<Vec<_>, _>>()?;
+    let sds = sds
+        .iter()
+        .map(|sd| Rounded::sd(sd, rounding, threshold))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ns: Vec<f64> = ns.iter().map(|&n| n as f64).collect();
+
+    let (recomputed, df1, df2) = anova_f(&means, &sds, &ns);
+    let comparison = compare(&recomputed, f_reported.as_ref(), p_reported.as_ref(), alpha, rounding, threshold)?;
+
+    let dict = to_dict(py, &recomputed, &comparison)?;
+    dict.set_item("DF1", df1)?;
+    dict.set_item("DF2", df2)?;
+    Ok(dict.into())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn rounded(x: &str) -> Rounded {
+        Rounded::new(&RIVETSInput::Str(x.to_string()), "up_or_down", 5.0).unwrap()
+    }
+
+    fn reported(x: &str) -> RIVETSInput {
+        RIVETSInput::Str(x.to_string())
+    }
+
+    #[test]
+    fn p_values() {
+        // the critical values of t(10) and F(2, 12) at 0.05
+        assert!((t_p(2.228138851986, 10.0) - 0.05).abs() < 1e-10);
+        assert!((f_p(3.885293834652, 2.0, 12.0) - 0.05).abs() < 1e-10);
+        // with 2 numerator degrees of freedom, p = (1 + 2F / df2)^(-df2 / 2)
+        assert!((f_p(5.0, 2.0, 12.0) - (1.0f64 + 10.0 / 12.0).powf(-6.0)).abs() < 1e-12);
+        assert_eq!(t_p(0.0, 10.0), 1.0);
+        // a, b = 0.1, 0.4, so df = 0.25 / (0.17 / 9)
+        assert!((welch_df(1.0, 10.0, 2.0, 10.0) - 0.25 / (0.17 / 9.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn independent_t() {
+        let (recomputed, df) = t_independent(rounded("5.25"), rounded("1.10"), 20.0, rounded("4.60"), rounded("1.30"), 20.0, false);
+        assert_eq!(df, 38.0);
+        assert!((recomputed.statistic - 0.65 / 0.145f64.sqrt()).abs() < 1e-12);
+        let (t_min, t_max) = recomputed.statistic_range;
+        assert!(t_min < recomputed.statistic && recomputed.statistic < t_max);
+        assert!((t_min - 0.64 / ((1.105f64.powi(2) + 1.305f64.powi(2)) / 20.0).sqrt()).abs() < 1e-12);
+        assert!(recomputed.p_range.0 < recomputed.p && recomputed.p < recomputed.p_range.1);
+        let compare_p = |p: &str| compare(&recomputed, Some(&reported("1.71")), Some(&ReportedMetric::Str(p.to_string())), 0.05, "up_or_down", 5.0).unwrap();
+        assert_eq!(compare_p(".10"), Comparison { statistic: Some(true), p: Some(true), decision_error: Some(false) });
+        assert_eq!(compare_p(".04").decision_error, Some(true));
+
+        // with means which could be equal, |t| can be 0
+        let (recomputed, _) = t_independent(rounded("5.0"), rounded("1.0"), 10.0, rounded("5.1"), rounded("1.0"), 10.0, true);
+        assert_eq!(recomputed.statistic_range.0, 0.0);
+        assert_eq!(recomputed.p_range.1, 1.0);
+    }
+
+    #[test]
+    fn welch_df_peak() {
+        // with equal n, Welch's df peak at equal SDs, which lie inside the rounding intervals but
+        // at no corner of them
+        let (sd1, sd2) = (rounded("1.0"), rounded("1.05"));
+        let corners = sd1.corners().into_iter().flat_map(|s1| sd2.corners().map(move |s2| welch_df(s1, 20.0, s2, 20.0)));
+        let corner_max = min_max(corners).1;
+        assert!(corner_max < 38.0);
+
+        let (recomputed, _) = t_independent(rounded("5.25"), sd1, 20.0, rounded("4.60"), sd2, 20.0, true);
+        let t_max = recomputed.statistic_range.1;
+        assert!((recomputed.p_range.0 - t_p(t_max, 38.0)).abs() < 1e-15);
+        assert!(recomputed.p_range.0 < t_p(t_max, corner_max));
+
+        // far apart SDs keep the ratio away from the peak, so the corners bound it
+        let (recomputed, _) = t_independent(rounded("5.25"), rounded("1.0"), 20.0, rounded("4.60"), rounded("2.0"), 20.0, true);
+        let df_max = welch_df(1.05, 20.0, 1.95, 20.0);
+        assert!(df_max < 38.0);
+        assert!((recomputed.p_range.0 - t_p(recomputed.statistic_range.1, df_max)).abs() < 1e-15);
+    }
+
+    #[test]
+    fn paired_t() {
+        let recomputed = t_paired(rounded("0.50"), rounded("1.00"), 25.0);
+        assert_eq!(recomputed.statistic, 2.5);
+        assert!((recomputed.statistic_range.0 - 0.495 / (1.005 / 5.0)).abs() < 1e-12);
+        assert!((recomputed.statistic_range.1 - 0.505 / (0.995 / 5.0)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn anova() {
+        let means = [rounded("1.0"), rounded("2.0"), rounded("3.0")];
+        let sds = [rounded("1.0"), rounded("1.0"), rounded("1.0")];
+        let ns = [5.0, 5.0, 5.0];
+        // SSB = 5 * (1 + 0 + 1) = 10 on 2 df, and SSW = 3 * 4 = 12 on 12 df
+        let (recomputed, df1, df2) = anova_f(&means, &sds, &ns);
+        assert_eq!((df1, df2), (2.0, 12.0));
+        assert!((recomputed.statistic - 5.0).abs() < 1e-12);
+        // the means move to 1.05, 2.0 and 2.95 at least, and to 0.95, 1.95 or 2.05 and 3.05 at
+        // most, with SSB = 5 * (0.95^2 + 1.95^2 + 3.05^2 - 5.95^2 / 3)
+        assert!((min_between_ss(&means, &ns) - 5.0 * 2.0 * 0.95f64.powi(2)).abs() < 1e-9);
+        let widest = 0.95f64.powi(2) + 1.95f64.powi(2) + 3.05f64.powi(2) - 5.95f64.powi(2) / 3.0;
+        assert!((max_between_ss(&means, &ns) - 5.0 * widest).abs() < 1e-9);
+
+        // overlapping intervals allow equal means
+        let close = [rounded("1.0"), rounded("1.1")];
+        assert!(min_between_ss(&close, &[5.0, 5.0]) < 1e-12);
+    }
+
+    #[test]
+    fn comparisons() {
+        let recomputed = t_paired(rounded("0.50"), rounded("1.00"), 25.0);
+        let compare_p = |p: &str| compare(&recomputed, Some(&reported("-2.50")), Some(&ReportedMetric::Str(p.to_string())), 0.05, "up_or_down", 5.0).unwrap();
+
+        assert_eq!(compare_p(".02"), Comparison { statistic: Some(true), p: Some(true), decision_error: Some(false) });
+        assert_eq!(compare_p("<.05").p, Some(true));
+        // inconsistent, but significant either way
+        assert_eq!(compare_p(".03").decision_error, Some(false));
+        assert_eq!(compare_p(".03").p, Some(false));
+        // inconsistent, and not significant
+        assert_eq!(compare_p(".06").decision_error, Some(true));
+
+        let none = compare(&recomputed, None, None, 0.05, "up_or_down", 5.0).unwrap();
+        assert_eq!(none, Comparison { statistic: None, p: None, decision_error: None });
+        assert!(compare(&recomputed, Some(&reported("2.50")), None, 0.05, "up_from", 5.0).is_err());
+    }
+}
@@ -255,4 +255,32 @@ mod tests {
 
         assert_eq!(val, vec![2.988, 2.988, 8.790, 8.790, 6.988, 6.988])
     }
+
+    #[test]
+    fn restore_zeros_vec_test_1() {
+        let xs: Vec<String> = ["5.3", "4.25", "6", "-0.5"].iter().map(|x| x.to_string()).collect();
+        assert_eq!(restore_zeros_vec(&xs, None), vec!["5.30", "4.25", "6.00", "-0.50"]);
+    }
+
+    #[test]
+    fn restore_zeros_vec_test_2() {
+        // a given width never truncates, and anything which isn't a plain number is left alone
+        let xs: Vec<String> = ["5.3", "4.255", "null", "1e-3", ""].iter().map(|x| x.to_string()).collect();
+        assert_eq!(restore_zeros_vec(&xs, Some(2)), vec!["5.30", "4.255", "null", "1e-3", ""]);
+        assert_eq!(restore_zeros_vec(&xs, None), vec!["5.300", "4.255", "null", "1e-3", ""]);
+    }
+
+    #[test]
+    fn stream_rng_test_1() {
+        use rand::Rng;
+        let draws = |seed: u64, stream: u64| {
+            let mut rng = stream_rng(seed, stream);
+            (0..8).map(|_| rng.random::<u64>()).collect::<Vec<u64>>()
+        };
+        assert_eq!(draws(42, 3), draws(42, 3));
+        // seed + stream does not pick the generator, so seed s at stream i + 1 differs from
+        // seed s + 1 at stream i
+        assert_ne!(draws(42, 4), draws(43, 3));
+        assert_ne!(draws(42, 3), draws(42, 4));
+    }
 }
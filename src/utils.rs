@@ -5,6 +5,8 @@ use pyo3::{PyErr, Python,
     types::{PyAnyMethods, PyString}};
 use thiserror::Error;
 use num::NumCast;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 
 use crate::rounding::*;
 use crate::sd_binary::*;
@@ -49,6 +51,18 @@ pub fn decimal_places_scalar(x: Option<&str>, sep: &str) -> Option<i32> {
     caps.get(1).map(|c| c.as_str().len() as i32)
 }
 
+/// Returns the random number generator for the `stream`-th of the streams seeded with `seed`,
+/// such as one per iteration of a search.
+///
+/// ChaCha20 keys its output by the seed and the stream separately, so no stream of one seed
+/// replays a stream of another, as seeding with `seed + stream` would. Its output is also fixed
+/// across rand releases, unlike that of `StdRng`, so seeded results stay reproducible.
+pub fn stream_rng(seed: u64, stream: u64) -> ChaCha20Rng {
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    rng.set_stream(stream);
+    rng
+}
+
 #[derive(Debug, Error)]
 pub enum ReconstructSdError {
     #[error("{0} is not a number")]
@@ -190,7 +204,8 @@ pub fn process_series_to_string(
     py: Python, 
     series: Series, 
     silence_numeric_warning: bool, 
-    input_type: InputType
+    input_type: InputType,
+    restore_zeros: bool,
 ) -> Result<Vec<String>, PyErr> {
     let warnings = py.import("warnings").unwrap();
 
@@ -210,7 +225,8 @@ pub fn process_series_to_string(
             // DataType::Unknown(UnknownKind::Int(_)) and DataType::Unknown(UnknownKind::Float)
             // these types should only come up when dealing directly with the Arrow FFI, so we
             // don't handle them differently here
-            if !silence_numeric_warning {
+            // restoring the zeros answers the warning
+            if !silence_numeric_warning && !restore_zeros {
                 warnings
                     .call_method1(
                         "warn",
@@ -235,9 +251,46 @@ pub fn process_series_to_string(
         Err(_) => return Err(PyTypeError::new_err(format!("The column `{col_name}` is composed of neither strings nor numeric types. Please check the input types and the documentation."))),
     };
 
+    if restore_zeros {
+        return Ok(restore_zeros_vec(&series_vec, None));
+    }
     Ok(series_vec)
 }
 
+/// Pads each number in `xs` with trailing zeros to `width` decimal places, or if `width` is None,
+/// to the most decimal places of any of them. Numbers which already have as many decimal places,
+/// and anything which isn't written as a plain decimal number, are left as they are.
+/// ```
+/// let xs = vec!["5.3".to_string(), "4.25".to_string(), "6".to_string()];
+/// assert_eq!(restore_zeros_vec(&xs, None), vec!["5.30", "4.25", "6.00"]);
+/// ```
+pub fn restore_zeros_vec(xs: &[String], width: Option<usize>) -> Vec<String> {
+    let is_plain = |x: &str| {
+        let digits = x.strip_prefix('-').unwrap_or(x);
+        digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && digits.chars().filter(|&c| c == '.').count() <= 1
+            && digits.chars().any(|c| c.is_ascii_digit())
+    };
+    let decimals = |x: &str| decimal_places_scalar(Some(x), ".").unwrap_or(0) as usize;
+
+    let width = width.unwrap_or_else(|| {
+        xs.iter().filter(|x| is_plain(x)).map(|x| decimals(x)).max().unwrap_or(0)
+    });
+
+    xs.iter()
+        .map(|x| {
+            let missing = width.saturating_sub(decimals(x));
+            if !is_plain(x) || missing == 0 {
+                x.clone()
+            } else if x.contains('.') {
+                format!("{x}{}", "0".repeat(missing))
+            } else {
+                format!("{x}.{}", "0".repeat(missing))
+            }
+        })
+        .collect()
+}
+
 /// TODO
 pub fn process_series_to_num(ns: Series) -> Result<Vec<Result<u32, NsParsingError>>, PyErr> {
     let ns_result = match ns.dtype() {
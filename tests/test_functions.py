@@ -6,10 +6,28 @@ import polars as pl
 from scrutipy import grim_map
 import pytest
 from scrutipy import closure
+from scrutipy import closure_sample
+from scrutipy import closure_estimate
+from scrutipy import sprite
+from scrutipy import calculate_snspn
+from scrutipy import reconstruct_confusion
+from scrutipy import calculate_metrics_from_counts
+from scrutipy import check_confusion_ci
+from scrutipy import calculate_kappa, kappa_from_counts
 from scrutipy import grimmer
 from scrutipy import debit
 from scrutipy import debit_map_pl
 from scrutipy import debit_map
+from scrutipy import rivets
+from scrutipy import set_progress, get_progress
+from scrutipy import u_distribution, u_pvalue, grim_u_check, auc_check
+from scrutipy import simrank, simrank_tied, simrank_all, simrank_count
+from scrutipy import simrank_ties, u_variance
+from scrutipy import w_distribution, w_pvalue, grim_w_check
+from scrutipy import h_distribution, h_pvalue, grim_h_check
+from scrutipy import t_test_check, paired_t_check, anova_check
+from scrutipy import parse_apa, apa_check
+from scrutipy import split_mean_sd, split_by_parens, restore_zeros
 
 def test_grim_1():
     result = grim_scalar("5.19", 40)
@@ -165,6 +183,207 @@ def test_closure_568():
 def test_closure_empty():
     assert not closure(10.0, 2.0, 3, 1, 5, 0.1, 0.1)
 
+def test_closure_items():
+    res = sorted(closure(2.25, 0.87, 4, 1, 4, 0.005, 0.005, items = 2))
+    assert res == [[1.0, 2.5, 2.5, 3.0], [1.5, 1.5, 3.0, 3.0], [1.5, 2.0, 2.0, 3.5]]
+
+def test_closure_items_invalid(tmp_path):
+    with pytest.raises(ValueError, match="items must be at least 1"):
+        closure(2.25, 0.87, 4, 1, 4, items = 0)
+    with pytest.raises(ValueError, match="items must be at least 1"):
+        closure(2.25, 0.87, 4, 1, 4, items = -1, checkpoint = str(tmp_path / "closure.csv"))
+    with pytest.raises(ValueError, match="items must be at least 1"):
+        closure_estimate(2.25, 0.87, 4, 1, 4, items = 0)
+    with pytest.raises(ValueError, match="items must be at least 1"):
+        closure_sample(2.25, 0.87, 4, 1, 4, items = -2)
+
+def test_closure_sample_1():
+    samples = closure_sample(3.5, 0.57, 100, 0, 7, 0.05, 0.05, k = 10, seed = 1)
+    full = closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05)
+    assert len(samples) == 10
+    assert all(s in full for s in samples)
+
+def test_closure_estimate_feasible():
+    est = closure_estimate(3.5, 1.2, 50, 0, 7, 0.05, 0.005, seed = 1)
+    assert est.feasible
+    assert est.nodes > 0
+
+def test_closure_estimate_sd_too_large():
+    est = closure_estimate(3.5, 4.0, 50, 0, 7)
+    assert not est.feasible
+    assert "standard deviation" in est.reason
+
+def test_closure_budget_error():
+    with pytest.raises(ValueError):
+        closure(3.5, 1.2, 50, 0, 7, 0.05, 0.005, budget = 0.0, on_budget = "error")
+
+def test_closure_budget_warn():
+    with pytest.warns(UserWarning):
+        res = closure(3.5, 1.2, 50, 0, 7, 0.05, 0.005, budget = 0.0)
+    assert len(res) == 7980
+
+def test_closure_shards():
+    shards = [closure(3.5, 1.2, 50, 0, 7, 0.05, 0.005, shard_index = i, num_shards = 3) for i in range(3)]
+    assert sum(len(s) for s in shards) == 7980
+
+def test_closure_invalid_shard():
+    with pytest.raises(ValueError, match = "shard_index must be less than num_shards"):
+        closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05, shard_index = 3)
+    # also when the problem has no solutions
+    with pytest.raises(ValueError, match = "shard_index must be less than num_shards"):
+        closure(10.0, 2.0, 3, 1, 5, 0.1, 0.1, shard_index = 2, num_shards = 2)
+
+def test_closure_checkpoint_resume(tmp_path):
+    path = str(tmp_path / "closure.csv")
+    first = closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05, checkpoint = path)
+    with pytest.raises(ValueError):
+        closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05, checkpoint = path)
+    resumed = closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05, checkpoint = path, resume = True)
+    assert resumed == first
+
+def test_closure_sample_seed():
+    a = closure_sample(3.5, 0.57, 100, 0, 7, 0.05, 0.05, k = 5, seed = 3)
+    b = closure_sample(3.5, 0.57, 100, 0, 7, 0.05, 0.05, k = 5, seed = 3)
+    assert a == b
+
+def test_sprite_1():
+    res = sprite(2.2, 1.3, 20, 1, 5, n_distributions = 5, seed = 1)
+    assert len(res) == 5
+    for dataset in res:
+        assert len(dataset) == 20
+        assert abs(sum(dataset) / 20 - 2.2) <= 0.05 + 1e-9
+
+def test_sprite_restrictions():
+    res = sprite(3.0, 1.5, 30, 1, 7, restrictions_exact = {1: 4, 7: 0}, seed = 3)
+    assert len(res) > 0
+    for dataset in res:
+        assert dataset.count(1) == 4
+        assert 7 not in dataset
+
+def test_sprite_minimum():
+    res = sprite(3.0, 1.5, 30, 1, 7, restrictions_minimum = {5: 6}, seed = 2)
+    assert len(res) > 0
+    for dataset in res:
+        assert dataset.count(5) >= 6
+
+def test_sprite_invalid_size():
+    with pytest.raises(ValueError, match = "n must be at least 1"):
+        sprite(2.2, 1.3, 0, 1, 5)
+    with pytest.raises(ValueError, match = "items must be at least 1"):
+        sprite(2.2, 1.3, 20, 1, 5, items = 0)
+    with pytest.raises(ValueError, match = "items must be at least 1"):
+        sprite(2.2, 1.3, 20, 1, 5, items = -1)
+
+def test_sprite_unreachable():
+    with pytest.raises(ValueError, match = "cannot be reached"):
+        sprite(3.0, 2.0, 20, 1, 7, restrictions_minimum = {7: 10})
+
+def test_snspn_rounding():
+    res = calculate_snspn("0.71", "0.75", 11, top_n = 3)
+    assert res[0]["Exact_Match"]
+    assert (res[0]["TP"], res[0]["TN"], res[0]["FP"], res[0]["FN"]) == (5, 3, 1, 2)
+
+def test_snspn_rounding_impossible():
+    res = calculate_snspn("0.71", "0.75", 5, top_n = 3)
+    assert not any(r["Exact_Match"] for r in res)
+
+def test_reconstruct_confusion():
+    res = reconstruct_confusion({"sensitivity": "0.71", "specificity": "0.75", "prevalence": "0.64"}, 11, exact_only = True)
+    assert [(r["TP"], r["TN"], r["FP"], r["FN"]) for r in res] == [(5, 3, 1, 2)]
+    assert "Calculated_Prevalence" in res[0]
+
+def test_snspn_output_frames():
+    dicts = calculate_snspn(0.8, 0.70588, 20, top_n = 5)
+    pl_df = calculate_snspn(0.8, 0.70588, 20, top_n = 5, output = "polars")
+    pd_df = calculate_snspn(0.8, 0.70588, 20, top_n = 5, output = "pandas")
+    assert isinstance(pl_df, pl.DataFrame)
+    assert isinstance(pd_df, pd.DataFrame)
+    assert pl_df.columns == list(dicts[0].keys())
+    assert list(pd_df.columns) == list(dicts[0].keys())
+    assert pl_df["TP"].to_list() == [d["TP"] for d in dicts]
+    assert pd_df["Total_Error"].tolist() == [d["Total_Error"] for d in dicts]
+
+def test_reconstruct_confusion_polars():
+    res = reconstruct_confusion({"sensitivity": "0.71", "specificity": "0.75", "prevalence": "0.64"}, 11, exact_only = True, output = "polars")
+    assert res.select(["TP", "TN", "FP", "FN"]).rows() == [(5, 3, 1, 2)]
+
+def test_snspn_output_invalid():
+    with pytest.raises(ValueError):
+        calculate_snspn(0.8, 0.70588, 20, output = "arrow")
+
+def test_reconstruct_confusion_unknown_metric():
+    with pytest.raises(ValueError):
+        reconstruct_confusion({"auc": 0.5}, 11)
+
+def test_reconstruct_confusion_duplicate_metric():
+    with pytest.raises(ValueError):
+        reconstruct_confusion({"sens": "0.80", "sensitivity": "0.80"}, 11)
+    with pytest.raises(ValueError):
+        reconstruct_confusion({"sensitivity": "0.80"}, 11, cis = {"sens": ("0.49", "0.94"), "Sensitivity": ("0.49", "0.94")})
+
+def test_metrics_from_counts_ci():
+    res = calculate_metrics_from_counts(34, 88, 94, 234)
+    assert abs(res["Sensitivity_CI_Lower"] - 0.0922245) < 1e-6
+    assert abs(res["+LR_CI_Upper"] - 0.3465227) < 1e-6
+    assert "F1_CI_Lower" not in res
+
+def test_metrics_from_counts_undefined():
+    res = calculate_metrics_from_counts(0, 5, 0, 0)
+    assert res["Sensitivity"] is None
+    assert res["Sensitivity_CI_Lower"] is None
+    assert res["Specificity"] == 1.0
+
+def test_check_confusion_ci():
+    assert check_confusion_ci(8, 7, 3, 2, {"sensitivity": ("0.49", "0.94")}) == {"sensitivity": True}
+    assert check_confusion_ci(16, 7, 3, 4, {"sensitivity": ("0.49", "0.94")}) == {"sensitivity": False}
+
+def test_reconstruct_confusion_cis():
+    res = reconstruct_confusion({"sensitivity": "0.80"}, 30, exact_only = True, cis = {"sensitivity": ("0.49", "0.94")})
+    assert len(res) > 0
+    assert all((r["TP"], r["FN"]) == (8, 2) for r in res)
+
+def test_progress_modes():
+    assert get_progress() == "auto"
+    set_progress("never")
+    try:
+        assert get_progress() == "never"
+        res = calculate_snspn(0.8, 0.7, 20, top_n = 1)
+        assert len(res) == 1
+    finally:
+        set_progress("auto")
+
+def test_progress_argument(capfd):
+    calculate_snspn(0.8, 0.7, 20, top_n = 1, progress = "never")
+    assert capfd.readouterr().err == ""
+    calculate_snspn(0.8, 0.7, 20, top_n = 1, progress = "always")
+    assert capfd.readouterr().err != ""
+
+def test_progress_invalid():
+    with pytest.raises(ValueError):
+        set_progress("sometimes")
+    with pytest.raises(ValueError):
+        closure(3.5, 0.57, 100, 0, 7, 0.05, 0.05, progress = "sometimes")
+
+def test_calculate_kappa():
+    res = calculate_kappa("0.40", "0.70", 20, exact_only = True)
+    assert len(res) == 13
+    assert all(r["Both_Yes"] + r["Both_No"] == 14 for r in res)
+    assert "Calculated_Kappa" in res[0]
+    assert calculate_kappa("0.47", "0.70", 20, exact_only = True) == []
+
+def test_calculate_kappa_numbers():
+    res = calculate_kappa(0.4, 0.7, 50, top_n = 1)
+    assert res[0]["Exact_Match"]
+    assert abs(res[0]["Calculated_Kappa"] - 0.4) < 1e-9
+
+def test_kappa_from_counts():
+    res = kappa_from_counts(20, 15, 5, 10)
+    assert abs(res["Kappa"] - 0.4) < 1e-12
+    assert res["Agreement"] == 0.7
+    assert res["Expected_Agreement"] == 0.5
+    assert res["Kappa_CI_Lower"] < 0.4 < res["Kappa_CI_Upper"]
+    assert kappa_from_counts(10, 0, 0, 0)["Kappa"] is None
+
 def test_grimmer_1():
     b = grimmer(["1.03"],
             ["0.41"],
@@ -200,3 +419,252 @@ def test_debit_map_pd():
     assert bools == list([True, True, True, False, True, True, True])
     assert errors == None
 
+
+def test_u_distribution():
+    dist = u_distribution(2, 2)
+    assert [round(p * 6) for p in dist] == [1, 1, 2, 1, 1]
+    assert abs(sum(u_distribution(7, 9)) - 1) < 1e-12
+
+def test_u_pvalue():
+    assert abs(u_pvalue(5, 5, 2) - 8 / 252) < 1e-15
+    assert abs(u_pvalue(5, 5, 2, alternative = "less") - 4 / 252) < 1e-15
+    assert abs(u_pvalue(10, 10, 20, method = "normal") - 0.025748080821108) < 1e-10
+    assert abs(u_pvalue(10, 10, 20, method = "normal", continuity = False) - 0.023342202012891) < 1e-10
+
+def test_u_pvalue_invalid():
+    with pytest.raises(ValueError):
+        u_pvalue(5, 5, 26)
+    with pytest.raises(ValueError):
+        u_pvalue(5, 5, 2, method = "bootstrap")
+
+def test_grim_u_check():
+    res = grim_u_check(5, 5, 2, ".032")
+    assert res["Exact_Consistent"]
+    assert not res["Normal_Consistent"]
+    assert res["Consistent"]
+    assert res["Achievable"]
+    assert not grim_u_check(5, 5, 2, ".033")["Achievable"]
+    assert grim_u_check(5, 5, 2, "<.05")["Exact_Consistent"]
+
+def test_simrank_all():
+    res = simrank_all(5, 5, 2)
+    assert [r.n1 for r in res] == [[1, 2, 3, 4, 7], [1, 2, 3, 5, 6]]
+    assert res[1].n2 == [4, 7, 8, 9, 10]
+    assert simrank_count(5, 5, 2) == 2
+    assert sum(simrank_count(6, 7, u) for u in range(43)) == 1716
+
+def test_simrank_all_limit():
+    with pytest.raises(ValueError):
+        simrank_all(10, 10, 50, max_results = 100)
+
+def test_simrank_invalid_u():
+    with pytest.raises(ValueError):
+        simrank(5, 5, 26)
+    with pytest.raises(ValueError):
+        simrank(5, 5, 7.5)
+    with pytest.raises(ValueError):
+        simrank_tied(5, 5, 7)
+    with pytest.raises(ValueError):
+        simrank_count(5, 5, -1)
+
+def test_simrank_seeded():
+    first = [r.n1 for r in simrank(10, 12, 40, length = 20, seed = 42)]
+    second = [r.n1 for r in simrank(10, 12, 40, length = 20, seed = 42)]
+    assert len(first) == 20
+    assert first == second
+    tied = [r.n1 for r in simrank_tied(6, 7, 20.5, length = 5, seed = 7)]
+    assert tied == [r.n1 for r in simrank_tied(6, 7, 20.5, length = 5, seed = 7)]
+
+def test_simrank_ties():
+    res = simrank_ties(6, 7, 20.5, ties = [2, 3], length = 5, seed = 3)
+    assert len(res) == 5
+    for r in res:
+        assert sorted(r.tie_sizes()) == [2, 3]
+        assert abs(r.u_variance() - u_variance(6, 7, [2, 3])) < 1e-12
+    random_ties = simrank_ties(5, 5, 12, max_ties = 3, length = 5, seed = 11)
+    assert all(1 <= sum(t - 1 for t in r.tie_sizes()) <= 3 for r in random_ties)
+    with pytest.raises(ValueError):
+        simrank_ties(5, 5, 12)
+    with pytest.raises(ValueError):
+        simrank_ties(5, 5, 12, ties = [1])
+
+def test_u_variance_ties():
+    assert abs(u_variance(5, 5) - 25 * 11 / 12) < 1e-12
+    assert abs(u_variance(5, 5, [2]) - 25 / 12 * (11 - 6 / 90)) < 1e-12
+    assert u_pvalue(5, 5, 7, method = "normal", ties = [2, 3]) < u_pvalue(5, 5, 7, method = "normal")
+    with pytest.raises(ValueError):
+        u_pvalue(5, 5, 7, ties = [2])
+
+def test_w_pvalue():
+    assert w_distribution(2) == [0.25, 0.25, 0.25, 0.25]
+    assert abs(sum(w_distribution(15)) - 1) < 1e-12
+    assert w_pvalue(10, 8) == 50 / 1024
+    assert w_pvalue(10, 8, alternative = "less") == 25 / 1024
+    assert abs(w_pvalue(10, 8, method = "normal") - 0.052787001) < 1e-8
+    with pytest.raises(ValueError):
+        w_pvalue(10, 56)
+    with pytest.raises(ValueError):
+        w_pvalue(10, 8.25)
+
+def test_grim_w_check():
+    res = grim_w_check(10, 8, ".049")
+    assert res["Exact_Consistent"]
+    assert not res["Normal_Consistent"]
+    assert res["Consistent"]
+    assert not grim_w_check(10, 8, ".045")["Consistent"]
+
+def test_h_pvalue():
+    assert h_distribution([1, 1, 1]) == [(2.0, 1.0)]
+    dist = h_distribution([2, 2, 2])
+    assert abs(sum(p for _, p in dist) - 1) < 1e-12
+    assert abs(dist[-1][0] - 32 / 7) < 1e-12
+    assert abs(h_pvalue([3, 3, 3], 7.2) - 6 / 1680) < 1e-15
+    assert abs(h_pvalue([3, 3, 3], 5.991464547107979, method = "chi2") - 0.05) < 1e-10
+    with pytest.raises(ValueError):
+        h_pvalue([5], 1.0)
+    with pytest.raises(ValueError):
+        h_pvalue([40, 40, 40], 1.0)
+
+def test_grim_h_check():
+    res = grim_h_check([3, 3, 3], "7.20", ".004")
+    assert res["H_Achievable"]
+    assert res["Exact_Consistent"]
+    assert not res["Chi2_Consistent"]
+    # the largest H for three groups of 2 is 4.57
+    assert not grim_h_check([2, 2, 2], "4.80", ".05")["H_Achievable"]
+
+def test_auc_check():
+    res = auc_check("0.84", 5, 4)
+    assert not res["Consistent"]
+    assert res["AUC_Values"] == []
+    assert res["Nearest"] == [0.825, 0.85]
+    res = auc_check(0.85, 5, 4)
+    assert res["Consistent"]
+    assert res["U_Values"] == [17.0]
+    # without ties the AUC moves in steps of 0.05
+    assert auc_check("0.86", 5, 4, ties = False)["Nearest"] == [0.85, 0.9]
+    with pytest.raises(ValueError):
+        auc_check("1.2", 5, 4)
+
+def test_simrank_auc():
+    res = simrank(5, 4, 4, length = 3, seed = 1)
+    assert all(r.auc() == 0.2 for r in res)
+
+def test_rivets():
+    res = rivets("5.25", "1.10", 20, "4.60", "1.30", 20, "1.71")
+    assert res["X1_Values"] == [5.25]
+    assert res["X2_Values"] == [4.6]
+    assert res["T_Min"] < 1.705 < 1.71 < res["T_Max"] < 1.715
+    assert res["Consistent"]
+    assert not rivets("5.25", "1.10", 20, "4.60", "1.30", 20, "1.80")["Consistent"]
+    # 5.27 is GRIM-inconsistent for n = 20
+    res = rivets("5.27", "1.10", 20, "4.60", "1.30", 20, "1.71")
+    assert res["X1_Values"] == []
+    assert res["T_Min"] is None
+    assert not res["Consistent"]
+
+def test_rivets_invalid():
+    with pytest.raises(ValueError):
+        rivets("5.25", "1.10", 1, "4.60", "1.30", 20, "1.71")
+    with pytest.raises(ValueError):
+        rivets("5.25", "1.10", 20, "4.60", "1.30", 20, "1.71", rounding = "up_from")
+
+def test_t_test_check():
+    res = t_test_check("5.25", "1.10", 20, "4.60", "1.30", 20, "1.71", ".10")
+    assert res["DF"] == 38.0
+    assert res["Statistic_Min"] < 1.71 < res["Statistic_Max"]
+    assert res["Consistent"]
+    assert not res["Decision_Error"]
+    # the sign of t depends on the order of the groups
+    assert t_test_check("4.60", "1.30", 20, "5.25", "1.10", 20, "-1.71")["Statistic_Consistent"]
+    res = t_test_check("5.25", "1.10", 20, "4.60", "1.30", 20, p_reported = ".04")
+    assert res["Statistic_Consistent"] is None
+    assert res["Decision_Error"]
+    assert t_test_check("5.25", "1.10", 20, "4.60", "1.30", 20, welch = True)["DF"] < 38.0
+
+def test_paired_t_check():
+    res = paired_t_check("0.50", "1.00", 25, "2.50", ".02")
+    assert res["Statistic"] == 2.5
+    assert res["DF"] == 24
+    assert res["Consistent"]
+    res = paired_t_check("0.50", "1.00", 25, "2.50", ".06")
+    assert not res["P_Consistent"]
+    assert res["Decision_Error"]
+    # inconsistent, but significant either way
+    assert not paired_t_check("0.50", "1.00", 25, p_reported = ".03")["Decision_Error"]
+
+def test_anova_check():
+    res = anova_check(["1.0", "2.0", "3.0"], ["1.0", "1.0", "1.0"], [5, 5, 5], "5.00", ".026")
+    assert abs(res["Statistic"] - 5.0) < 1e-9
+    assert (res["DF1"], res["DF2"]) == (2, 12)
+    assert res["Consistent"]
+    assert not anova_check(["1.0", "2.0", "3.0"], ["1.0", "1.0", "1.0"], [5, 5, 5], "7.00")["Consistent"]
+
+def test_statcheck_invalid():
+    with pytest.raises(ValueError):
+        t_test_check("5.25", "1.10", 1, "4.60", "1.30", 20)
+    with pytest.raises(ValueError):
+        paired_t_check("0.50", "1.00", 25, alpha = 1.5)
+    with pytest.raises(ValueError):
+        anova_check(["1.0", "2.0"], ["1.0"], [5, 5])
+    with pytest.raises(ValueError):
+        anova_check(["1.0"], ["1.0"], [5])
+
+def test_parse_apa():
+    res = parse_apa("t(38) = 2.41, p = .021, while F(2, 57) = 3.9, p < .05 and χ²(1, N = 90) = 4.2, p = .04")
+    assert [r["Test"] for r in res] == ["t", "F", "chi2"]
+    assert (res[0]["DF1"], res[0]["DF2"], res[0]["Statistic"], res[0]["P"]) == (38.0, None, "2.41", ".021")
+    assert (res[1]["DF2"], res[1]["Comparison"]) == (57.0, "<")
+    assert res[2]["DF2"] is None
+    assert parse_apa("r(48) = -.32, p = .02")[0]["Statistic"] == "-.32"
+    assert parse_apa("F(2) = 3.9, p < .05") == []
+
+def test_apa_check():
+    res = apa_check("t(38) = 2.41, p = .021; t(38) = 1.20, p = .04; z = 1.96, p = .05")
+    assert [r["Consistent"] for r in res] == [True, False, True]
+    assert [r["Decision_Error"] for r in res] == [False, True, False]
+    assert res[0]["P_Min"] < res[0]["Computed_P"] < res[0]["P_Max"]
+    assert apa_check("F(2, 57) = 3.9, p < .05")[0]["Consistent"]
+    assert apa_check("no results here") == []
+    with pytest.raises(ValueError):
+        apa_check("t(38) = 2.41, p = .021", alpha = 0)
+
+def test_split_mean_sd():
+    means, sds, errors = split_mean_sd(pl.Series(["0.53 (0.50)", "0.44 ± 0.50", "0.77 +/- 0.42"]))
+    assert means == ["0.53", "0.44", "0.77"]
+    assert sds == ["0.50", "0.50", "0.42"]
+    assert errors is None
+    means, sds, errors = split_mean_sd(pd.Series(["5.3 (1.20)", "n/a", None]))
+    assert means == ["5.3", None, None]
+    assert errors == [1, 2]
+    with pytest.raises(TypeError):
+        split_mean_sd(pl.Series([1.0, 2.0]))
+
+def test_split_into_debit_map():
+    df = pd.DataFrame({"m_sd": ["0.53 (0.50)", "0.44 (0.50)", "0.77 (0.42)", "0.19 (0.35)"], "n": [1683] * 4})
+    df["x"], df["sd"], errors = split_mean_sd(df["m_sd"])
+    assert errors is None
+    bools, errors = debit_map(df, x_col = "x", sd_col = "sd", n_col = "n")
+    assert bools == [True, True, True, False]
+
+def test_split_by_parens():
+    assert split_by_parens(pl.Series(["5.3 (1.20)"])) == (["5.3"], ["1.20"], None)
+    assert split_by_parens(pl.Series(["5.3 [1.20]"]), sep = "brackets") == (["5.3"], ["1.20"], None)
+    assert split_by_parens(pl.Series(["5.3 ± 1.20"])) == ([None], [None], [0])
+    with pytest.raises(ValueError):
+        split_by_parens(pl.Series(["5.3 (1.20)"]), sep = "commas")
+
+def test_restore_zeros():
+    assert restore_zeros(pl.Series([5.3, 4.25, 6.0])) == ["5.30", "4.25", "6.00"]
+    assert restore_zeros(pd.Series([5.3, 4.25]), width = 3) == ["5.300", "4.250"]
+    assert restore_zeros(pl.Series(["5.3", None, "4.125"])) == ["5.300", None, "4.125"]
+    with pytest.raises(TypeError):
+        restore_zeros(pl.Series([True, False]))
+
+def test_grim_map_restore_zeros():
+    df = pd.DataFrame({"x": [5.3, 5.27], "n": [15, 15]})
+    # 5.3 is GRIM-consistent for n = 15, but 5.30 is not
+    bools, errors = grim_map(df, "x", "n", silence_numeric_warning = True)
+    assert bools == [True, True]
+    bools, errors = grim_map(df, "x", "n", restore_zeros = True)
+    assert bools == [False, True]